@@ -1,6 +1,16 @@
 import Module from "./module.js";
 import { moduleRegistry } from "./registry.js";
 
+// Keys of the content map are relative to the site root, which is the parent
+// of this script's directory.
+const siteRoot = new URL("../", import.meta.url).pathname;
+
+function contentMapKey(target) {
+	return target.pathname.startsWith(siteRoot)
+		? target.pathname.slice(siteRoot.length)
+		: target.pathname.split("/").pop();
+}
+
 function createOrUseContainer(link, preview) {
 	let container = document.getElementById(`hover-${link}`);
 
@@ -32,7 +42,7 @@ function initLinkPreview(map) {
 	links.forEach((link) => {
 		link.addEventListener("mouseover", (event) => {
 			const target = event.target;
-			const link = target.pathname.split("/").pop();
+			const link = contentMapKey(target);
 			const preview = map[link];
 
 			if (!preview) return;
@@ -46,7 +56,7 @@ function initLinkPreview(map) {
 		});
 
 		link.addEventListener("mouseout", (event) => {
-			const link = event.target.pathname.split("/").pop();
+			const link = contentMapKey(event.target);
 			const container = document.getElementById(`hover-${link}`);
 
 			if (!container) return;
@@ -58,7 +68,7 @@ function initLinkPreview(map) {
 
 (async () => {
 	try {
-		const response = await fetch("./map.json");
+		const response = await fetch(new URL("../map.json", import.meta.url));
 		const map = await response.json();
 
 		const linkPreviewModule = new Module("link-preview", () => {
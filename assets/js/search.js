@@ -59,7 +59,7 @@ class SearchResult {
 			const link = document.createElement("a");
 			const filename = item[0];
 
-			link.href = filename;
+			link.href = new URL(`../${filename}`, import.meta.url);
 			link.textContent = filename;
 
 			link.addEventListener("mouseenter", () =>
@@ -174,7 +174,7 @@ function initSearch(map, input, output, preview, container) {
 
 (async () => {
 	try {
-		const response = await fetch("./map.json");
+		const response = await fetch(new URL("../map.json", import.meta.url));
 		const map = await response.json();
 
 		const input = document.getElementById("search-input");
@@ -1,88 +1,635 @@
+use rayon::ThreadPoolBuilder;
 use rayon::prelude::*;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{fs, io};
 
+use anyhow::Context as _;
+use serde::Serialize;
 use serde_json::json;
 use tera::{Context, Tera};
 
-use crate::content_map::ContentMap;
+use crate::content_map::{ContentMap, SHARD_DIR, SHARD_MANIFEST_FILE, shard_manifest, shard_paths};
+use crate::diagnostics::{Category, Diagnostic, Diagnostics};
+use crate::error::Error;
+use crate::feed::{
+    ATOM_FEED_FILE, FEED_FILE, JSON_FEED_FILE, OPML_FILE, TAG_FEED_DIR, absolute_url, write_feeds,
+};
+use crate::graph::{GRAPH_FILE, write_graph};
+use crate::hash::content_hash;
+use crate::images::write_image_variants;
+use crate::llms_txt::{LLMS_TXT_FILE, write_llms_txt};
 use crate::navigation::Navigation;
-use crate::post_note::PostNote;
-use crate::settings::Settings;
+use crate::pdf::write_pdfs;
+use crate::post_note::{InternalLink, MediaLink, PostNote, is_remote_url, media_output_paths};
+use crate::progress::Progress;
+use crate::retry;
+use crate::settings::{
+    BuildSettings, OversizedMediaPolicy, RetrySettings, Settings, ensure_directory,
+};
+use crate::single_page::{SINGLE_PAGE_FILE, write_single_page};
+use crate::sitemap::{ROBOTS_FILE, SITEMAP_FILE, write_sitemap};
+use crate::template_check::undefined_variables;
+use crate::template_helpers::{AssetManifest, register_filters, register_functions};
+
+/// Name of the template every note gets rendered with.
+const BASE_TEMPLATE: &str = "base.html";
+/// Minimal template used if the template directory doesn't provide a
+/// [BASE_TEMPLATE].
+const BUILTIN_BASE_TEMPLATE: &str = include_str!("builtin/base.html");
+/// Language of notes if neither the note nor the website declares one.
+const DEFAULT_LANG: &str = "en";
+/// Languages written from right to left.
+const RTL_LANGS: &[&str] = &["ar", "he", "fa"];
+/// Number of hex digits of the content hash used to fingerprint assets.
+const FINGERPRINT_LENGTH: usize = 8;
+/// File listing every written file, see [write_output_manifest].
+const OUTPUT_MANIFEST_FILE: &str = "manifest.json";
+/// File name of the content map, unless it's sharded.
+const CONTENT_MAP_FILE: &str = "map.json";
+/// File name of the tag list.
+const TAGS_FILE: &str = "tags.json";
 
 /// Builds the static site by rendering templates and copying assets.
 ///
 /// Steps:
-/// - Initializes the Tera template engine with HTML templates
 /// - Creates the output directory structure
-/// - Copies all static asset directories to output
-/// - Copies media files referenced in notes
-/// - Writes the content map index
-/// - Renders all notes using templates
+/// - Copies all static asset directories to output, optionally adding
+///   fingerprinted copies of the assets
+/// - Copies media files referenced in notes and the assets colocated with
+///   them
+/// - Writes the content map index (and optionally the tag list)
+/// - Writes the RSS and JSON feeds
+/// - Writes the sitemap and `robots.txt`, if enabled
+/// - Writes the `llms.txt`, if enabled
+/// - Writes the single page export of all notes, if enabled
+/// - Renders all notes using templates, with the custom filters and functions
+///   registered
+/// - Converts the rendered notes into PDFs, if a renderer is configured
+/// - Lists every written file in the `manifest.json`, if enabled
+/// - Applies the configured permissions to everything written
 ///
 /// # Errors
 ///
-/// Returns an error if template loading, directory creation, file copying, or rendering fails.
+/// Returns an error if directory creation, file copying, or rendering fails.
+/// Notes which fail to render don't stop the build, but it returns an
+/// [Error::RenderFailed] once everything else is written.
 pub fn build(
     notes: &[PostNote],
     content_map: ContentMap,
     navigation: Navigation,
+    tera: &Tera,
     settings: &Settings,
+    diagnostics: &Diagnostics,
 ) -> anyhow::Result<()> {
-    let template_pattern = format!("{}/**/*.html", settings.path.template.display());
-    let tera = Tera::new(&template_pattern)?;
+    let mut static_paths = Vec::new();
     for asset_path in &settings.path.assets {
-        copy_static_dir(asset_path, &settings.path.output)?;
+        ensure_directory(asset_path, "--assets", "path.assets")?;
+        copy_static_dir(
+            asset_path,
+            &settings.path.output,
+            &settings.build.retry,
+            &mut static_paths,
+        )?;
+    }
+    let asset_manifest = if settings.build.fingerprint_assets {
+        fingerprint_assets(
+            &settings.path.assets,
+            &settings.path.output,
+            &settings.build.retry,
+        )?
+    } else {
+        AssetManifest::new()
+    };
+    static_paths.extend(
+        asset_manifest
+            .values()
+            .map(|fingerprinted| settings.path.output.join(fingerprinted)),
+    );
+    let media_paths = copy_media_files(
+        notes,
+        &settings.path.input,
+        &settings.path.output,
+        &settings.build,
+        diagnostics,
+    )?;
+    write_image_variants(&media_paths, &settings.images, &settings.build.retry);
+    copy_colocated_assets(notes, &media_paths, &static_paths, settings)?;
+    if settings.content_map.shard_by_tag {
+        write_content_map_shards(
+            notes,
+            &content_map,
+            &settings.path.output,
+            &settings.build.retry,
+        )?;
+    } else {
+        write_content_map(content_map, &settings.path.output, &settings.build.retry)?;
+    }
+    if settings.build.emit_tags_json {
+        write_tags_json(&navigation, &settings.path.output, &settings.build.retry)?;
+    }
+    if settings.build.emit_graph_json {
+        write_graph(notes, &settings.path.output, &settings.build.retry)?;
+    }
+    write_feeds(notes, &navigation, settings, &settings.path.output)?;
+    write_sitemap(notes, settings, &settings.path.output)?;
+    if settings.llms_txt.enabled {
+        write_llms_txt(notes, &navigation, settings, &settings.path.output)?;
+    }
+    let mut tera = tera.clone();
+    register_filters(&mut tera);
+    register_functions(&mut tera, &settings.site, asset_manifest);
+    if settings.build.single_page {
+        write_single_page(notes, &mut tera, settings)?;
+    }
+    let failed = render_notes(notes, &navigation, &tera, settings, diagnostics)?;
+    write_pdfs(notes, &settings.pdf, &settings.path.output);
+    // Written last, so it covers everything written before.
+    if settings.build.emit_manifest {
+        write_output_manifest(&settings.path.output, &settings.build.retry)?;
+    }
+    apply_permissions(
+        &settings.path.output,
+        settings.build.file_mode.as_deref(),
+        settings.build.dir_mode.as_deref(),
+    )?;
+
+    if failed > 0 {
+        return Err(Error::RenderFailed { failed }.into());
     }
-    copy_media_files(notes, &settings.path.input, &settings.path.output)?;
-    write_content_map(content_map, &settings.path.output)?;
-    render_notes(notes, &navigation, &tera, &settings.path.output)?;
 
     Ok(())
 }
 
+/// Loads all HTML templates from the template directory.
+///
+/// If the directory doesn't contain a [BASE_TEMPLATE] the built-in template is
+/// used instead, as long as `builtin_fallback` is enabled.
+///
+/// # Errors
+///
+/// Returns an error if no [BASE_TEMPLATE] could be found and the fallback is
+/// disabled, or if the templates can't be parsed.
+pub fn load_templates(template_path: &Path, builtin_fallback: bool) -> anyhow::Result<Tera> {
+    if template_path.join(BASE_TEMPLATE).is_file() {
+        let template_pattern = format!("{}/**/*.html", template_path.display());
+        return Tera::new(&template_pattern).with_context(|| {
+            format!(
+                "Could not load the templates from {}",
+                template_path.display()
+            )
+        });
+    }
+
+    if !builtin_fallback {
+        anyhow::bail!(
+            "No templates found at {}; expected {}",
+            template_path.display(),
+            BASE_TEMPLATE
+        );
+    }
+
+    log::warn!(
+        "No {} found at {}, using the built-in template instead.",
+        BASE_TEMPLATE,
+        template_path.display()
+    );
+
+    let mut tera = Tera::default();
+    tera.add_raw_template(BASE_TEMPLATE, BUILTIN_BASE_TEMPLATE)?;
+
+    Ok(tera)
+}
+
+/// Replaces the templates with freshly loaded ones, e.g. after they were edited.
+///
+/// If loading fails (for example because of a syntax error) the error is
+/// logged and the previous templates are kept.
+pub fn reload_templates(tera: &mut Tera, template_path: &Path, builtin_fallback: bool) {
+    match load_templates(template_path, builtin_fallback) {
+        Ok(reloaded) => {
+            *tera = reloaded;
+            log::info!("Reloaded templates from {}", template_path.display());
+        }
+        Err(err) => log::error!("Keeping the previous templates, reloading failed: {err:#}"),
+    }
+}
+
+/// Renders all notes in parallel, except for notes larger than
+/// `large_note_size`: those are rendered one at a time afterwards and
+/// streamed to disk, so at most one of their pages is held in memory. Their
+/// html is still copied into the template context while rendering.
 fn render_notes(
     notes: &[PostNote],
     navigation: &Navigation,
     tera: &Tera,
-    output_path: &Path,
-) -> anyhow::Result<()> {
-    notes.par_iter().for_each(|note| {
-        let mut context = Context::new();
+    settings: &Settings,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<usize> {
+    let large_note_size = parse_size(&settings.build.large_note_size)?;
+    let (large_notes, notes): (Vec<&PostNote>, Vec<&PostNote>) = notes
+        .iter()
+        .partition(|note| note.html_content.len() > large_note_size as usize);
+    let total = notes.len() + large_notes.len();
+    let progress = Progress::new("Rendering notes", total, settings.build.progress);
+    let failed = AtomicUsize::new(0);
+    let featured = featured_notes(
+        notes.iter().chain(&large_notes).copied(),
+        &settings.site.featured,
+    );
+
+    let render = |note: &PostNote, stream: bool| {
+        // A panic while rendering a single note mustn't take down the whole build.
+        let rendered = panic::catch_unwind(AssertUnwindSafe(|| {
+            render_note(note, &featured, navigation, tera, settings, stream)
+        }));
+        let rendered = match rendered {
+            Ok(Ok(())) => true,
+            Ok(Err(err)) => {
+                if let Error::UndefinedVariables {
+                    template,
+                    variables,
+                    ..
+                } = &err
+                {
+                    for variable in variables {
+                        diagnostics.push(Diagnostic::new(
+                            Category::UndefinedVariable,
+                            format!("{template} reads the undefined variable {variable}"),
+                            &*note.file_name,
+                        ));
+                    }
+                }
+                log::error!("Rendering failed: {:#}", anyhow::Error::from(err));
+                false
+            }
+            Err(payload) => {
+                log::error!(
+                    "Rendering panicked for {:?}: {}",
+                    note.file_name,
+                    panic_message(payload.as_ref())
+                );
+                false
+            }
+        };
+
+        if !rendered {
+            failed.fetch_add(1, Ordering::Relaxed);
+        }
+        progress.inc();
+    };
+
+    notes.par_iter().for_each(|note| render(note, false));
+    for note in large_notes {
+        log::debug!("Rendering the large note {} on its own.", &*note.file_name);
+        render(note, true);
+    }
+    progress.finish();
+
+    let failed = failed.into_inner();
+    log::info!(
+        "Rendered {} of {} note(s), {} failed.",
+        total - failed,
+        total,
+        failed
+    );
+
+    Ok(failed)
+}
 
-        if let Err(err) = context.try_insert("note", note) {
-            log::error!("Failed to insert note for {:?}: {}", &note.file_name, err);
+/// A note pinned to the `featured` notes of the templates.
+#[derive(Debug, Clone, Serialize)]
+struct FeaturedNote<'a> {
+    link: &'a InternalLink,
+    title: &'a str,
+    description: &'a str,
+}
+
+/// The published notes listed in the settings, in their order, followed by
+/// the notes marked as `featured` ordered by their `featured_order`.
+fn featured_notes<'a>(
+    notes: impl Iterator<Item = &'a PostNote>,
+    names: &[String],
+) -> Vec<FeaturedNote<'a>> {
+    let listed = names
+        .iter()
+        .map(|name| InternalLink::from(name.to_owned()))
+        .collect::<Vec<InternalLink>>();
+    let position = |note: &PostNote| {
+        let source = InternalLink::try_from(note.source_path.clone()).ok()?;
+        listed
+            .iter()
+            .position(|link| link.target() == source.target())
+    };
+
+    let mut featured = notes
+        .filter(|note| note.is_published())
+        .filter_map(|note| match position(note) {
+            Some(position) => Some(((0, position as i64), note)),
+            None if note.properties.featured => Some((
+                (1, note.properties.featured_order.unwrap_or(i64::MAX)),
+                note,
+            )),
+            None => None,
+        })
+        .collect::<Vec<((u8, i64), &PostNote)>>();
+    featured.sort_by(|(a_key, a), (b_key, b)| {
+        a_key.cmp(b_key).then_with(|| a.file_name.cmp(&b.file_name))
+    });
+
+    featured
+        .into_iter()
+        .map(|(_, note)| FeaturedNote {
+            link: &note.file_name,
+            title: &note.properties.title,
+            description: &note.properties.description,
+        })
+        .collect()
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+/// Renders a single note.
+///
+/// With `stream` enabled the page is written to disk while rendering instead
+/// of being held in memory completely. With `strict_templates` enabled notes
+/// whose template reads undefined variables fail.
+fn render_note(
+    note: &PostNote,
+    featured: &[FeaturedNote],
+    navigation: &Navigation,
+    tera: &Tera,
+    settings: &Settings,
+    stream: bool,
+) -> Result<(), Error> {
+    let site = &settings.site;
+    let render_error = |source| Error::Render {
+        note: note.file_name.to_string(),
+        source,
+    };
+    let mut context = Context::new();
+
+    context.insert("site", site);
+    context.insert("root", &note.file_name.root_path(site.base_path.as_deref()));
+    context.insert("base_path", &site.base_path);
+    context.insert("image_url", &image_url(note, site.base_url.as_deref()));
+    let lang = note
+        .properties
+        .lang
+        .as_deref()
+        .or(site.lang.as_deref())
+        .unwrap_or(DEFAULT_LANG);
+    context.insert("lang", lang);
+    context.insert("dir", text_direction(lang));
+    context.insert("featured", featured);
+    context.try_insert("note", note).map_err(render_error)?;
+    context
+        .try_insert("navigation", navigation)
+        .map_err(render_error)?;
+
+    let path = settings.path.output.join(note.file_name.output_path());
+    if let Some(parent) = path.parent()
+        && let Err(err) = fs::create_dir_all(parent)
+    {
+        log::warn!("Could not create parent directory: {}", err);
+    }
+
+    let template = note_template(note, tera);
+    if settings.build.strict_templates {
+        let variables = undefined_variables(tera, template, &context);
+        if !variables.is_empty() {
+            return Err(Error::UndefinedVariables {
+                note: note.file_name.to_string(),
+                template: template.to_string(),
+                variables,
+            });
+        }
+    }
+    if stream {
+        stream_note(note, tera, template, &context, &path, &settings.build.retry)?;
+    } else {
+        let content = tera.render(template, &context).map_err(render_error)?;
+        retry::write(&settings.build.retry, &path, content).map_err(|source| Error::Io {
+            path: path.clone(),
+            source,
+        })?;
+    }
+    log::info!("Rendered: {}", path.display());
+
+    if settings.build.emit_note_json {
+        write_note_json(note, &path, &settings.build.retry);
+    }
+
+    Ok(())
+}
+
+/// The template set by the `template` front matter field (or a directory
+/// config), falling back to [BASE_TEMPLATE] if it isn't set or doesn't exist.
+fn note_template<'a>(note: &'a PostNote, tera: &Tera) -> &'a str {
+    let Some(template) = note
+        .properties
+        .extra
+        .get("template")
+        .and_then(serde_json::Value::as_str)
+    else {
+        return BASE_TEMPLATE;
+    };
+
+    if !tera.get_template_names().any(|name| name == template) {
+        log::warn!(
+            "The template {} of {} doesn't exist, using {} instead.",
+            template,
+            &*note.file_name,
+            BASE_TEMPLATE
+        );
+        return BASE_TEMPLATE;
+    }
+
+    template
+}
+
+/// Renders the page straight into a temporary file which replaces the page
+/// once rendering succeeded, so a failed render keeps the previous page.
+fn stream_note(
+    note: &PostNote,
+    tera: &Tera,
+    template: &str,
+    context: &Context,
+    path: &Path,
+    retry: &RetrySettings,
+) -> Result<(), Error> {
+    let partial_path = path.with_extension("html.partial");
+    let io_error = |source| Error::Io {
+        path: partial_path.clone(),
+        source,
+    };
+    let result = retry::with_retry(retry, || fs::File::create(&partial_path))
+        .map_err(io_error)
+        .and_then(|file| {
+            let mut writer = io::BufWriter::new(file);
+            tera.render_to(template, context, &mut writer)
+                .map_err(|source| Error::Render {
+                    note: note.file_name.to_string(),
+                    source,
+                })?;
+            writer
+                .into_inner()
+                .map_err(|err| io_error(err.into_error()))?;
+            retry::with_retry(retry, || fs::rename(&partial_path, path)).map_err(io_error)
+        });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&partial_path);
+    }
+
+    result
+}
+
+/// Writes the serialized note next to its rendered HTML page.
+fn write_note_json(note: &PostNote, html_path: &Path, retry: &RetrySettings) {
+    let path = html_path.with_extension("json");
+    let note_json = match serde_json::to_string(note) {
+        Ok(note_json) => note_json,
+        Err(err) => {
+            log::error!("Failed to serialize {:?}: {}", note.file_name, err);
             return;
         }
+    };
+
+    if let Err(err) = retry::write(retry, &path, note_json) {
+        log::error!("Writing failed for {}: {}", path.display(), err);
+    }
+}
+
+/// Resolves the cover image of a note to an absolute url (if a base url is
+/// configured), remote images are passed through unchanged.
+fn image_url(note: &PostNote, base_url: Option<&str>) -> Option<String> {
+    let image = note.properties.image.as_deref()?;
+
+    if is_remote_url(image) {
+        return Some(image.to_string());
+    }
+
+    Some(match base_url {
+        Some(base_url) => absolute_url(base_url, image),
+        None => format!("./{}", image),
+    })
+}
 
-        if let Err(err) = context.try_insert("navigation", navigation) {
-            log::error!(
-                "Failed to insert navigation for {:?}: {}",
-                &note.file_name,
-                err
+/// Text direction of the language, `rtl` or `ltr`. Regional variants like
+/// `ar-EG` are written in the direction of their language.
+fn text_direction(lang: &str) -> &'static str {
+    let language = lang.split(['-', '_']).next().unwrap_or(lang);
+
+    if RTL_LANGS
+        .iter()
+        .any(|rtl| rtl.eq_ignore_ascii_case(language))
+    {
+        "rtl"
+    } else {
+        "ltr"
+    }
+}
+
+/// Copies the files colocated with the notes, see
+/// [crate::post_note::collect_colocated_assets].
+///
+/// # Errors
+///
+/// Returns an error if a file can't be copied or if its copy would replace a
+/// rendered note, a copied media file or static asset, a file generated by
+/// the build or the copy of another colocated asset.
+fn copy_colocated_assets(
+    notes: &[PostNote],
+    media_paths: &[PathBuf],
+    static_paths: &[PathBuf],
+    settings: &Settings,
+) -> anyhow::Result<()> {
+    let output_path = &settings.path.output;
+    let generated = generated_outputs(settings);
+    let pages = notes
+        .iter()
+        .map(|note| note.file_name.output_path())
+        .collect::<HashSet<PathBuf>>();
+    let mut copied = HashMap::<&str, &Path>::new();
+    for asset in notes.iter().flat_map(|note| &note.colocated_assets) {
+        if let Some(source) = copied.insert(&asset.output, &asset.source) {
+            anyhow::bail!(
+                "The colocated assets {} and {} would both be copied to {}",
+                source.display(),
+                asset.source.display(),
+                asset.output
             );
-            return;
         }
 
-        let content = match tera.render("base.html", &context) {
-            Ok(content) => content,
-            Err(err) => {
-                log::error!("Rendering failed for {:?}: {}", note.file_name, err);
-                return;
-            }
-        };
+        let output = Path::new(&asset.output);
+        let to = output_path.join(output);
+        let is_generated = generated
+            .iter()
+            .any(|generated| output.starts_with(generated));
+        if pages.contains(output) || is_generated || media_paths.contains(&to) {
+            anyhow::bail!(
+                "The colocated asset {} would replace the generated {}, rename it",
+                asset.source.display(),
+                asset.output
+            );
+        }
+        if static_paths.contains(&to) {
+            anyhow::bail!(
+                "The colocated asset {} would replace the static asset {}, rename it",
+                asset.source.display(),
+                asset.output
+            );
+        }
 
-        let path = output_path.join(note.file_name.to_string());
-        if let Err(err) = fs::write(&path, content) {
-            log::error!("Writing failed for {}: {}", path.display(), err);
-        } else {
-            log::info!("Rendered: {}", path.display());
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
         }
-    });
+        retry::copy(&settings.build.retry, &asset.source, &to).with_context(|| {
+            format!(
+                "Could not copy the colocated asset {}",
+                asset.source.display()
+            )
+        })?;
+        log::debug!("Copied the colocated asset to {}", to.display());
+    }
 
     Ok(())
 }
 
+/// Files and directories the build writes besides the notes with the given
+/// settings, which a colocated asset must not replace.
+fn generated_outputs(settings: &Settings) -> Vec<&'static str> {
+    [
+        (settings.build.emit_manifest, OUTPUT_MANIFEST_FILE),
+        (!settings.content_map.shard_by_tag, CONTENT_MAP_FILE),
+        (settings.content_map.shard_by_tag, SHARD_DIR),
+        (settings.build.emit_tags_json, TAGS_FILE),
+        (settings.feed.enabled, FEED_FILE),
+        (settings.feed.json, JSON_FEED_FILE),
+        (settings.feed.atom, ATOM_FEED_FILE),
+        (settings.feed.per_tag, TAG_FEED_DIR),
+        (settings.feed.per_tag && settings.feed.opml, OPML_FILE),
+        (settings.sitemap.enabled, SITEMAP_FILE),
+        (settings.robots.enabled, ROBOTS_FILE),
+        (settings.build.emit_graph_json, GRAPH_FILE),
+        (settings.llms_txt.enabled, LLMS_TXT_FILE),
+        (settings.build.single_page, SINGLE_PAGE_FILE),
+    ]
+    .into_iter()
+    .filter_map(|(enabled, output)| enabled.then_some(output))
+    .collect()
+}
+
 /// Recursively copies a directory tree from source to destination.
 ///
 /// Creates the destination directory if it doesn't exist. For each entry in the source:
@@ -90,11 +637,17 @@ fn render_notes(
 /// - Files are copied directly
 ///
 /// If destination already exists, contents are merged (existing files are overwritten).
+/// The path of every copy is added to `copied`.
 ///
 /// # Errors
 ///
 /// Returns an error if any filesystem operation fails (reading, creating directories, copying).
-fn copy_static_dir(from: &Path, to: &Path) -> io::Result<()> {
+fn copy_static_dir(
+    from: &Path,
+    to: &Path,
+    retry: &RetrySettings,
+    copied: &mut Vec<PathBuf>,
+) -> io::Result<()> {
     // Ensure the destination directory exists before copying contents.
     fs::create_dir_all(to)?;
     // Iterate through all entries in the source directory.
@@ -104,46 +657,1379 @@ fn copy_static_dir(from: &Path, to: &Path) -> io::Result<()> {
         let to = to.join(entry.file_name());
         if entry.file_type()?.is_dir() {
             // Recursively copy subdirectories.
-            copy_static_dir(&from, &to)?;
+            copy_static_dir(&from, &to, retry, copied)?;
         } else {
-            fs::copy(&from, &to)?;
+            retry::copy(retry, &from, &to)?;
+            copied.push(to);
         }
     }
 
     Ok(())
 }
 
-fn copy_media_files(notes: &[PostNote], src: &Path, destination: &Path) -> anyhow::Result<()> {
-    fs::create_dir_all(destination)?;
-    notes.par_iter().for_each(|note| {
-        note.media_links.par_iter().for_each(|media_link| {
-            let media_path = PathBuf::from(media_link.to_string());
-            let output_media_path = PathBuf::from(media_link.to_string());
-            if let Some(parent) = media_path.parent()
-                && let Err(err) = fs::create_dir_all(destination.join(parent))
-            {
-                log::warn!("Could not create parent directory: {}", err);
+/// Writes a copy of every asset with a hash of its content in the file name,
+/// e.g. `css/style.1a2b3c4d.css` next to `css/style.css`, so they can be
+/// cached indefinitely.
+///
+/// # Errors
+///
+/// Returns an error if an asset can't be read or its copy can't be written.
+fn fingerprint_assets(
+    asset_paths: &[PathBuf],
+    output_path: &Path,
+    retry: &RetrySettings,
+) -> io::Result<AssetManifest> {
+    fn visit(
+        directory: &Path,
+        asset_path: &Path,
+        output_path: &Path,
+        manifest: &mut AssetManifest,
+        retry: &RetrySettings,
+    ) -> io::Result<()> {
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                visit(&path, asset_path, output_path, manifest, retry)?;
+                continue;
+            }
+
+            let content = fs::read(&path)?;
+            let hash = &content_hash(&content)[..FINGERPRINT_LENGTH];
+            let relative = path.strip_prefix(asset_path).unwrap_or(&path);
+            let fingerprinted = match (relative.file_stem(), relative.extension()) {
+                (Some(stem), Some(extension)) => relative.with_file_name(format!(
+                    "{}.{hash}.{}",
+                    stem.to_string_lossy(),
+                    extension.to_string_lossy()
+                )),
+                _ => relative.with_file_name(format!(
+                    "{}.{hash}",
+                    relative.file_name().unwrap_or_default().to_string_lossy()
+                )),
             };
-            if let Err(err) = fs::copy(src.join(&media_path), destination.join(&output_media_path))
-            {
-                log::warn!(
-                    "Could not copy file {:?} into output directory: {}",
-                    &src.join(&media_path),
-                    err
-                );
+
+            retry::write(retry, output_path.join(&fingerprinted), content)?;
+            manifest.insert(
+                relative.to_string_lossy().replace('\\', "/"),
+                fingerprinted.to_string_lossy().replace('\\', "/"),
+            );
+        }
+
+        Ok(())
+    }
+
+    let mut manifest = AssetManifest::new();
+    for asset_path in asset_paths {
+        visit(asset_path, asset_path, output_path, &mut manifest, retry)?;
+    }
+    log::info!("Fingerprinted {} asset(s).", manifest.len());
+
+    Ok(manifest)
+}
+
+/// Writes the `manifest.json` listing every file within the output directory
+/// with its path, size in bytes and content hash, sorted by path, so builds
+/// can be verified and compared.
+///
+/// # Errors
+///
+/// Returns an error if a file can't be read or the manifest can't be written.
+fn write_output_manifest(output_path: &Path, retry: &RetrySettings) -> anyhow::Result<()> {
+    fn visit(
+        directory: &Path,
+        output_path: &Path,
+        files: &mut Vec<serde_json::Value>,
+    ) -> io::Result<()> {
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                visit(&path, output_path, files)?;
+                continue;
             }
-        })
-    });
+
+            let relative = path
+                .strip_prefix(output_path)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            if relative == OUTPUT_MANIFEST_FILE {
+                continue;
+            }
+            let content = fs::read(&path)?;
+            files.push(json!({
+                "path": relative,
+                "size": content.len(),
+                "hash": content_hash(&content),
+            }));
+        }
+
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    visit(output_path, output_path, &mut files)
+        .with_context(|| format!("Could not list the files in {}", output_path.display()))?;
+    files.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+
+    let path = output_path.join(OUTPUT_MANIFEST_FILE);
+    retry::write(
+        retry,
+        &path,
+        serde_json::to_string_pretty(&json!({ "files": files }))?,
+    )?;
+    log::info!(
+        "Created the manifest of {} file(s) at: {}",
+        files.len(),
+        path.display()
+    );
 
     Ok(())
 }
 
-fn write_content_map(content_map: ContentMap, output_path: &Path) -> anyhow::Result<()> {
+/// Parses an octal permission string like `644`, `0644` or `0o644`.
+fn parse_mode(mode: &str) -> anyhow::Result<u32> {
+    let digits = mode.trim().trim_start_matches("0o");
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .with_context(|| format!("Invalid permissions {mode:?}, expected an octal mode like 644"))
+}
+
+/// Recursively applies the permissions to all files and directories below
+/// (and including) the output directory.
+///
+/// # Errors
+///
+/// Returns an error if a mode is invalid or the permissions can't be set.
+#[cfg(unix)]
+fn apply_permissions(
+    output_path: &Path,
+    file_mode: Option<&str>,
+    dir_mode: Option<&str>,
+) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fn apply(path: &Path, file_mode: Option<u32>, dir_mode: Option<u32>) -> io::Result<()> {
+        if path.is_dir() {
+            for entry in fs::read_dir(path)? {
+                apply(&entry?.path(), file_mode, dir_mode)?;
+            }
+            if let Some(mode) = dir_mode {
+                fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+            }
+        } else if let Some(mode) = file_mode {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+
+        Ok(())
+    }
+
+    let file_mode = file_mode.map(parse_mode).transpose()?;
+    let dir_mode = dir_mode.map(parse_mode).transpose()?;
+    if file_mode.is_none() && dir_mode.is_none() {
+        return Ok(());
+    }
+
+    apply(output_path, file_mode, dir_mode)
+        .with_context(|| format!("Could not set permissions in {}", output_path.display()))
+}
+
+#[cfg(not(unix))]
+fn apply_permissions(
+    _output_path: &Path,
+    file_mode: Option<&str>,
+    dir_mode: Option<&str>,
+) -> anyhow::Result<()> {
+    if file_mode.is_some() || dir_mode.is_some() {
+        log::warn!(
+            "Ignoring `build.file_mode` and `build.dir_mode`, they are only supported on unix."
+        );
+    }
+
+    Ok(())
+}
+
+/// Copies all media files referenced by the notes, using a dedicated pool of
+/// `io_threads` threads. Files larger than `max_media_size` are skipped, just
+/// like files whose copy is up to date unless `force` is enabled. Returns the
+/// paths of the copies, including the ones which were already up to date.
+///
+/// # Errors
+///
+/// Returns an error if `max_media_size` is invalid or if files were skipped
+/// and the [OversizedMediaPolicy] is [OversizedMediaPolicy::Fail].
+fn copy_media_files(
+    notes: &[PostNote],
+    src: &Path,
+    destination: &Path,
+    settings: &BuildSettings,
+    diagnostics: &Diagnostics,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let max_size = settings
+        .max_media_size
+        .as_deref()
+        .map(parse_size)
+        .transpose()?;
+    fs::create_dir_all(destination)?;
+
+    // Every file is copied once, even if several notes embed it, so no two
+    // threads write the same destination.
+    let mut seen = HashSet::new();
+    let media_links = notes
+        .iter()
+        .flat_map(|note| note.media_links.iter().map(move |link| (note, link)))
+        .filter(|(_, link)| seen.insert(&***link))
+        .collect::<Vec<(&PostNote, &MediaLink)>>();
+    let output_paths = settings
+        .media_output_dir
+        .as_deref()
+        .map(|media_dir| media_output_paths(notes, media_dir))
+        .unwrap_or_default();
+    let oversized = AtomicUsize::new(0);
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(settings.io_threads.max(1))
+        .thread_name(|index| format!("media-copy-{index}"))
+        .build()?;
+
+    let copied = pool.install(|| {
+        media_links
+            .into_par_iter()
+            .filter_map(|(note, media_link)| {
+                // Media links hold the decoded path, so it's used for both the
+                // source and the output file (unless the media files are
+                // collected in one directory); only the link in the html is
+                // encoded.
+                let media_path = Path::new(&**media_link);
+                if let Some(max_size) = max_size
+                    && let Ok(metadata) = fs::metadata(src.join(media_path))
+                    && metadata.len() > max_size
+                {
+                    log::warn!(
+                        "Skipping {}, it is larger than the maximum media size of {} bytes.",
+                        media_path.display(),
+                        max_size
+                    );
+                    diagnostics.push(Diagnostic::new(
+                        Category::OversizedMedia,
+                        format!(
+                            "{} has {} bytes, the maximum is {} bytes",
+                            media_path.display(),
+                            metadata.len(),
+                            max_size
+                        ),
+                        &*note.file_name,
+                    ));
+                    oversized.fetch_add(1, Ordering::Relaxed);
+                    return None;
+                }
+                let output_path = output_paths
+                    .get(&**media_link)
+                    .map_or(media_path, Path::new);
+                if let Some(parent) = output_path.parent()
+                    && let Err(err) = fs::create_dir_all(destination.join(parent))
+                {
+                    log::warn!("Could not create parent directory: {}", err);
+                };
+                let (from, to) = (src.join(media_path), destination.join(output_path));
+                if !settings.force && is_up_to_date(&from, &to) {
+                    log::debug!("Skipping {}, it is up to date.", media_path.display());
+                    return Some(to);
+                }
+                if let Err(err) = retry::with_retry(&settings.retry, || copy_file(&from, &to)) {
+                    log::warn!(
+                        "Could not copy file {:?} into output directory: {}",
+                        &src.join(media_path),
+                        err
+                    );
+                    diagnostics.push(Diagnostic::new(
+                        Category::MissingMedia,
+                        format!("Could not copy {}: {}", media_path.display(), err),
+                        &*note.file_name,
+                    ));
+                    return None;
+                }
+
+                Some(to)
+            })
+            .collect::<Vec<PathBuf>>()
+    });
+
+    let oversized = oversized.into_inner();
+    if oversized > 0 && settings.oversized_media == OversizedMediaPolicy::Fail {
+        anyhow::bail!(
+            "Found {oversized} media file(s) larger than {}",
+            settings.max_media_size.as_deref().unwrap_or_default()
+        );
+    }
+
+    Ok(copied)
+}
+
+/// Whether the destination is a copy of the source with the same size and
+/// modification time, as left behind by [copy_file].
+fn is_up_to_date(source: &Path, destination: &Path) -> bool {
+    let (Ok(source), Ok(destination)) = (fs::metadata(source), fs::metadata(destination)) else {
+        return false;
+    };
+
+    source.len() == destination.len()
+        && matches!(
+            (source.modified(), destination.modified()),
+            (Ok(source), Ok(destination)) if source == destination
+        )
+}
+
+/// Copies the file and carries over its modification time, so
+/// [is_up_to_date] recognizes the copy on the next build.
+fn copy_file(source: &Path, destination: &Path) -> io::Result<()> {
+    fs::copy(source, destination)?;
+    let modified = fs::metadata(source)?.modified()?;
+    fs::File::options()
+        .write(true)
+        .open(destination)?
+        .set_modified(modified)
+}
+
+/// Parses a size like `1024`, `10MB` or `512 KiB` into bytes. Units without
+/// an `i` are powers of 1000, units with one powers of 1024.
+fn parse_size(size: &str) -> anyhow::Result<u64> {
+    let size = size.trim();
+    let unit_start = size
+        .find(|char: char| !char.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(unit_start);
+    let multiplier = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1000,
+        "mb" => 1000u64.pow(2),
+        "gb" => 1000u64.pow(3),
+        "kib" => 1024,
+        "mib" => 1024u64.pow(2),
+        "gib" => 1024u64.pow(3),
+        _ => anyhow::bail!("Invalid size {size:?}, expected a size like 10MB"),
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .with_context(|| format!("Invalid size {size:?}, expected a size like 10MB"))
+}
+
+fn write_content_map(
+    content_map: ContentMap,
+    output_path: &Path,
+    retry: &RetrySettings,
+) -> anyhow::Result<()> {
     let map_json = serde_json::to_string(&json!(content_map))?;
-    let path = output_path.join("map.json");
+    let path = output_path.join(CONTENT_MAP_FILE);
 
-    fs::write(&path, map_json)?;
+    retry::write(retry, &path, map_json)?;
     log::info!("Created the content map at: {}", path.display());
 
     Ok(())
 }
+
+fn write_content_map_shards(
+    notes: &[PostNote],
+    content_map: &ContentMap,
+    output_path: &Path,
+    retry: &RetrySettings,
+) -> anyhow::Result<()> {
+    let shards = content_map.shards(notes);
+    let paths = shard_paths(&shards);
+    fs::create_dir_all(output_path.join(SHARD_DIR))?;
+
+    for (tag, shard) in &shards {
+        let path = output_path.join(&paths[tag]);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        retry::write(retry, &path, serde_json::to_string(&json!(shard))?)?;
+    }
+    let path = output_path.join(SHARD_DIR).join(SHARD_MANIFEST_FILE);
+    retry::write(
+        retry,
+        &path,
+        serde_json::to_string(&shard_manifest(&shards))?,
+    )?;
+    log::info!(
+        "Created {} content map shard(s) at: {}",
+        shards.len(),
+        output_path.join(SHARD_DIR).display()
+    );
+
+    Ok(())
+}
+
+fn write_tags_json(
+    navigation: &Navigation,
+    output_path: &Path,
+    retry: &RetrySettings,
+) -> anyhow::Result<()> {
+    let tags = navigation
+        .flat_tags()
+        .into_iter()
+        .map(|(tag, count)| json!({ "tag": tag, "count": count }))
+        .collect::<Vec<serde_json::Value>>();
+    let path = output_path.join(TAGS_FILE);
+
+    retry::write(retry, &path, serde_json::to_string(&tags)?)?;
+    log::info!("Created the tag list at: {}", path.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post_note::{find_home_note, parse_public, relocate_media, relocate_notes};
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_missing_templates_without_fallback() {
+        let template_dir = tempfile::tempdir().unwrap();
+        let missing = template_dir.path().join("missing");
+
+        let err = load_templates(&missing, false).unwrap_err();
+
+        assert_eq!(
+            format!(
+                "No templates found at {}; expected base.html",
+                missing.display()
+            ),
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_missing_templates_with_builtin_fallback() {
+        let template_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        let notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Fallback\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nContent",
+        )];
+
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+
+        let tera = load_templates(&template_dir.path().join("missing"), true).unwrap();
+        render_notes(
+            &notes,
+            &Navigation::from(&notes),
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        let produced = fs::read_to_string(output_dir.path().join("note.html")).unwrap();
+        assert!(produced.contains("<h1>Fallback</h1>"));
+        assert!(produced.contains("<p>Content</p>"));
+    }
+
+    #[test]
+    fn test_reload_templates_after_edit() {
+        let template_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        let base_template = template_dir.path().join("base.html");
+        fs::write(&base_template, "<h1>{{ note.properties.title }}</h1>").unwrap();
+        let notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Reload\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+        )];
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+        let navigation = Navigation::from(&notes);
+        let mut tera = load_templates(template_dir.path(), false).unwrap();
+
+        fs::write(&base_template, "<h2>{{ note.properties.title }}</h2>").unwrap();
+        reload_templates(&mut tera, template_dir.path(), false);
+        render_notes(
+            &notes,
+            &navigation,
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        let produced = fs::read_to_string(output_dir.path().join("note.html")).unwrap();
+        assert_eq!("<h2>Reload</h2>", produced);
+
+        fs::write(&base_template, "<h2>{{ note.properties.title </h2>").unwrap();
+        reload_templates(&mut tera, template_dir.path(), false);
+        render_notes(
+            &notes,
+            &navigation,
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        let produced = fs::read_to_string(output_dir.path().join("note.html")).unwrap();
+        assert_eq!("<h2>Reload</h2>", produced);
+    }
+
+    #[test]
+    fn test_home_note_is_rendered_as_index() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let mut notes = vec![
+            parse_public(
+                "home.md",
+                "---\ntitle: Home\ndescription: Description\ncategory: pages\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nWelcome home",
+            ),
+            parse_public(
+                "other.md",
+                "---\ntitle: Other\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nBack [[home]]",
+            ),
+        ];
+        let home_note = find_home_note(&notes, "home.md").unwrap();
+        relocate_notes(&mut notes, true, false, None, Some(&home_note));
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+
+        let tera = load_templates(&output_dir.path().join("missing"), true).unwrap();
+        render_notes(
+            &notes,
+            &Navigation::from(&notes),
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        assert!(
+            fs::read_to_string(output_dir.path().join("index.html"))
+                .unwrap()
+                .contains("Welcome home")
+        );
+        assert!(!output_dir.path().join("pages/home.html").exists());
+        assert!(
+            fs::read_to_string(output_dir.path().join("other.html"))
+                .unwrap()
+                .contains("<a href=\"./index.html\" data-wikilink=\"true\">home</a>")
+        );
+    }
+
+    #[test]
+    fn test_home_note_must_exist_without_conflicts() {
+        let note = |file_name: &str| {
+            parse_public(
+                file_name,
+                "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+            )
+        };
+
+        assert!(find_home_note(&[note("home.md")], "missing").is_err());
+        assert!(find_home_note(&[note("home.md"), note("index.md")], "home").is_err());
+        assert_eq!(
+            "index.html",
+            &*find_home_note(&[note("index.md")], "index.md").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_categorized_note_is_rendered_in_category_directory() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let mut notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Guide\ndescription: Description\ncategory: guides\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nContent",
+        )];
+        relocate_notes(&mut notes, true, false, None, None);
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+
+        let tera = load_templates(&output_dir.path().join("missing"), true).unwrap();
+        render_notes(
+            &notes,
+            &Navigation::from(&notes),
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        assert!(output_dir.path().join("guides/note.html").is_file());
+        assert!(!output_dir.path().join("note.html").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rendered_file_carries_configured_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let output_dir = tempfile::tempdir().unwrap();
+        let notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Mode\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+        )];
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+
+        let tera = load_templates(&output_dir.path().join("missing"), true).unwrap();
+        render_notes(
+            &notes,
+            &Navigation::from(&notes),
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+        apply_permissions(output_dir.path(), Some("0640"), Some("750")).unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(0o640, mode(&output_dir.path().join("note.html")));
+        assert_eq!(0o750, mode(output_dir.path()));
+        assert!(apply_permissions(output_dir.path(), Some("999"), None).is_err());
+    }
+
+    #[test]
+    fn test_tags_json_lists_each_tag_once() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let notes = vec![
+            parse_public(
+                "a.md",
+                "---\ntitle: A\ndescription: Description\ntags: [rust, Rust, blog]\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+            ),
+            parse_public(
+                "b.md",
+                "---\ntitle: B\ndescription: Description\ntags: [rust]\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+            ),
+        ];
+
+        write_tags_json(
+            &Navigation::from(&notes),
+            output_dir.path(),
+            &RetrySettings::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            r#"[{"count":1,"tag":"blog"},{"count":2,"tag":"rust"}]"#,
+            fs::read_to_string(output_dir.path().join("tags.json")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_wikilinked_pdf_is_copied() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        fs::write(input_dir.path().join("report.pdf"), "report").unwrap();
+        let notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n[[report.pdf]]",
+        )];
+
+        copy_media_files(
+            &notes,
+            input_dir.path(),
+            output_dir.path(),
+            &BuildSettings {
+                io_threads: 1,
+                ..Default::default()
+            },
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        assert!(notes[0].internal_links.is_empty());
+        assert_eq!(
+            "report",
+            fs::read_to_string(output_dir.path().join("report.pdf")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_media_copy_with_single_io_thread() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(input_dir.path().join("media")).unwrap();
+        let notes = (0..8)
+            .map(|index| {
+                fs::write(input_dir.path().join(format!("media/{index}.png")), "media").unwrap();
+                parse_public(
+                    &format!("{index}.md"),
+                    &format!(
+                        "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n![[media/{index}.png]]"
+                    ),
+                )
+            })
+            .collect::<Vec<PostNote>>();
+
+        copy_media_files(
+            &notes,
+            input_dir.path(),
+            output_dir.path(),
+            &BuildSettings {
+                io_threads: 1,
+                ..Default::default()
+            },
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        for index in 0..8 {
+            assert!(
+                output_dir
+                    .path()
+                    .join(format!("media/{index}.png"))
+                    .is_file()
+            );
+        }
+    }
+
+    #[test]
+    fn test_unchanged_media_is_not_copied_again() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(input_dir.path().join("media")).unwrap();
+        fs::write(input_dir.path().join("media/cover.png"), "media").unwrap();
+        let notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n![[media/cover.png]]",
+        )];
+        let mut settings = BuildSettings::default();
+        let copy = |settings: &BuildSettings| {
+            copy_media_files(
+                &notes,
+                input_dir.path(),
+                output_dir.path(),
+                settings,
+                &Diagnostics::default(),
+            )
+            .unwrap();
+        };
+        let source = input_dir.path().join("media/cover.png");
+        let copied = output_dir.path().join("media/cover.png");
+
+        copy(&settings);
+        let modified = fs::metadata(&source).unwrap().modified().unwrap();
+        assert_eq!(modified, fs::metadata(&copied).unwrap().modified().unwrap());
+
+        // A copy with the same size and modification time counts as up to date.
+        fs::write(&copied, "MEDIA").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&copied)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        copy(&settings);
+        assert_eq!("MEDIA", fs::read_to_string(&copied).unwrap());
+
+        settings.force = true;
+        copy(&settings);
+        assert_eq!("media", fs::read_to_string(&copied).unwrap());
+    }
+
+    #[test]
+    fn test_media_is_collected_in_media_output_dir() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        for folder in ["a", "b", "media"] {
+            fs::create_dir_all(input_dir.path().join(folder)).unwrap();
+        }
+        fs::write(input_dir.path().join("a/cover.png"), "a").unwrap();
+        fs::write(input_dir.path().join("b/cover.png"), "b").unwrap();
+        fs::write(input_dir.path().join("media/logo.png"), "logo").unwrap();
+        fs::write(input_dir.path().join("media/chart a.png"), "chart").unwrap();
+        fs::write(input_dir.path().join("media/chart b.png"), "chart").unwrap();
+        let mut notes = ["a", "b"]
+            .map(|folder| {
+                parse_public(
+                    &format!("{folder}.md"),
+                    &format!(
+                        "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n[[{folder}/cover.png]] ![[media/logo.png]] ![Chart](media/chart%20{folder}.png)"
+                    ),
+                )
+            })
+            .to_vec();
+        let settings = BuildSettings {
+            media_output_dir: Some("assets/media".to_string()),
+            ..Default::default()
+        };
+
+        relocate_media(&mut notes, "assets/media");
+        copy_media_files(
+            &notes,
+            input_dir.path(),
+            output_dir.path(),
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        let hash = |path: &str| content_hash(path.as_bytes())[..8].to_string();
+        let media_dir = output_dir.path().join("assets/media");
+        assert_eq!(
+            "a",
+            fs::read_to_string(media_dir.join(format!("cover.{}.png", hash("a/cover.png"))))
+                .unwrap()
+        );
+        assert_eq!(
+            "b",
+            fs::read_to_string(media_dir.join(format!("cover.{}.png", hash("b/cover.png"))))
+                .unwrap()
+        );
+        assert_eq!(
+            "logo",
+            fs::read_to_string(media_dir.join("logo.png")).unwrap()
+        );
+        assert!(notes[1].html_content.contains(&format!(
+            "href=\"./assets/media/cover.{}.png\"",
+            hash("b/cover.png")
+        )));
+        assert!(
+            notes[1]
+                .html_content
+                .contains("src=\"./assets/media/logo.png\"")
+        );
+        assert_eq!(
+            "chart",
+            fs::read_to_string(media_dir.join("chart a.png")).unwrap()
+        );
+        assert!(
+            notes[0]
+                .html_content
+                .contains("src=\"./assets/media/chart%20a.png\" alt=\"Chart\"")
+        );
+    }
+
+    #[test]
+    fn test_oversized_media_is_skipped() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(input_dir.path().join("media")).unwrap();
+        fs::write(input_dir.path().join("media/small.png"), "small").unwrap();
+        fs::write(input_dir.path().join("media/large.png"), "large media").unwrap();
+        let notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n![[media/small.png]] ![[media/large.png]]",
+        )];
+        let mut settings = BuildSettings {
+            max_media_size: Some("10B".to_string()),
+            ..Default::default()
+        };
+        let diagnostics = Diagnostics::default();
+
+        copy_media_files(
+            &notes,
+            input_dir.path(),
+            output_dir.path(),
+            &settings,
+            &diagnostics,
+        )
+        .unwrap();
+
+        assert!(output_dir.path().join("media/small.png").is_file());
+        assert!(!output_dir.path().join("media/large.png").exists());
+        diagnostics.write(output_dir.path()).unwrap();
+        let produced: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(output_dir.path().join("diagnostics.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            json!([{
+                "category": "oversized_media",
+                "message": "media/large.png has 11 bytes, the maximum is 10 bytes",
+                "source": "note.html",
+            }]),
+            produced
+        );
+
+        settings.oversized_media = OversizedMediaPolicy::Fail;
+        assert!(
+            copy_media_files(
+                &notes,
+                input_dir.path(),
+                output_dir.path(),
+                &settings,
+                &Diagnostics::default(),
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(1024, parse_size("1024").unwrap());
+        assert_eq!(10_000_000, parse_size("10MB").unwrap());
+        assert_eq!(512 * 1024, parse_size("512 KiB").unwrap());
+        assert!(parse_size("10 parsecs").is_err());
+    }
+
+    #[test]
+    fn test_large_notes_are_streamed_to_disk() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let body = "Some long text.\n\n".repeat(100);
+        let notes = ["small", "large"]
+            .map(|name| {
+                let body = if name == "large" { body.as_str() } else { "" };
+                parse_public(
+                    &format!("{name}.md"),
+                    &format!(
+                        "---\ntitle: {name}\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n{body}"
+                    ),
+                )
+            })
+            .to_vec();
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+        settings.build.large_note_size = "1KB".to_string();
+        let mut tera = Tera::default();
+        tera.add_raw_template(BASE_TEMPLATE, "{{ note.html_content | safe }}")
+            .unwrap();
+
+        let failed = render_notes(
+            &notes,
+            &Navigation::from(&notes),
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        assert_eq!(0, failed);
+        assert_eq!(
+            &*notes[1].html_content,
+            fs::read_to_string(output_dir.path().join("large.html")).unwrap()
+        );
+        assert!(output_dir.path().join("small.html").is_file());
+        assert!(!output_dir.path().join("large.html.partial").exists());
+    }
+
+    #[test]
+    fn test_featured_notes_in_context() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let notes = [
+            ("pinned", ""),
+            ("second", "featured: true\nfeatured_order: 2\n"),
+            ("first", "featured: true\nfeatured_order: 1\n"),
+            ("other", ""),
+        ]
+        .map(|(name, featured)| {
+            parse_public(
+                &format!("{name}.md"),
+                &format!(
+                    "---\ntitle: {name}\ndescription: Description\ntags: []\n{featured}created: 2025-05-23T13:35\npublic: true\n---\n"
+                ),
+            )
+        })
+        .to_vec();
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+        settings.site.featured = vec!["Pinned".to_string()];
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            BASE_TEMPLATE,
+            "{% for featured_note in featured %}{{ featured_note.link }} {% endfor %}",
+        )
+        .unwrap();
+
+        render_notes(
+            &notes,
+            &Navigation::from(&notes),
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            "pinned.html first.html second.html ",
+            fs::read_to_string(output_dir.path().join("other.html")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_lang_and_dir_in_context() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let notes = [("arabic", "lang: ar-EG\n"), ("english", "")]
+            .map(|(name, lang)| {
+                parse_public(
+                    &format!("{name}.md"),
+                    &format!(
+                        "---\ntitle: {name}\ndescription: Description\ntags: []\n{lang}created: 2025-05-23T13:35\npublic: true\n---\n"
+                    ),
+                )
+            })
+            .to_vec();
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+        let mut tera = Tera::default();
+        tera.add_raw_template(BASE_TEMPLATE, "{{ lang }} {{ dir }}")
+            .unwrap();
+
+        let render = |settings: &Settings| {
+            render_notes(
+                &notes,
+                &Navigation::from(&notes),
+                &tera,
+                settings,
+                &Diagnostics::default(),
+            )
+            .unwrap();
+            ["arabic.html", "english.html"]
+                .map(|file| fs::read_to_string(output_dir.path().join(file)).unwrap())
+        };
+
+        assert_eq!(["ar-EG rtl", "en ltr"], render(&settings));
+        settings.site.lang = Some("he".to_string());
+        assert_eq!(["ar-EG rtl", "he rtl"], render(&settings));
+    }
+
+    #[test]
+    fn test_note_template_from_front_matter() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let notes = [("entry", "journal.html"), ("other", "missing.html")]
+            .map(|(name, template)| {
+                parse_public(
+                    &format!("{name}.md"),
+                    &format!(
+                        "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\ntemplate: {template}\n---\n"
+                    ),
+                )
+            })
+            .to_vec();
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+        let mut tera = Tera::default();
+        tera.add_raw_template(BASE_TEMPLATE, "base").unwrap();
+        tera.add_raw_template("journal.html", "journal").unwrap();
+
+        render_notes(
+            &notes,
+            &Navigation::from(&notes),
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            "journal",
+            fs::read_to_string(output_dir.path().join("entry.html")).unwrap()
+        );
+        assert_eq!(
+            "base",
+            fs::read_to_string(output_dir.path().join("other.html")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_open_graph_title_is_escaped() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let notes = vec![parse_public(
+            "tips.md",
+            "---\ntitle: \"Tips & Tricks <v2>\"\ndescription: Say \"hi\"\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+        )];
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            BASE_TEMPLATE,
+            "<meta property=\"og:title\" content=\"{{ note.properties.title }}\">\n<meta property=\"og:description\" content=\"{{ note.properties.description }}\">",
+        )
+        .unwrap();
+
+        render_notes(
+            &notes,
+            &Navigation::from(&notes),
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            "<meta property=\"og:title\" content=\"Tips &amp; Tricks &lt;v2&gt;\">\n<meta property=\"og:description\" content=\"Say &quot;hi&quot;\">",
+            fs::read_to_string(output_dir.path().join("tips.html")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_strict_templates_fail_on_undefined_variables() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Strict\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+        )];
+        let navigation = Navigation::from(&notes);
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            BASE_TEMPLATE,
+            "{% if note.propertis.title %}{{ note.properties.title }}{% endif %}",
+        )
+        .unwrap();
+
+        let failed = render_notes(
+            &notes,
+            &navigation,
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+        assert_eq!(0, failed);
+
+        settings.build.strict_templates = true;
+        fs::remove_file(output_dir.path().join("note.html")).unwrap();
+        let failed = render_notes(
+            &notes,
+            &navigation,
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+        assert_eq!(1, failed);
+        assert!(!output_dir.path().join("note.html").exists());
+        let Err(Error::UndefinedVariables { variables, .. }) =
+            render_note(&notes[0], &[], &navigation, &tera, &settings, false)
+        else {
+            panic!("Expected the undefined variables");
+        };
+        assert_eq!(vec!["note.propertis.title"], variables);
+
+        let tera = load_templates(Path::new("templates"), false).unwrap();
+        let failed = render_notes(
+            &notes,
+            &navigation,
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+        assert_eq!(0, failed);
+    }
+
+    #[test]
+    fn test_render_panic_only_fails_its_note() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let notes = ["good", "bad", "other"]
+            .map(|name| {
+                parse_public(
+                    &format!("{name}.md"),
+                    &format!(
+                        "---\ntitle: {name}\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n"
+                    ),
+                )
+            })
+            .to_vec();
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+
+        let mut tera = Tera::default();
+        tera.register_function("explode", |args: &HashMap<String, tera::Value>| {
+            if args["title"] == "bad" {
+                panic!("exploded");
+            }
+            Ok(args["title"].clone())
+        });
+        tera.add_raw_template(BASE_TEMPLATE, "{{ explode(title=note.properties.title) }}")
+            .unwrap();
+
+        let failed = render_notes(
+            &notes,
+            &Navigation::from(&notes),
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        assert_eq!(1, failed);
+        assert!(!output_dir.path().join("bad.html").exists());
+        assert_eq!(
+            "good",
+            fs::read_to_string(output_dir.path().join("good.html")).unwrap()
+        );
+        assert!(output_dir.path().join("other.html").is_file());
+
+        let err = build(
+            &notes,
+            ContentMap::from(&notes),
+            Navigation::from(&notes),
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::RenderFailed { failed: 1 })
+        ));
+        assert!(output_dir.path().join("good.html").is_file());
+    }
+
+    #[test]
+    fn test_local_cover_image_is_copied() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(input_dir.path().join("media")).unwrap();
+        fs::write(input_dir.path().join("media/cover.png"), "cover").unwrap();
+        let notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\nimage: ./media/cover.png\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+        )];
+
+        copy_media_files(
+            &notes,
+            input_dir.path(),
+            output_dir.path(),
+            &BuildSettings {
+                io_threads: 4,
+                ..Default::default()
+            },
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            "cover",
+            fs::read_to_string(output_dir.path().join("media/cover.png")).unwrap()
+        );
+        assert_eq!(
+            Some("https://example.com/media/cover.png".to_string()),
+            image_url(&notes[0], Some("https://example.com"))
+        );
+    }
+
+    #[test]
+    fn test_media_file_with_space_is_copied() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(input_dir.path().join("media")).unwrap();
+        fs::write(input_dir.path().join("media/my cover.png"), "cover").unwrap();
+        let notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\nimage: media/my%20cover.png\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n![[media/my cover.png]]",
+        )];
+
+        copy_media_files(
+            &notes,
+            input_dir.path(),
+            output_dir.path(),
+            &BuildSettings {
+                io_threads: 4,
+                ..Default::default()
+            },
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            "cover",
+            fs::read_to_string(output_dir.path().join("media/my cover.png")).unwrap()
+        );
+        assert!(
+            notes[0]
+                .html_content
+                .contains("src=\"./media/my%20cover.png\"")
+        );
+        assert_eq!(
+            Some("media/my%20cover.png"),
+            notes[0].properties.image.as_deref()
+        );
+    }
+
+    #[test]
+    fn test_remote_cover_image_is_passed_through() {
+        let note = parse_public(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\nimage: https://cdn.example.com/cover.png\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+        );
+
+        assert!(note.media_links.is_empty());
+        assert_eq!(
+            Some("https://cdn.example.com/cover.png".to_string()),
+            image_url(&note, Some("https://example.com"))
+        );
+    }
+
+    #[test]
+    fn test_note_json_is_written_next_to_html() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let notes = vec![parse_public(
+            "My Note.md",
+            "---\ntitle: Json\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nContent",
+        )];
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+        settings.build.emit_note_json = true;
+
+        let tera = load_templates(&output_dir.path().join("missing"), true).unwrap();
+        render_notes(
+            &notes,
+            &Navigation::from(&notes),
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        assert!(output_dir.path().join("my-note.html").is_file());
+        let produced: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(output_dir.path().join("my-note.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!("Json", produced["properties"]["title"]);
+    }
+
+    #[test]
+    fn test_manifest_lists_rendered_note() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let asset_dir = tempfile::tempdir().unwrap();
+        fs::write(asset_dir.path().join("style.css"), "body {}").unwrap();
+        let notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nContent",
+        )];
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+        settings.path.assets = vec![asset_dir.path().to_path_buf()];
+        settings.build.emit_manifest = true;
+        let tera = load_templates(&output_dir.path().join("missing"), true).unwrap();
+
+        build(
+            &notes,
+            ContentMap::from(&notes),
+            Navigation::from(&notes),
+            &tera,
+            &settings,
+            &Diagnostics::default(),
+        )
+        .unwrap();
+
+        let manifest: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(output_dir.path().join(OUTPUT_MANIFEST_FILE)).unwrap(),
+        )
+        .unwrap();
+        let files = manifest["files"].as_array().unwrap();
+        let paths = files
+            .iter()
+            .map(|file| file["path"].as_str().unwrap())
+            .collect::<Vec<&str>>();
+        let mut sorted = paths.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, paths);
+        assert!(paths.contains(&"style.css"));
+        assert!(!paths.contains(&OUTPUT_MANIFEST_FILE));
+
+        let note = files
+            .iter()
+            .find(|file| file["path"] == "note.html")
+            .unwrap();
+        let content = fs::read(output_dir.path().join("note.html")).unwrap();
+        assert!(note["size"].as_u64().unwrap() > 0);
+        assert_eq!(content.len() as u64, note["size"].as_u64().unwrap());
+        assert_eq!(content_hash(&content), note["hash"]);
+    }
+}
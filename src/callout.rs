@@ -0,0 +1,95 @@
+use comrak::nodes::{AlertType, AstNode, NodeAlert, NodeValue};
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Matches the `[!type]` marker (optionally foldable with `+`/`-`) and title
+/// on the first line of an Obsidian callout.
+static CALLOUT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\[!([A-Za-z-]+)\][+-]?\s*(.*)$").expect("Invalid callout pattern")
+});
+
+/// Turns blockquotes starting with an Obsidian `[!type]` marker into alerts,
+/// so they render with the same markup as GitHub alerts.
+///
+/// Obsidian's additional types are mapped onto the closest alert type, a
+/// missing title falls back to the callout type as written.
+pub fn convert_callouts<'a>(root: &'a AstNode<'a>) {
+    let blockquotes = root
+        .descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::BlockQuote))
+        .collect::<Vec<_>>();
+
+    for blockquote in blockquotes {
+        let Some(paragraph) = blockquote.first_child() else {
+            continue;
+        };
+        let Some(marker) = paragraph.first_child() else {
+            continue;
+        };
+
+        let (kind, title) = match &marker.data.borrow().value {
+            NodeValue::Text(text) => match CALLOUT_RE.captures(text) {
+                Some(caps) => (caps[1].to_string(), caps[2].trim().to_string()),
+                None => continue,
+            },
+            _ => continue,
+        };
+        let Some(alert_type) = alert_type(&kind) else {
+            continue;
+        };
+
+        // Remove the marker line, and the paragraph if nothing else is left.
+        if let Some(line_break) = marker.next_sibling()
+            && matches!(
+                line_break.data.borrow().value,
+                NodeValue::SoftBreak | NodeValue::LineBreak
+            )
+        {
+            line_break.detach();
+        }
+        marker.detach();
+        if paragraph.first_child().is_none() {
+            paragraph.detach();
+        }
+
+        let title = if title.is_empty() {
+            capitalize(&kind)
+        } else {
+            title
+        };
+        blockquote.data.borrow_mut().value = NodeValue::Alert(NodeAlert {
+            alert_type,
+            title: Some(title),
+            multiline: false,
+            fence_length: 0,
+            fence_offset: 0,
+        });
+    }
+}
+
+fn alert_type(kind: &str) -> Option<AlertType> {
+    Some(match kind.to_lowercase().as_str() {
+        "note" | "info" | "todo" | "abstract" | "summary" | "tldr" | "example" | "quote"
+        | "cite" => AlertType::Note,
+        "tip" | "hint" | "success" | "check" | "done" => AlertType::Tip,
+        "important" | "question" | "help" | "faq" => AlertType::Important,
+        "warning" | "attention" => AlertType::Warning,
+        "caution" | "failure" | "fail" | "missing" | "danger" | "error" | "bug" => {
+            AlertType::Caution
+        }
+        _ => return None,
+    })
+}
+
+fn capitalize(kind: &str) -> String {
+    let mut chars = kind.chars();
+    chars
+        .next()
+        .map(|first| {
+            first
+                .to_uppercase()
+                .chain(chars.flat_map(char::to_lowercase))
+                .collect()
+        })
+        .unwrap_or_default()
+}
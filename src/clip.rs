@@ -0,0 +1,60 @@
+use comrak::nodes::{AstNode, NodeValue};
+
+use crate::settings::{ClipRule, ClipScope};
+
+/// Removes the sections whose heading matches one of the rules, like the
+/// `## Questions` at the end of a note.
+///
+/// A thematic break (`---`) right before a clipped heading is removed as
+/// well, as it only separated the clipped section.
+pub fn clip_sections<'a>(root: &'a AstNode<'a>, rules: &[ClipRule]) {
+    for rule in rules {
+        let headings = root
+            .descendants()
+            .filter(|node| heading_level(node) == Some(rule.level))
+            .filter(|node| rule.matches(heading_text(node).trim()))
+            .collect::<Vec<_>>();
+
+        for heading in headings {
+            let mut next_sibling = heading.next_sibling();
+            while let Some(sibling) = next_sibling {
+                if rule.scope == ClipScope::Section
+                    && heading_level(sibling).is_some_and(|level| level <= rule.level)
+                {
+                    break;
+                }
+                next_sibling = sibling.next_sibling();
+                sibling.detach();
+            }
+
+            if let Some(previous_sibling) = heading.previous_sibling()
+                && matches!(
+                    previous_sibling.data.borrow().value,
+                    NodeValue::ThematicBreak
+                )
+            {
+                previous_sibling.detach();
+            }
+            heading.detach();
+        }
+    }
+}
+
+fn heading_level<'a>(node: &'a AstNode<'a>) -> Option<u8> {
+    match node.data.borrow().value {
+        NodeValue::Heading(ref heading) => Some(heading.level),
+        _ => None,
+    }
+}
+
+/// The plain text of the heading, without any formatting.
+fn heading_text<'a>(heading: &'a AstNode<'a>) -> String {
+    heading
+        .descendants()
+        .filter_map(|node| match node.data.borrow().value {
+            NodeValue::Text(ref text) => Some(text.to_string()),
+            NodeValue::Code(ref code) => Some(code.literal.clone()),
+            _ => None,
+        })
+        .collect()
+}
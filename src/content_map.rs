@@ -1,36 +1,301 @@
 use serde::Serialize;
-use std::collections::HashMap;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::LazyLock;
 
-use crate::post_note::{InternalLink, PostNote, Properties, Tag};
+use regex::Regex;
 
-#[derive(Debug, Clone, Serialize)]
-struct SearchProperties<'a> {
-    tags: &'a Vec<Tag>,
-    title: &'a str,
-    description: &'a str,
-}
+use crate::hash::content_hash;
+use crate::post_note::{InternalLink, PostNote, slugify};
 
-impl<'a> From<&'a Properties> for SearchProperties<'a> {
-    fn from(props: &'a Properties) -> Self {
-        Self {
-            tags: &props.tags,
-            title: &props.title,
-            description: &props.description,
-        }
-    }
-}
+/// Fields included if none are configured.
+pub const DEFAULT_FIELDS: &[&str] = &["tags", "title", "description"];
+
+/// Directory the shards of the content map and their manifest are written to.
+pub const SHARD_DIR: &str = "map";
+/// File within the [SHARD_DIR] listing the shards and the notes within them.
+pub const SHARD_MANIFEST_FILE: &str = "manifest.json";
+/// Directory within the [SHARD_DIR] holding the shards, so a shard can't
+/// replace the manifest.
+const SHARD_FILE_DIR: &str = "shards";
+/// Shard of the notes without tags.
+const UNTAGGED_SHARD: &str = "_untagged";
+
+/// Number of characters of the plain text used as `excerpt`.
+const EXCERPT_LENGTH: usize = 160;
+/// Words per minute used to estimate the `reading_time`.
+const WORDS_PER_MINUTE: usize = 200;
+
+static TAG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<[^>]*>").expect("Invalid html tag pattern"));
 
-#[derive(Debug, Clone, Serialize)]
-pub struct ContentMap<'a>(HashMap<&'a InternalLink, SearchProperties<'a>>);
+/// Searchable fields of every note, keyed by the note's link.
+///
+/// Fields are either [Properties](crate::post_note::Properties) fields
+/// (nested ones like `series.name` separated by `.`) or the computed
+/// `excerpt`, `reading_time` (in minutes) and `content_hash`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ContentMap<'a>(HashMap<&'a InternalLink, Map<String, Value>>);
 
 impl<'a> From<&'a Vec<PostNote>> for ContentMap<'a> {
     fn from(post_notes: &'a Vec<PostNote>) -> Self {
+        let fields = DEFAULT_FIELDS
+            .iter()
+            .map(|field| field.to_string())
+            .collect::<Vec<String>>();
+
+        Self::new(post_notes, &fields)
+    }
+}
+
+impl<'a> ContentMap<'a> {
+    pub fn new(post_notes: &'a [PostNote], fields: &[String]) -> Self {
         let mut search_props = HashMap::new();
 
-        for note in post_notes.iter() {
-            search_props.insert(&note.file_name, SearchProperties::from(&note.properties));
+        for note in post_notes.iter().filter(|note| note.is_published()) {
+            search_props.insert(&note.file_name, search_properties(note, fields));
         }
 
         Self(search_props)
     }
+
+    /// Splits the map by the top-level tags of the notes, a note tagged
+    /// `rust/async` and `cooking` is part of both the `rust` and the
+    /// `cooking` shard. Notes without tags end up in the `_untagged` shard.
+    pub fn shards(&self, post_notes: &'a [PostNote]) -> BTreeMap<String, ContentMap<'a>> {
+        let mut shards = BTreeMap::<String, ContentMap>::new();
+
+        for note in post_notes {
+            let Some(properties) = self.0.get(&note.file_name) else {
+                continue;
+            };
+            let mut top_level_tags = note
+                .properties
+                .tags
+                .iter()
+                .filter_map(|tag| tag.split('/').find(|part| !part.is_empty()))
+                .map(str::to_string)
+                .collect::<BTreeSet<String>>();
+            if top_level_tags.is_empty() {
+                top_level_tags.insert(UNTAGGED_SHARD.to_string());
+            }
+
+            for tag in top_level_tags {
+                shards
+                    .entry(tag)
+                    .or_default()
+                    .0
+                    .insert(&note.file_name, properties.clone());
+            }
+        }
+
+        shards
+    }
+}
+
+/// Paths of the shards of the top-level tags, relative to the output
+/// directory. Tags sharing a slug, like `c++` and `c`, keep their shards
+/// apart by the hash of the tag appended to all but the first of them.
+pub fn shard_paths(shards: &BTreeMap<String, ContentMap>) -> BTreeMap<String, String> {
+    let mut taken = HashMap::<String, &str>::new();
+
+    shards
+        .keys()
+        .map(|tag| {
+            let mut slug = slugify(tag);
+            if let Some(other) = taken.get(&slug) {
+                log::warn!(
+                    "The tags {} and {} share the content map shard {}, the shard of {} gets a hash appended",
+                    other,
+                    tag,
+                    slug,
+                    tag
+                );
+                slug = format!("{slug}-{}", &content_hash(tag.as_bytes())[..8]);
+            }
+            taken.insert(slug.clone(), tag);
+
+            (
+                tag.clone(),
+                format!("{SHARD_DIR}/{SHARD_FILE_DIR}/{slug}.json"),
+            )
+        })
+        .collect()
+}
+
+/// Lists the path of every shard and the shards every note is part of, so
+/// clients only have to load the shards they need.
+pub fn shard_manifest(shards: &BTreeMap<String, ContentMap>) -> Value {
+    let mut notes = BTreeMap::<&str, Vec<&str>>::new();
+    for (tag, shard) in shards {
+        for link in shard.0.keys() {
+            notes.entry(link).or_default().push(tag);
+        }
+    }
+
+    serde_json::json!({ "shards": shard_paths(shards), "notes": notes })
+}
+
+fn search_properties(note: &PostNote, fields: &[String]) -> Map<String, Value> {
+    let properties = serde_json::to_value(&note.properties).unwrap_or_default();
+    let text = || TAG_RE.replace_all(&note.html_content, "").into_owned();
+
+    fields
+        .iter()
+        .filter_map(|field| {
+            let value = match field.as_str() {
+                "excerpt" => Value::from(excerpt(&text())),
+                "content_hash" => Value::from(note.content_hash.as_str()),
+                "reading_time" => {
+                    let words = text().split_whitespace().count();
+                    Value::from(words.div_ceil(WORDS_PER_MINUTE).max(1))
+                }
+                _ => match properties.pointer(&format!("/{}", field.replace('.', "/"))) {
+                    Some(value) => value.clone(),
+                    None => {
+                        log::debug!("The note {} has no field {}", &*note.file_name, field);
+                        return None;
+                    }
+                },
+            };
+
+            Some((field.clone(), value))
+        })
+        .collect()
+}
+
+/// The beginning of the text with collapsed whitespace, cut at a word
+/// boundary.
+fn excerpt(text: &str) -> String {
+    let mut excerpt = String::new();
+    for word in text.split_whitespace() {
+        if excerpt.chars().count() + word.chars().count() > EXCERPT_LENGTH {
+            excerpt.push('…');
+            break;
+        }
+        if !excerpt.is_empty() {
+            excerpt.push(' ');
+        }
+        excerpt.push_str(word);
+    }
+
+    excerpt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post_note::parse_public;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn test_content_map_contains_duplicate_tags_once() {
+        let notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\ntags: [rust, rust]\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+        )];
+
+        let produced = serde_json::to_value(ContentMap::from(&notes)).unwrap();
+
+        assert_eq!(
+            json!({
+                "note.html": {
+                    "tags": ["rust"],
+                    "title": "Note",
+                    "description": "Description",
+                }
+            }),
+            produced
+        );
+    }
+
+    #[test]
+    fn test_note_appears_in_the_shard_of_every_top_level_tag() {
+        let notes = vec![
+            parse_public(
+                "pasta.md",
+                "---\ntitle: Pasta\ndescription: Description\ntags: [rust/async, cooking, rust]\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+            ),
+            parse_public(
+                "garden.md",
+                "---\ntitle: Garden\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+            ),
+        ];
+        let content_map = ContentMap::from(&notes);
+
+        let shards = content_map.shards(&notes);
+
+        assert_eq!(
+            json!({
+                "_untagged": { "garden.html": { "tags": [], "title": "Garden", "description": "Description" } },
+                "cooking": { "pasta.html": { "tags": ["rust/async", "cooking", "rust"], "title": "Pasta", "description": "Description" } },
+                "rust": { "pasta.html": { "tags": ["rust/async", "cooking", "rust"], "title": "Pasta", "description": "Description" } },
+            }),
+            serde_json::to_value(&shards).unwrap()
+        );
+        assert_eq!(
+            json!({
+                "shards": {
+                    "_untagged": "map/shards/_untagged.json",
+                    "cooking": "map/shards/cooking.json",
+                    "rust": "map/shards/rust.json",
+                },
+                "notes": {
+                    "garden.html": ["_untagged"],
+                    "pasta.html": ["cooking", "rust"],
+                },
+            }),
+            shard_manifest(&shards)
+        );
+    }
+
+    #[test]
+    fn test_shards_never_share_a_path() {
+        let notes = vec![parse_public(
+            "a.md",
+            "---\ntitle: A\ndescription: Description\ntags: [manifest, c, c++]\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+        )];
+        let content_map = ContentMap::from(&notes);
+        let shards = content_map.shards(&notes);
+
+        let produced = shard_paths(&shards);
+
+        assert_eq!(3, produced.values().collect::<BTreeSet<&String>>().len());
+        assert_eq!("map/shards/c.json", produced["c"]);
+        assert!(produced["c++"].starts_with("map/shards/c-"));
+        assert_eq!("map/shards/manifest.json", produced["manifest"]);
+    }
+
+    #[test]
+    fn test_content_map_with_custom_fields() {
+        let notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\ntags: [rust]\ncreated: 2025-05-23T13:35\npublic: true\ndifficulty: easy\n---\nSome *text* here.",
+        )];
+        let fields = [
+            "title",
+            "created",
+            "extra.difficulty",
+            "excerpt",
+            "reading_time",
+            "missing",
+        ]
+        .map(String::from);
+
+        let produced = serde_json::to_value(ContentMap::new(&notes, &fields)).unwrap();
+
+        assert_eq!(
+            json!({
+                "note.html": {
+                    "title": "Note",
+                    "created": "2025-05-23T13:35",
+                    "extra.difficulty": "easy",
+                    "excerpt": "Some text here.",
+                    "reading_time": 1,
+                }
+            }),
+            produced
+        );
+    }
 }
@@ -0,0 +1,44 @@
+use std::env;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+
+/// Formats which are accepted for dates in the front matter.
+const DATE_TIME_FORMATS: [&str; 3] = ["%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"];
+const DATE_FORMAT: &str = "%Y-%m-%d";
+/// Format dates get stored in if they don't originate from the front matter.
+const CANONICAL_FORMAT: &str = "%Y-%m-%dT%H:%M";
+/// Environment variable pinning the current time to a unix timestamp for
+/// reproducible builds, see <https://reproducible-builds.org/specs/source-date-epoch/>.
+const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// The current local time, unless it's pinned by `SOURCE_DATE_EPOCH`. Every
+/// time written into the output has to come from here, so builds can be
+/// reproduced.
+pub fn now() -> NaiveDateTime {
+    env::var(SOURCE_DATE_EPOCH)
+        .ok()
+        .and_then(|epoch| epoch.trim().parse::<i64>().ok())
+        .and_then(|epoch| DateTime::from_timestamp(epoch, 0))
+        .map(|date| date.naive_utc())
+        .unwrap_or_else(|| Local::now().naive_local())
+}
+
+/// Parses a date from the front matter, e.g. `2025-05-23T13:35` or
+/// `2025-05-23`. Dates without a time are interpreted as midnight.
+pub fn parse_date(raw_date: &str) -> Option<NaiveDateTime> {
+    let raw_date = raw_date.trim();
+
+    DATE_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(raw_date, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(raw_date, DATE_FORMAT)
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+}
+
+/// Formats a date the same way dates are written in the front matter.
+pub fn format_date(date: NaiveDateTime) -> String {
+    date.format(CANONICAL_FORMAT).to_string()
+}
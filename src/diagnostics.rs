@@ -0,0 +1,169 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::external_links::ExternalLinkIssue;
+use crate::validation::LinkIssue;
+
+/// Name of the file the diagnostics are written to.
+const DIAGNOSTICS_FILE: &str = "diagnostics.json";
+
+/// Kind of problem a [Diagnostic] reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// A note couldn't be read.
+    ReadError,
+    /// A note couldn't be parsed.
+    ParseError,
+    /// A note violates the configured schema.
+    SchemaViolation,
+    /// A link points to a missing note.
+    BrokenLink,
+    /// A link points to a private note.
+    PrivateLink,
+    /// A link points to a missing block of an existing note.
+    UnknownBlock,
+    /// A link points to a missing heading of an existing note.
+    UnknownHeading,
+    /// A link to an external website couldn't be reached.
+    DeadExternalLink,
+    /// A referenced media file couldn't be copied.
+    MissingMedia,
+    /// A referenced media file exceeded the maximum media size.
+    OversizedMedia,
+    /// The template of a note reads an undefined variable.
+    UndefinedVariable,
+}
+
+/// A single warning or error, as written to `diagnostics.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub category: Category,
+    pub message: String,
+    /// The note or file the problem was found in.
+    pub source: String,
+}
+
+impl Diagnostic {
+    pub fn new(category: Category, message: impl ToString, source: impl ToString) -> Self {
+        Diagnostic {
+            category,
+            message: message.to_string(),
+            source: source.to_string(),
+        }
+    }
+}
+
+impl From<&LinkIssue> for Diagnostic {
+    fn from(issue: &LinkIssue) -> Self {
+        match issue {
+            LinkIssue::MissingNote { source, .. } => {
+                Diagnostic::new(Category::BrokenLink, issue, &**source)
+            }
+            LinkIssue::PrivateNote { source, .. } => {
+                Diagnostic::new(Category::PrivateLink, issue, &**source)
+            }
+            LinkIssue::UnknownBlock { source, .. } => {
+                Diagnostic::new(Category::UnknownBlock, issue, &**source)
+            }
+            LinkIssue::UnknownHeading { source, .. } => {
+                Diagnostic::new(Category::UnknownHeading, issue, &**source)
+            }
+        }
+    }
+}
+
+impl From<&ExternalLinkIssue> for Diagnostic {
+    fn from(issue: &ExternalLinkIssue) -> Self {
+        Diagnostic::new(Category::DeadExternalLink, issue, &*issue.source)
+    }
+}
+
+/// Collects diagnostics from all phases of a build, including the parallel
+/// ones, complementing the logs with a machine-readable report.
+#[derive(Debug, Default)]
+pub struct Diagnostics(Mutex<Vec<Diagnostic>>);
+
+impl Diagnostics {
+    pub fn push(&self, diagnostic: Diagnostic) {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(diagnostic);
+    }
+
+    /// Writes all collected diagnostics to `diagnostics.json` in the directory,
+    /// sorted so the file is stable across builds.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory can't be created or the file can't be
+    /// written.
+    pub fn write(&self, directory: &Path) -> anyhow::Result<()> {
+        let mut diagnostics = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        diagnostics.sort_by(|a, b| {
+            (&a.source, &a.message, a.category as u8).cmp(&(
+                &b.source,
+                &b.message,
+                b.category as u8,
+            ))
+        });
+
+        fs::create_dir_all(directory)?;
+        let path = directory.join(DIAGNOSTICS_FILE);
+        fs::write(&path, serde_json::to_string_pretty(&diagnostics)?)?;
+        log::info!(
+            "Wrote {} diagnostic(s) to: {}",
+            diagnostics.len(),
+            path.display()
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post_note::parse_public;
+    use crate::validation::validate_links;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_broken_link_produces_diagnostic() {
+        let volatile_dir = tempfile::tempdir().unwrap();
+        let raw_md = "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n[[Missing]]";
+        let notes = vec![parse_public("note.md", raw_md)];
+
+        let diagnostics = Diagnostics::default();
+        for issue in validate_links(
+            &notes,
+            &Default::default(),
+            &Default::default(),
+            Default::default(),
+        ) {
+            diagnostics.push(Diagnostic::from(&issue));
+        }
+        diagnostics.write(volatile_dir.path()).unwrap();
+
+        let produced: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(volatile_dir.path().join("diagnostics.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::json!([{
+                "category": "broken_link",
+                "message": "note.html links to the missing note missing.html",
+                "source": "note.html",
+            }]),
+            produced
+        );
+    }
+}
@@ -0,0 +1,33 @@
+use std::io;
+use std::path::PathBuf;
+
+/// Errors of loading and rendering single notes which the build tells apart,
+/// e.g. to report notes which couldn't be read or parsed as diagnostics.
+/// Everything else, and these errors once they leave the step which returned
+/// them, is an [anyhow::Error]; its chain of sources is rendered with `{:#}`.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A note couldn't be parsed.
+    #[error("Could not parse {}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: anyhow::Error,
+    },
+    /// A note couldn't be rendered with its template.
+    #[error("Could not render {note}")]
+    Render { note: String, source: tera::Error },
+    /// The template of a note reads variables which aren't defined, only
+    /// checked with `strict_templates`.
+    #[error("{template} reads the undefined variable(s) {} while rendering {note}", variables.join(", "))]
+    UndefinedVariables {
+        note: String,
+        template: String,
+        variables: Vec<String>,
+    },
+    /// Some notes couldn't be rendered, the rest of the website was built.
+    #[error("{failed} note(s) could not be rendered")]
+    RenderFailed { failed: usize },
+    /// A file couldn't be read or written.
+    #[error("Could not access {}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+}
@@ -0,0 +1,90 @@
+use std::borrow::Cow;
+
+/// Escapes the characters which are not allowed in XML text and attributes.
+/// Used for every title, description and url written into the feeds, the
+/// sitemap and the OPML, the templates get the same escaping from Tera.
+pub fn escape_xml(raw: &str) -> String {
+    raw.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Decodes the predefined XML entities and numeric character references,
+/// like `&amp;` or `&#38;`, so text which is escaped on output isn't escaped
+/// twice. Unknown or malformed entities are kept as they are.
+pub fn decode_entities(raw: &str) -> Cow<'_, str> {
+    if !raw.contains('&') {
+        return Cow::Borrowed(raw);
+    }
+
+    let mut decoded = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest
+            .find(';')
+            .and_then(|end| Some((decode_entity(&rest[1..end])?, end)));
+        match entity {
+            Some((char, end)) => {
+                decoded.push(char);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    Cow::Owned(decoded)
+}
+
+/// Decodes the name of an entity without its `&` and `;`.
+fn decode_entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let code = name.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => code.parse(),
+            };
+            char::from_u32(code.ok()?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(
+            "Tips &amp; Tricks &lt;v2&gt; &quot;quoted&quot; &apos;single&apos;",
+            escape_xml("Tips & Tricks <v2> \"quoted\" 'single'")
+        );
+    }
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(
+            "Tips & Tricks <v2> \"&\" 'A'",
+            decode_entities("Tips &amp; Tricks &lt;v2&gt; &quot;&#38;&quot; &apos;&#x41;&apos;")
+        );
+        assert_eq!(
+            "R&D &unknown; &#xZZ; & done",
+            decode_entities("R&D &unknown; &#xZZ; & done")
+        );
+        assert!(matches!(decode_entities("Plain"), Cow::Borrowed("Plain")));
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use derive_more::Display;
+use rayon::ThreadPoolBuilder;
+use rayon::prelude::*;
+use regex::Regex;
+use ureq::Agent;
+
+use crate::post_note::{InternalLink, PostNote};
+use crate::settings::ExternalLinkSettings;
+
+/// Matches the target of links to external websites within the rendered html.
+static EXTERNAL_LINK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<a href="(https?://[^"]+)""#).expect("Invalid external link pattern")
+});
+
+/// A link to an external website which couldn't be reached.
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+#[display("{} links to the dead url {url}: {problem}", &**source)]
+pub struct ExternalLinkIssue {
+    pub source: InternalLink,
+    pub url: String,
+    /// The status code or the error the request failed with.
+    pub problem: String,
+}
+
+/// Requests every `http(s)://` url linked by the notes once, except for the
+/// ones on a skipped domain, and reports the urls which fail or answer
+/// with an error status. Every issue found is logged as a warning.
+///
+/// # Errors
+///
+/// Returns an error if the thread pool making the requests can't be created.
+pub fn check_external_links(
+    notes: &[PostNote],
+    settings: &ExternalLinkSettings,
+) -> anyhow::Result<Vec<ExternalLinkIssue>> {
+    let mut sources = BTreeMap::<String, Vec<&InternalLink>>::new();
+    for note in notes {
+        for caps in EXTERNAL_LINK_RE.captures_iter(&note.html_content) {
+            let url = caps[1].replace("&amp;", "&");
+            if !is_skipped(&url, &settings.skip_domains) {
+                sources.entry(url).or_default().push(&note.file_name);
+            }
+        }
+    }
+    log::info!("Checking {} external link(s).", sources.len());
+
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(Duration::from_millis(settings.timeout_ms)))
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(settings.concurrency.max(1))
+        .thread_name(|index| format!("link-check-{index}"))
+        .build()?;
+
+    let issues = pool.install(|| {
+        sources
+            .par_iter()
+            .filter_map(|(url, sources)| Some((url, sources, check_url(&agent, url)?)))
+            .flat_map_iter(|(url, sources, problem)| {
+                sources.iter().map(move |source| ExternalLinkIssue {
+                    source: (*source).clone(),
+                    url: url.clone(),
+                    problem: problem.clone(),
+                })
+            })
+            .collect::<Vec<ExternalLinkIssue>>()
+    });
+    for issue in &issues {
+        log::warn!("{}", issue);
+    }
+
+    Ok(issues)
+}
+
+/// Returns the problem, if the url can't be reached. Servers which don't
+/// support `HEAD` requests are asked again with a `GET` request.
+fn check_url(agent: &Agent, url: &str) -> Option<String> {
+    let status = match agent.head(url).call() {
+        Ok(response) if matches!(response.status().as_u16(), 405 | 501) => {
+            agent.get(url).call().map(|response| response.status())
+        }
+        response => response.map(|response| response.status()),
+    };
+
+    match status {
+        Ok(status) if status.is_client_error() || status.is_server_error() => {
+            Some(status.to_string())
+        }
+        Ok(_) => None,
+        Err(err) => Some(err.to_string()),
+    }
+}
+
+/// Whether the host of the url is one of the domains or a subdomain of one.
+fn is_skipped(url: &str, skip_domains: &[String]) -> bool {
+    let authority = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or(authority);
+    let host = host.split(':').next().unwrap_or(host).to_lowercase();
+
+    skip_domains.iter().any(|domain| {
+        let domain = domain.to_lowercase();
+        host == domain || host.ends_with(&format!(".{domain}"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post_note;
+    use crate::serve::Server;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use std::thread;
+
+    fn parse_public(file_name: &str, body: &str) -> PostNote {
+        let raw_md = format!(
+            "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n{body}"
+        );
+        post_note::parse_public(file_name, &raw_md)
+    }
+
+    #[test]
+    fn test_dead_external_links_are_reported() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("alive.html"), "Alive").unwrap();
+        let server = Server::bind(root.path(), 0).unwrap();
+        let url = server.url();
+        thread::spawn(move || server.run());
+        let notes = vec![parse_public(
+            "source.md",
+            &format!(
+                "[Alive]({url}/alive.html) [Dead]({url}/dead.html) [Skipped](https://docs.skipped.example/)"
+            ),
+        )];
+        let settings = ExternalLinkSettings {
+            enabled: true,
+            skip_domains: vec!["skipped.example".to_string()],
+            ..ExternalLinkSettings::default()
+        };
+
+        let produced = check_external_links(&notes, &settings).unwrap();
+
+        assert_eq!(
+            vec![ExternalLinkIssue {
+                source: InternalLink::from("source".to_string()),
+                url: format!("{url}/dead.html"),
+                problem: "404 Not Found".to_string(),
+            }],
+            produced
+        );
+    }
+}
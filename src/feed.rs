@@ -0,0 +1,663 @@
+use chrono::NaiveDateTime;
+use serde_json::{Value, json};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::Path;
+
+use crate::date::parse_date;
+use crate::escape::escape_xml;
+use crate::navigation::Navigation;
+use crate::post_note::PostNote;
+use crate::retry;
+use crate::settings::Settings;
+
+/// File name of the feed containing all notes.
+pub const FEED_FILE: &str = "feed.xml";
+/// File name of the JSON feed containing all notes.
+pub const JSON_FEED_FILE: &str = "feed.json";
+/// File name of the Atom feed containing all notes.
+pub const ATOM_FEED_FILE: &str = "atom.xml";
+/// Directory containing the per-tag feeds.
+pub const TAG_FEED_DIR: &str = "feeds";
+/// File name of the OPML list of all RSS feeds.
+pub const OPML_FILE: &str = "feeds.opml";
+
+/// Metadata describing a single feed.
+struct Channel<'a> {
+    title: String,
+    description: &'a str,
+    base_url: &'a str,
+}
+
+/// Writes the RSS feed of all notes and, if enabled, one feed per tag (listed
+/// in an OPML file), an Atom feed and a JSON feed of all notes.
+///
+/// Feeds require absolute links, so nothing gets written if no base url is
+/// configured.
+///
+/// # Errors
+///
+/// Returns an error if a feed could not be written.
+pub fn write_feeds(
+    notes: &[PostNote],
+    navigation: &Navigation,
+    settings: &Settings,
+    output_path: &Path,
+) -> anyhow::Result<()> {
+    if !settings.feed.enabled
+        && !settings.feed.per_tag
+        && !settings.feed.json
+        && !settings.feed.atom
+    {
+        return Ok(());
+    }
+
+    let Some(base_url) = settings.site.base_url.as_deref() else {
+        log::warn!("Skipping feeds, because no base url is configured.");
+        return Ok(());
+    };
+
+    if settings.feed.enabled {
+        let channel = Channel {
+            title: settings.site.title.clone(),
+            description: &settings.site.description,
+            base_url,
+        };
+        let path = output_path.join(FEED_FILE);
+
+        retry::write(
+            &settings.build.retry,
+            &path,
+            render_feed(&channel, notes.iter()),
+        )?;
+        log::info!("Created the feed at: {}", path.display());
+    }
+
+    if settings.feed.per_tag {
+        fs::create_dir_all(output_path.join(TAG_FEED_DIR))?;
+
+        for tag in feed_tags(navigation, &settings.feed.tags) {
+            let channel = Channel {
+                title: format!("{} | {}", settings.site.title, tag),
+                description: &settings.site.description,
+                base_url,
+            };
+            let path = output_path.join(tag_feed_path(&tag));
+
+            retry::write(
+                &settings.build.retry,
+                &path,
+                render_feed(&channel, tagged_notes(notes, navigation, &tag)),
+            )?;
+            log::info!("Created the feed for {} at: {}", tag, path.display());
+        }
+
+        if settings.feed.opml {
+            let path = output_path.join(OPML_FILE);
+
+            retry::write(
+                &settings.build.retry,
+                &path,
+                render_opml(navigation, settings, base_url),
+            )?;
+            log::info!("Created the OPML list of feeds at: {}", path.display());
+        }
+    }
+
+    if settings.feed.atom {
+        let channel = Channel {
+            title: settings.site.title.clone(),
+            description: &settings.site.description,
+            base_url,
+        };
+        let path = output_path.join(ATOM_FEED_FILE);
+
+        retry::write(
+            &settings.build.retry,
+            &path,
+            render_atom_feed(&channel, notes.iter()),
+        )?;
+        log::info!("Created the Atom feed at: {}", path.display());
+    }
+
+    if settings.feed.json {
+        let channel = Channel {
+            title: settings.site.title.clone(),
+            description: &settings.site.description,
+            base_url,
+        };
+        let path = output_path.join(JSON_FEED_FILE);
+
+        retry::write(
+            &settings.build.retry,
+            &path,
+            serde_json::to_string_pretty(&render_json_feed(&channel, notes.iter()))?,
+        )?;
+        log::info!("Created the JSON feed at: {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Relative path of the feed for the given tag.
+pub fn tag_feed_path(tag: &str) -> String {
+    format!("{}/{}.xml", TAG_FEED_DIR, tag.replace('/', "-"))
+}
+
+/// Returns the configured tags or, if none are configured, all top-level tags.
+fn feed_tags(navigation: &Navigation, configured_tags: &[String]) -> Vec<String> {
+    if !configured_tags.is_empty() {
+        let mut tags = Vec::new();
+        for tag in configured_tags {
+            let tag = tag.trim().trim_matches('/').to_lowercase();
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        return tags;
+    }
+
+    navigation
+        .root
+        .child_tags
+        .iter()
+        .map(|tag_node| tag_node.tag.to_string())
+        .collect()
+}
+
+/// Returns all notes tagged with the given tag, including notes tagged with
+/// one of its descendants.
+fn tagged_notes<'a>(
+    notes: &'a [PostNote],
+    navigation: &Navigation,
+    tag: &str,
+) -> impl Iterator<Item = &'a PostNote> {
+    let files = navigation
+        .root
+        .find(tag)
+        .map(|tag_node| tag_node.all_files())
+        .unwrap_or_default();
+
+    notes
+        .iter()
+        .filter(move |note| files.contains(&note.file_name))
+}
+
+/// Renders an OPML 2.0 list of the feed of all notes (if enabled) and the
+/// per-tag feeds, labeled like the tags in the navigation.
+fn render_opml(navigation: &Navigation, settings: &Settings, base_url: &str) -> String {
+    let outline = |title: &str, path: &str| {
+        format!(
+            "<outline type=\"rss\" text=\"{0}\" title=\"{0}\" xmlUrl=\"{1}\"/>\n",
+            escape_xml(title),
+            escape_xml(&absolute_url(base_url, path))
+        )
+    };
+
+    let mut outlines = String::new();
+    if settings.feed.enabled {
+        outlines.push_str(&outline(&settings.site.title, FEED_FILE));
+    }
+    for tag in feed_tags(navigation, &settings.feed.tags) {
+        let label = navigation
+            .root
+            .find(&tag)
+            .map_or(tag.as_str(), |tag_node| &tag_node.label);
+        outlines.push_str(&outline(label, &tag_feed_path(&tag)));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <opml version=\"2.0\">\n\
+         <head>\n\
+         <title>{}</title>\n\
+         </head>\n\
+         <body>\n\
+         {}\
+         </body>\n\
+         </opml>\n",
+        escape_xml(&settings.site.title),
+        outlines
+    )
+}
+
+/// Sorts the published notes from newest to oldest by their parsed created
+/// date, so dates written in different formats compare correctly. Notes
+/// created at the same time are ordered by their link.
+fn newest_first<'a>(notes: impl Iterator<Item = &'a PostNote>) -> Vec<&'a PostNote> {
+    let mut notes = notes
+        .filter(|note| note.is_published())
+        .collect::<Vec<&PostNote>>();
+    notes.sort_by_cached_key(|note| {
+        (
+            Reverse(parse_date(&note.properties.created)),
+            note.file_name.clone(),
+        )
+    });
+
+    notes
+}
+
+/// Renders an RSS 2.0 feed with the notes sorted from newest to oldest.
+fn render_feed<'a>(channel: &Channel, notes: impl Iterator<Item = &'a PostNote>) -> String {
+    let items = newest_first(notes)
+        .into_iter()
+        .map(|note| render_item(channel.base_url, note))
+        .collect::<String>();
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\">\n\
+         <channel>\n\
+         <title>{}</title>\n\
+         <link>{}</link>\n\
+         <description>{}</description>\n\
+         {}\
+         </channel>\n\
+         </rss>\n",
+        escape_xml(&channel.title),
+        escape_xml(channel.base_url),
+        escape_xml(channel.description),
+        items
+    )
+}
+
+fn render_item(base_url: &str, note: &PostNote) -> String {
+    let link = escape_xml(&absolute_url(base_url, &note.file_name));
+    let pub_date = parse_date(&note.properties.created)
+        .map(|date| {
+            format!(
+                "<pubDate>{}</pubDate>\n",
+                date.and_utc().format("%a, %d %b %Y %H:%M:%S +0000")
+            )
+        })
+        .unwrap_or_default();
+    let categories = note
+        .properties
+        .tags
+        .iter()
+        .map(|tag| format!("<category>{}</category>\n", escape_xml(tag)))
+        .collect::<String>();
+
+    format!(
+        "<item>\n\
+         <title>{}</title>\n\
+         <link>{}</link>\n\
+         <guid>{}</guid>\n\
+         <description>{}</description>\n\
+         {}\
+         {}\
+         </item>\n",
+        escape_xml(&note.properties.title),
+        link,
+        link,
+        escape_xml(&note.properties.description),
+        pub_date,
+        categories
+    )
+}
+
+/// The last modification of the note, falling back to its creation.
+fn updated_date(note: &PostNote) -> Option<NaiveDateTime> {
+    note.properties
+        .modified
+        .as_deref()
+        .and_then(parse_date)
+        .or_else(|| parse_date(&note.properties.created))
+}
+
+/// Renders an Atom 1.0 feed with the notes sorted from newest to oldest.
+/// Entries are `published` when they were created and `updated` when they
+/// were last modified, the feed is as recent as its most recent entry.
+fn render_atom_feed<'a>(channel: &Channel, notes: impl Iterator<Item = &'a PostNote>) -> String {
+    let notes = newest_first(notes);
+    let rfc3339 = |date: NaiveDateTime| date.and_utc().to_rfc3339();
+    let feed_updated = notes
+        .iter()
+        .filter_map(|note| updated_date(note))
+        .max()
+        .unwrap_or_default();
+    let entries = notes
+        .into_iter()
+        .map(|note| {
+            let link = escape_xml(&absolute_url(channel.base_url, &note.file_name));
+            let published = parse_date(&note.properties.created)
+                .map(|date| format!("<published>{}</published>\n", rfc3339(date)))
+                .unwrap_or_default();
+            let categories = note
+                .properties
+                .tags
+                .iter()
+                .map(|tag| format!("<category term=\"{}\"/>\n", escape_xml(tag)))
+                .collect::<String>();
+
+            format!(
+                "<entry>\n\
+                 <title>{}</title>\n\
+                 <link href=\"{}\"/>\n\
+                 <id>{}</id>\n\
+                 {}\
+                 <updated>{}</updated>\n\
+                 <summary>{}</summary>\n\
+                 {}\
+                 </entry>\n",
+                escape_xml(&note.properties.title),
+                link,
+                link,
+                published,
+                rfc3339(updated_date(note).unwrap_or_default()),
+                escape_xml(&note.properties.description),
+                categories
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+         <title>{}</title>\n\
+         <subtitle>{}</subtitle>\n\
+         <link href=\"{}\"/>\n\
+         <link rel=\"self\" href=\"{}\"/>\n\
+         <id>{}</id>\n\
+         <updated>{}</updated>\n\
+         {}\
+         </feed>\n",
+        escape_xml(&channel.title),
+        escape_xml(channel.description),
+        escape_xml(channel.base_url),
+        escape_xml(&absolute_url(channel.base_url, ATOM_FEED_FILE)),
+        escape_xml(channel.base_url),
+        rfc3339(feed_updated),
+        entries
+    )
+}
+
+/// Renders a JSON Feed 1.1 with the notes sorted from newest to oldest.
+fn render_json_feed<'a>(channel: &Channel, notes: impl Iterator<Item = &'a PostNote>) -> Value {
+    let items = newest_first(notes)
+        .into_iter()
+        .map(|note| {
+            let url = absolute_url(channel.base_url, &note.file_name);
+            let mut item = json!({
+                "id": url,
+                "url": url,
+                "title": note.properties.title,
+                "content_html": &*note.html_content,
+                "tags": note.properties.tags,
+            });
+            if let Some(date) = parse_date(&note.properties.created) {
+                item["date_published"] = Value::from(date.and_utc().to_rfc3339());
+            }
+            if let Some(date) = updated_date(note) {
+                item["date_modified"] = Value::from(date.and_utc().to_rfc3339());
+            }
+
+            item
+        })
+        .collect::<Vec<Value>>();
+
+    json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": channel.title,
+        "home_page_url": channel.base_url,
+        "feed_url": absolute_url(channel.base_url, JSON_FEED_FILE),
+        "description": channel.description,
+        "items": items,
+    })
+}
+
+/// Joins the base url and a path relative to the output directory.
+pub fn absolute_url(base_url: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post_note::{parse_public, parse_public_with};
+    use crate::settings::{ContentSettings, NavigationSettings};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_tag_feed_only_contains_tagged_notes() {
+        let notes = vec![
+            parse_public(
+                "async.md",
+                "---\ntitle: Async\ndescription: Description\ntags: [rust/async]\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+            ),
+            parse_public(
+                "bread.md",
+                "---\ntitle: Bread\ndescription: Description\ntags: [cooking]\ncreated: 2025-05-24T13:35\npublic: true\n---\n",
+            ),
+        ];
+        let navigation = Navigation::from(&notes);
+        let channel = Channel {
+            title: "Rust".to_string(),
+            description: "",
+            base_url: "https://example.com/",
+        };
+
+        let produced = render_feed(&channel, tagged_notes(&notes, &navigation, "rust"));
+
+        assert!(produced.contains("<link>https://example.com/async.html</link>"));
+        assert!(!produced.contains("bread.html"));
+        assert_eq!(
+            vec!["cooking".to_string(), "rust".to_string()],
+            feed_tags(&navigation, &[])
+        );
+        assert_eq!("feeds/rust.xml", tag_feed_path("rust"));
+    }
+
+    #[test]
+    fn test_newest_first_compares_the_parsed_dates() {
+        let note = |file_name: &str, created: &str| {
+            parse_public(
+                file_name,
+                &format!(
+                    "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: {created}\npublic: true\n---\n"
+                ),
+            )
+        };
+        let notes = [
+            note("morning.md", "2025-05-23T08:00"),
+            note("noon.md", "2025-05-23 12:00"),
+            note("evening.md", "2025-05-23T18:00:00"),
+        ];
+
+        let produced = newest_first(notes.iter())
+            .into_iter()
+            .map(|note| &*note.file_name)
+            .collect::<Vec<&str>>();
+
+        assert_eq!(vec!["evening.html", "noon.html", "morning.html"], produced);
+    }
+
+    #[test]
+    fn test_feed_escapes_titles() {
+        let raw_md = |title: &str| {
+            format!(
+                "---\ntitle: \"{title}\"\ndescription: Q&A\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n"
+            )
+        };
+        let channel = Channel {
+            title: "Notes".to_string(),
+            description: "",
+            base_url: "https://example.com/",
+        };
+        let notes = [parse_public("tips.md", &raw_md("Tips & Tricks <v2>"))];
+
+        let produced = render_feed(&channel, notes.iter());
+
+        assert!(produced.contains("<title>Tips &amp; Tricks &lt;v2&gt;</title>"));
+        assert!(produced.contains("<description>Q&amp;A</description>"));
+        assert!(!produced.contains("<v2>"));
+
+        // Escaped titles are only escaped once if entities are decoded.
+        let mut settings = ContentSettings::default();
+        settings.front_matter.decode_entities = true;
+        let escaped = parse_public_with(
+            "tips.md",
+            &raw_md("Tips &amp; Tricks &lt;v2&gt;"),
+            &settings,
+        );
+
+        assert_eq!("Tips & Tricks <v2>", escaped.properties.title);
+        assert!(
+            render_feed(&channel, std::iter::once(&escaped))
+                .contains("<title>Tips &amp; Tricks &lt;v2&gt;</title>")
+        );
+    }
+
+    #[test]
+    fn test_collapsed_chains_keep_the_feed_paths() {
+        let notes = vec![parse_public(
+            "q1.md",
+            "---\ntitle: Q1\ndescription: Description\ntags: [Projects/2024/Q1]\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+        )];
+        let mut settings = NavigationSettings::default();
+        let expanded = Navigation::new(&notes, &settings);
+        settings.collapse_chains = true;
+        let collapsed = Navigation::new(&notes, &settings);
+
+        let produced = feed_tags(&collapsed, &[]);
+
+        assert_eq!(feed_tags(&expanded, &[]), produced);
+        assert_eq!("feeds/projects.xml", tag_feed_path(&produced[0]));
+        for tag in ["projects", "projects/2024", "projects/2024/q1"] {
+            assert_eq!(
+                1,
+                tagged_notes(&notes, &collapsed, tag).count(),
+                "{tag} lost its notes"
+            );
+        }
+    }
+
+    #[test]
+    fn test_opml_lists_every_tag_feed_once() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let notes = vec![
+            parse_public(
+                "async.md",
+                "---\ntitle: Async\ndescription: Description\ntags: [Rust/async, cooking]\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+            ),
+            parse_public(
+                "traits.md",
+                "---\ntitle: Traits\ndescription: Description\ntags: [rust]\ncreated: 2025-05-24T13:35\npublic: true\n---\n",
+            ),
+        ];
+        let mut settings = Settings::default();
+        settings.site.base_url = Some("https://example.com/".to_string());
+        settings.feed.per_tag = true;
+        settings.feed.opml = true;
+        settings.navigation.original_tag_case = true;
+        let navigation = Navigation::new(&notes, &settings.navigation);
+
+        write_feeds(&notes, &navigation, &settings, output_dir.path()).unwrap();
+        let produced = fs::read_to_string(output_dir.path().join(OPML_FILE)).unwrap();
+
+        for feed in ["feeds/rust.xml", "feeds/cooking.xml"] {
+            assert_eq!(
+                1,
+                produced
+                    .matches(&format!("xmlUrl=\"https://example.com/{feed}\""))
+                    .count()
+            );
+        }
+        assert!(produced.contains("text=\"Rust\""));
+        assert_eq!(2, produced.matches("<outline").count());
+    }
+
+    #[test]
+    fn test_json_feed_structure() {
+        let notes = [
+            parse_public(
+                "old.md",
+                "---\ntitle: Old\ndescription: Description\ntags: [rust]\ncreated: 2025-05-23T13:35\npublic: true\n---\nOld *content*",
+            ),
+            parse_public(
+                "new.md",
+                "---\ntitle: New\ndescription: Description\ntags: []\ncreated: 2025-05-24T13:35\npublic: true\n---\n",
+            ),
+        ];
+        let channel = Channel {
+            title: "Notes".to_string(),
+            description: "All notes",
+            base_url: "https://example.com/",
+        };
+
+        let produced = render_json_feed(&channel, notes.iter());
+
+        assert_eq!("https://jsonfeed.org/version/1.1", produced["version"]);
+        assert_eq!("https://example.com/feed.json", produced["feed_url"]);
+        assert_eq!(2, produced["items"].as_array().unwrap().len());
+        assert_eq!(
+            json!({
+                "id": "https://example.com/old.html",
+                "url": "https://example.com/old.html",
+                "title": "Old",
+                "content_html": "<p>Old <em>content</em></p>",
+                "date_published": "2025-05-23T13:35:00+00:00",
+                "date_modified": "2025-05-23T13:35:00+00:00",
+                "tags": ["rust"],
+            }),
+            produced["items"][1]
+        );
+    }
+
+    #[test]
+    fn test_atom_feed_tracks_updated_alias() {
+        let notes = [
+            parse_public(
+                "edited.md",
+                "---\ntitle: Edited\ndescription: Description\ntags: [rust]\ncreated: 2025-05-23T13:35\nupdated: 2025-06-01T08:00\npublic: true\n---\n",
+            ),
+            parse_public(
+                "fresh.md",
+                "---\ntitle: Fresh\ndescription: Description\ntags: []\ncreated: 2025-05-24T13:35\npublic: true\n---\n",
+            ),
+        ];
+        let channel = Channel {
+            title: "Notes".to_string(),
+            description: "All notes",
+            base_url: "https://example.com/",
+        };
+
+        assert_eq!(
+            Some("2025-06-01T08:00"),
+            notes[0].properties.modified.as_deref()
+        );
+
+        let produced = render_atom_feed(&channel, notes.iter());
+
+        assert!(produced.contains(
+            "<entry>\n\
+             <title>Edited</title>\n\
+             <link href=\"https://example.com/edited.html\"/>\n\
+             <id>https://example.com/edited.html</id>\n\
+             <published>2025-05-23T13:35:00+00:00</published>\n\
+             <updated>2025-06-01T08:00:00+00:00</updated>\n"
+        ));
+        // Notes which were never modified are updated when created.
+        assert!(produced.contains(
+            "<published>2025-05-24T13:35:00+00:00</published>\n\
+             <updated>2025-05-24T13:35:00+00:00</updated>\n"
+        ));
+        // The feed is as recent as the most recently updated note.
+        assert!(produced.contains(
+            "<id>https://example.com/</id>\n<updated>2025-06-01T08:00:00+00:00</updated>"
+        ));
+        assert!(
+            render_json_feed(&channel, notes.iter())["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|item| item["date_modified"] == "2025-06-01T08:00:00+00:00")
+        );
+    }
+}
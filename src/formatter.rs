@@ -0,0 +1,294 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::sync::LazyLock;
+
+use comrak::create_formatter;
+use comrak::html::format_node_default;
+use comrak::nodes::{AstNode, NodeValue};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::{ContentSettings, MediaTitlePlacement, TaskListMode};
+
+/// Matches an Obsidian-style block id like `^intro` at the end of a block.
+static BLOCK_ID_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s*\^([A-Za-z0-9-]+)$").expect("Invalid block id pattern"));
+
+/// Matches a rendered heading without attributes.
+static HEADING_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<h([1-6])>(.*?)</h([1-6])>").expect("Invalid heading pattern")
+});
+
+/// Matches the id of a heading added by [add_heading_ids].
+static HEADING_ID_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<h[1-6] id="([^"]*)">"#).expect("Invalid heading id pattern"));
+
+/// Matches a heading with the id added by [add_heading_ids].
+static HEADING_WITH_ID_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)<h([1-6]) id="([^"]*)">(.*?)</h[1-6]>"#)
+        .expect("Invalid heading with id pattern")
+});
+
+/// Matches html tags and entities, which don't contribute to a heading id.
+static MARKUP_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<[^>]*>|&[A-Za-z0-9#]+;").expect("Invalid markup pattern"));
+
+/// Matches html tags, which are dropped from the titles of the [TocEntry]s.
+static TAG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<[^>]*>").expect("Invalid html tag pattern"));
+
+/// A heading listed in the table of contents of a note.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TocEntry {
+    pub level: u8,
+    /// Id of the heading including its prefix, so `#<id>` links to it.
+    pub id: String,
+    /// Text of the heading, still html-escaped.
+    pub title: String,
+}
+
+/// State shared by the nodes of a note while it's formatted.
+#[derive(Debug)]
+pub struct FormatState {
+    task_lists: TaskListMode,
+    /// Number of task list items formatted so far.
+    tasks: usize,
+    /// Class of the div wrapping every table, tables aren't wrapped if unset.
+    table_wrapper: Option<String>,
+    media_titles: MediaTitlePlacement,
+    /// Urls of the images written as media wikilinks, like `./media/cat.png`.
+    wikilink_media: HashSet<String>,
+}
+
+impl FormatState {
+    pub fn new(settings: &ContentSettings, wikilink_media: HashSet<String>) -> Self {
+        FormatState {
+            task_lists: settings.task_lists,
+            tasks: 0,
+            table_wrapper: settings.table_wrapper.clone(),
+            media_titles: settings.media_titles,
+            wikilink_media,
+        }
+    }
+}
+
+create_formatter!(NoteFormatter<FormatState>, {
+    NodeValue::Paragraph => |context, node, entering| {
+        let caption = match context.user.media_titles {
+            MediaTitlePlacement::Figcaption if !is_tight(node) => {
+                figure_caption(node, &context.user.wikilink_media)
+            }
+            _ => None,
+        };
+
+        if let Some(caption) = caption {
+            if entering {
+                context.cr()?;
+                context.write_all(b"<figure")?;
+                if let Some(block_id) = block_id(node) {
+                    context.write_all(b" id=\"")?;
+                    context.escape(block_id.as_bytes())?;
+                    context.write_all(b"\"")?;
+                }
+                context.write_all(b">")?;
+            } else {
+                context.write_all(b"<figcaption>")?;
+                context.escape(caption.as_bytes())?;
+                context.write_all(b"</figcaption></figure>\n")?;
+            }
+        } else if entering
+            && !is_tight(node)
+            && let Some(block_id) = block_id(node)
+        {
+            context.cr()?;
+            context.write_all(b"<p id=\"")?;
+            context.escape(block_id.as_bytes())?;
+            context.write_all(b"\">")?;
+        } else {
+            return format_node_default(context, node, entering);
+        }
+    },
+    NodeValue::Item(_) => |context, node, entering| {
+        let tight_block_id = node
+            .children()
+            .filter(|child| is_tight(child))
+            .find_map(block_id);
+
+        if entering && let Some(block_id) = tight_block_id {
+            context.cr()?;
+            context.write_all(b"<li id=\"")?;
+            context.escape(block_id.as_bytes())?;
+            context.write_all(b"\">")?;
+        } else {
+            return format_node_default(context, node, entering);
+        }
+    },
+    NodeValue::TaskItem(symbol) => |context, node, entering| {
+        if entering && context.user.task_lists == TaskListMode::Interactive {
+            context.user.tasks += 1;
+            let task = context.user.tasks;
+            let checked = if symbol.is_some() { " checked=\"\"" } else { "" };
+            context.cr()?;
+            write!(
+                context,
+                "<li><input type=\"checkbox\"{checked} data-task-id=\"task-{task}\" /> "
+            )?;
+        } else {
+            return format_node_default(context, node, entering);
+        }
+    },
+    NodeValue::Table(_) => |context, node, entering| {
+        let Some(class) = context.user.table_wrapper.clone() else {
+            return format_node_default(context, node, entering);
+        };
+
+        if entering {
+            context.cr()?;
+            context.write_all(b"<div class=\"")?;
+            context.escape(class.as_bytes())?;
+            context.write_all(b"\">\n")?;
+            return format_node_default(context, node, entering);
+        }
+        let rendering = format_node_default(context, node, entering)?;
+        context.write_all(b"</div>\n")?;
+        return Ok(rendering);
+    },
+    NodeValue::Text(ref text) => |context, node, entering| {
+        match block_id_marker(node) {
+            Some(marker_start) if entering => context.escape(&text.as_bytes()[..marker_start])?,
+            _ => return format_node_default(context, node, entering),
+        }
+    },
+});
+
+/// Adds an id derived from its text to every heading, e.g. `Getting Started`
+/// becomes `getting-started`. Repeated ids get a counter appended
+/// (`setup-1`), every id starts with the prefix.
+pub fn add_heading_ids(html: &str, prefix: &str) -> String {
+    let mut ids = HashSet::new();
+
+    HEADING_RE
+        .replace_all(html, |caps: &regex::Captures| {
+            if caps[1] != caps[3] {
+                return caps[0].to_string();
+            }
+
+            let slug = heading_slug(&MARKUP_RE.replace_all(&caps[2], ""));
+            let mut id = format!("{prefix}{slug}");
+            let mut counter = 0;
+            while !ids.insert(id.clone()) {
+                counter += 1;
+                id = format!("{prefix}{slug}-{counter}");
+            }
+
+            format!("<h{0} id=\"{id}\">{1}</h{0}>", &caps[1], &caps[2])
+        })
+        .into_owned()
+}
+
+/// Ids of the headings added by [add_heading_ids], including the prefix.
+pub fn heading_ids(html: &str) -> Vec<String> {
+    HEADING_ID_RE
+        .captures_iter(html)
+        .map(|caps| caps[1].to_string())
+        .collect()
+}
+
+/// The headings with an id added by [add_heading_ids] whose level is within
+/// `min_level..=max_level`, in the order they appear.
+pub fn table_of_contents(html: &str, min_level: u8, max_level: u8) -> Vec<TocEntry> {
+    HEADING_WITH_ID_RE
+        .captures_iter(html)
+        .filter_map(|caps| {
+            let level = caps[1].parse::<u8>().ok()?;
+            (min_level..=max_level).contains(&level).then(|| TocEntry {
+                level,
+                id: caps[2].to_string(),
+                title: TAG_RE.replace_all(&caps[3], "").trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Lowercases the alphanumerics of the heading and joins the words with `-`.
+pub fn heading_slug(heading: &str) -> String {
+    heading
+        .split(|char: char| char.is_whitespace() || char == '-')
+        .map(|word| {
+            word.chars()
+                .filter(|char| char.is_alphanumeric() || *char == '_')
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+/// Returns the id of a paragraph marked with a trailing `^block-id`. The id
+/// includes the caret, so `[[note#^block-id]]` resolves to it.
+pub fn block_id<'a>(paragraph: &'a AstNode<'a>) -> Option<String> {
+    if !matches!(paragraph.data.borrow().value, NodeValue::Paragraph) {
+        return None;
+    }
+
+    let last_child = paragraph.last_child()?;
+    let borrowed = last_child.data.borrow();
+    let NodeValue::Text(ref text) = borrowed.value else {
+        return None;
+    };
+
+    BLOCK_ID_RE
+        .captures(text)
+        .map(|caps| format!("^{}", &caps[1]))
+}
+
+/// Returns where the block id marker starts, if the text node carries one.
+fn block_id_marker<'a>(text: &'a AstNode<'a>) -> Option<usize> {
+    let paragraph = text.parent()?;
+    if text.next_sibling().is_some() || block_id(paragraph).is_none() {
+        return None;
+    }
+
+    let borrowed = text.data.borrow();
+    let NodeValue::Text(ref text) = borrowed.value else {
+        return None;
+    };
+
+    BLOCK_ID_RE.find(text).map(|marker| marker.start())
+}
+
+/// Returns the title of the media wikilink image if it's the only content of
+/// the paragraph besides a block id, so the paragraph can be rendered as a
+/// figure with the title as caption. Titled markdown images stay as they are.
+fn figure_caption<'a>(
+    paragraph: &'a AstNode<'a>,
+    wikilink_media: &HashSet<String>,
+) -> Option<String> {
+    let image = paragraph.first_child()?;
+    if let Some(rest) = image.next_sibling()
+        && (rest.next_sibling().is_some() || block_id_marker(rest) != Some(0))
+    {
+        return None;
+    }
+
+    match &image.data.borrow().value {
+        NodeValue::Image(link) if !link.title.is_empty() && wikilink_media.contains(&link.url) => {
+            Some(link.title.clone())
+        }
+        _ => None,
+    }
+}
+
+/// Whether the paragraph is part of a tight list and therefore rendered
+/// without its own `<p>` element.
+fn is_tight<'a>(paragraph: &'a AstNode<'a>) -> bool {
+    match paragraph
+        .parent()
+        .and_then(|item| item.parent())
+        .map(|list| list.data.borrow().value.clone())
+    {
+        Some(NodeValue::List(list)) => list.tight,
+        _ => false,
+    }
+}
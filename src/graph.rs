@@ -0,0 +1,195 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::post_note::PostNote;
+use crate::retry;
+use crate::settings::RetrySettings;
+
+/// File name of the exported link graph.
+pub const GRAPH_FILE: &str = "graph.json";
+
+/// The published notes and the links between them, shaped like the input
+/// of force-graph and most D3 force layouts.
+#[derive(Debug, Serialize)]
+pub struct Graph<'a> {
+    pub nodes: Vec<GraphNode<'a>>,
+    /// The edges, named `links` as the layouts expect them.
+    pub links: Vec<GraphEdge<'a>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct GraphNode<'a> {
+    /// The link of the note, referenced by the edges.
+    pub id: &'a str,
+    pub title: &'a str,
+    pub link: &'a str,
+    /// Number of notes linking to this one.
+    pub backlinks: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct GraphEdge<'a> {
+    pub source: &'a str,
+    pub target: &'a str,
+}
+
+/// Returns the published notes linking to each published note, keyed by the
+/// link of the linked note. Links to missing notes and links of a note to
+/// itself don't count.
+pub fn backlinks(notes: &[PostNote]) -> HashMap<&str, BTreeSet<&str>> {
+    let mut backlinks = notes
+        .iter()
+        .filter(|note| note.is_published())
+        .map(|note| (&*note.file_name, BTreeSet::new()))
+        .collect::<HashMap<&str, BTreeSet<&str>>>();
+
+    for note in notes.iter().filter(|note| note.is_published()) {
+        for link in &note.internal_links {
+            if link.target() == &*note.file_name {
+                continue;
+            }
+            if let Some(sources) = backlinks.get_mut(link.target()) {
+                sources.insert(&note.file_name);
+            }
+        }
+    }
+
+    backlinks
+}
+
+impl<'a> Graph<'a> {
+    /// Builds the graph of the published notes, notes without any links are
+    /// kept as nodes without edges.
+    pub fn new(notes: &'a [PostNote]) -> Self {
+        let backlinks = backlinks(notes);
+        let titles = notes
+            .iter()
+            .filter(|note| note.is_published())
+            .map(|note| (&*note.file_name, note.properties.title.as_str()))
+            .collect::<BTreeMap<&str, &str>>();
+
+        let nodes = titles
+            .iter()
+            .map(|(link, title)| GraphNode {
+                id: link,
+                title,
+                link,
+                backlinks: backlinks.get(link).map_or(0, BTreeSet::len),
+            })
+            .collect();
+        let links = backlinks
+            .iter()
+            .flat_map(|(target, sources)| sources.iter().map(|source| GraphEdge { source, target }))
+            .collect::<BTreeSet<GraphEdge>>()
+            .into_iter()
+            .collect();
+
+        Self { nodes, links }
+    }
+}
+
+/// Writes the link graph of the published notes as `graph.json`.
+///
+/// # Errors
+///
+/// Returns an error if the file could not be written.
+pub fn write_graph(
+    notes: &[PostNote],
+    output_path: &Path,
+    retry: &RetrySettings,
+) -> anyhow::Result<()> {
+    let graph = Graph::new(notes);
+    let path = output_path.join(GRAPH_FILE);
+
+    retry::write(retry, &path, serde_json::to_string(&graph)?)?;
+    log::info!(
+        "Created the link graph with {} node(s) and {} edge(s) at: {}",
+        graph.nodes.len(),
+        graph.links.len(),
+        path.display()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post_note;
+    use pretty_assertions::assert_eq;
+
+    fn parse_public(file_name: &str, content: &str) -> PostNote {
+        let raw_md = format!(
+            "---\ntitle: {file_name}\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n{content}"
+        );
+        post_note::parse_public(file_name, &raw_md)
+    }
+
+    #[test]
+    fn test_graph_has_every_note_and_their_links() {
+        let notes = vec![
+            parse_public("a.md", "[[b]] [[b#Setup]] [[a]] [[missing]]"),
+            parse_public("b.md", "[[a]]"),
+            parse_public("orphan.md", "No links"),
+        ];
+
+        let graph = Graph::new(&notes);
+
+        assert_eq!(3, graph.nodes.len());
+        assert_eq!(
+            vec![
+                GraphEdge {
+                    source: "a.html",
+                    target: "b.html"
+                },
+                GraphEdge {
+                    source: "b.html",
+                    target: "a.html"
+                },
+            ],
+            graph.links
+        );
+        assert_eq!(
+            vec![
+                GraphNode {
+                    id: "a.html",
+                    title: "a.md",
+                    link: "a.html",
+                    backlinks: 1,
+                },
+                GraphNode {
+                    id: "b.html",
+                    title: "b.md",
+                    link: "b.html",
+                    backlinks: 1,
+                },
+                GraphNode {
+                    id: "orphan.html",
+                    title: "orphan.md",
+                    link: "orphan.html",
+                    backlinks: 0,
+                },
+            ],
+            graph.nodes
+        );
+    }
+
+    #[test]
+    fn test_graph_json_is_written() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let notes = vec![parse_public("a.md", "[[b]]"), parse_public("b.md", "")];
+
+        write_graph(&notes, output_dir.path(), &RetrySettings::default()).unwrap();
+
+        let graph: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(output_dir.path().join(GRAPH_FILE)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::json!([{"source": "a.html", "target": "b.html"}]),
+            graph["links"]
+        );
+        assert_eq!(2, graph["nodes"].as_array().unwrap().len());
+    }
+}
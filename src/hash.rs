@@ -0,0 +1,9 @@
+use sha2::{Digest, Sha256};
+
+/// Hex encoded SHA-256 of the bytes, stable across runs and platforms.
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
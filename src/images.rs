@@ -0,0 +1,192 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use image::DynamicImage;
+use image::codecs::avif::AvifEncoder;
+use rayon::prelude::*;
+
+use crate::retry;
+use crate::settings::{ImageSettings, RetrySettings};
+
+/// Extensions of the raster images variants are written for.
+const RASTER_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg"];
+/// Encoding speed of the AVIF variants from 1 (slowest) to 10 (fastest).
+const AVIF_SPEED: u8 = 6;
+
+/// Formats a variant can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Webp,
+    Avif,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Webp => "webp",
+            Format::Avif => "avif",
+        }
+    }
+}
+
+/// Writes the enabled variants of every raster image beside it, appending
+/// the format to the file name, e.g. `photo.png.webp`, so templates can
+/// offer them as `<picture>` sources. Other files are left alone, just like
+/// images whose variants are newer than the image itself.
+///
+/// An image which couldn't be converted only logs a warning, the build goes
+/// on. Returns the number of variants written.
+pub fn write_image_variants(
+    paths: &[PathBuf],
+    settings: &ImageSettings,
+    retry: &RetrySettings,
+) -> usize {
+    let formats = [(settings.webp, Format::Webp), (settings.avif, Format::Avif)]
+        .into_iter()
+        .filter_map(|(enabled, format)| enabled.then_some(format))
+        .collect::<Vec<Format>>();
+    if formats.is_empty() {
+        return 0;
+    }
+
+    let written = paths
+        .par_iter()
+        .filter(|path| is_raster_image(path))
+        .map(|path| {
+            let outdated = formats
+                .iter()
+                .map(|format| (*format, variant_path(path, *format)))
+                .filter(|(_, variant)| !is_up_to_date(path, variant))
+                .collect::<Vec<(Format, PathBuf)>>();
+            if outdated.is_empty() {
+                return 0;
+            }
+
+            let image = match image::open(path) {
+                Ok(image) => image,
+                Err(err) => {
+                    log::warn!("Could not decode the image {}: {}", path.display(), err);
+                    return 0;
+                }
+            };
+            outdated
+                .into_iter()
+                .filter(|(format, variant)| {
+                    match encode(&image, *format, variant, settings, retry) {
+                        Ok(()) => true,
+                        Err(err) => {
+                            log::warn!("Could not write {}: {:#}", variant.display(), err);
+                            false
+                        }
+                    }
+                })
+                .count()
+        })
+        .sum();
+    log::info!("Wrote {} image variant(s).", written);
+
+    written
+}
+
+fn is_raster_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            RASTER_EXTENSIONS
+                .iter()
+                .any(|raster| raster.eq_ignore_ascii_case(extension))
+        })
+}
+
+fn variant_path(path: &Path, format: Format) -> PathBuf {
+    let mut variant = path.as_os_str().to_owned();
+    variant.push(".");
+    variant.push(format.extension());
+
+    PathBuf::from(variant)
+}
+
+/// Whether the variant was written after the image was last modified.
+fn is_up_to_date(image: &Path, variant: &Path) -> bool {
+    let (Ok(image), Ok(variant)) = (fs::metadata(image), fs::metadata(variant)) else {
+        return false;
+    };
+
+    matches!(
+        (image.modified(), variant.modified()),
+        (Ok(image), Ok(variant)) if variant >= image
+    )
+}
+
+fn encode(
+    image: &DynamicImage,
+    format: Format,
+    path: &Path,
+    settings: &ImageSettings,
+    retry: &RetrySettings,
+) -> anyhow::Result<()> {
+    match format {
+        Format::Webp => {
+            let rgba = image.to_rgba8();
+            let encoded = webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height())
+                .encode(f32::from(settings.webp_quality.min(100)));
+            retry::write(retry, path, &*encoded)?;
+        }
+        Format::Avif => {
+            // Encoded in memory first, a failed encoding must not leave a
+            // truncated variant which looks up to date to the next build.
+            let mut encoded = Vec::new();
+            let encoder = AvifEncoder::new_with_speed_quality(
+                &mut encoded,
+                AVIF_SPEED,
+                settings.avif_quality,
+            );
+            image
+                .write_with_encoder(encoder)
+                .map_err(|err| anyhow!("Could not encode the AVIF: {err}"))?;
+            retry::write(retry, path, encoded)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, RgbImage};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_png_gains_webp_variant_with_same_dimensions() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let png = output_dir.path().join("photo.png");
+        let text = output_dir.path().join("notes.txt");
+        RgbImage::from_pixel(3, 2, image::Rgb([200, 40, 90]))
+            .save_with_format(&png, ImageFormat::Png)
+            .unwrap();
+        fs::write(&text, "Not an image").unwrap();
+        let settings = ImageSettings {
+            webp: true,
+            ..Default::default()
+        };
+
+        let written = write_image_variants(
+            &[png.clone(), text.clone()],
+            &settings,
+            &RetrySettings::default(),
+        );
+
+        assert_eq!(1, written);
+        let webp = output_dir.path().join("photo.png.webp");
+        let variant = image::open(&webp).unwrap();
+        assert_eq!((3, 2), (variant.width(), variant.height()));
+        assert!(!output_dir.path().join("notes.txt.webp").exists());
+        // The variant is up to date now.
+        assert_eq!(
+            0,
+            write_image_variants(&[png, text], &settings, &RetrySettings::default())
+        );
+    }
+}
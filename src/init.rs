@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::settings::{CONFIG_PATH, Settings, render_config};
+
+const BASE_TEMPLATE: &str = include_str!("builtin/init/base.html");
+const STYLESHEET: &str = include_str!("builtin/init/style.css");
+const WELCOME_NOTE: &str = include_str!("builtin/init/welcome.md");
+
+/// Comment heading the scaffolded `Config.toml`.
+const CONFIG_HEADER: &str = "\
+# Settings of post-notes, every value below is the default.
+#
+# Remove the settings you don't want to change, each of them can also be
+# overridden by a command line argument, see `post-notes --help`.
+
+";
+
+/// Scaffolds a project which builds as it is into the directory: the notes,
+/// templates and assets directories at their default paths and a
+/// `Config.toml` listing the default settings.
+///
+/// Nothing is written if any of the files already exists, unless `force` is
+/// set. Returns the paths of the written files.
+pub fn init(dir: &Path, force: bool) -> Result<Vec<PathBuf>> {
+    let defaults = Settings::default();
+    let config = format!("{CONFIG_HEADER}{}", render_config(&defaults)?);
+    let templates = dir.join(&defaults.path.template);
+    let assets = dir.join(&defaults.path.assets[0]);
+    let files = [
+        (dir.join(CONFIG_PATH), config.as_str()),
+        (templates.join("base.html"), BASE_TEMPLATE),
+        (assets.join("css/style.css"), STYLESHEET),
+        (
+            dir.join(&defaults.path.input).join("welcome.md"),
+            WELCOME_NOTE,
+        ),
+    ];
+
+    let existing = files
+        .iter()
+        .filter(|(path, _)| path.exists())
+        .map(|(path, _)| path.display().to_string())
+        .collect::<Vec<String>>();
+    if !existing.is_empty() && !force {
+        anyhow::bail!(
+            "Refusing to overwrite {}; pass `--force` to overwrite them",
+            existing.join(", ")
+        );
+    }
+
+    files
+        .into_iter()
+        .map(|(path, content)| {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Could not create {}", parent.display()))?;
+            }
+            fs::write(&path, content)
+                .with_context(|| format!("Could not write {}", path.display()))?;
+            log::info!("Created {}", path.display());
+
+            Ok(path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::load_templates;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_init_scaffolds_a_buildable_project() {
+        let project_dir = tempfile::tempdir().unwrap();
+        let project = project_dir.path();
+
+        let written = init(project, false).unwrap();
+
+        assert_eq!(4, written.len());
+        for file in [
+            "Config.toml",
+            "templates/base.html",
+            "assets/css/style.css",
+            "notes/welcome.md",
+        ] {
+            assert!(project.join(file).is_file(), "{file} is missing");
+        }
+        let config = fs::read_to_string(project.join("Config.toml")).unwrap();
+        assert!(config.starts_with("# Settings of post-notes"));
+        let settings = config::Config::builder()
+            .add_source(config::File::from_str(&config, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize::<Settings>()
+            .unwrap();
+        assert_eq!(Settings::default(), settings);
+
+        let mut settings = Settings::default();
+        settings.path.input = project.join("notes");
+        settings.path.output = project.join("output");
+        settings.path.template = project.join("templates");
+        settings.path.assets = vec![project.join("assets")];
+        settings.path.volatile = project.join(".post_notes");
+        let tera = load_templates(&settings.path.template, false).unwrap();
+        crate::generate(&settings, &tera).unwrap();
+
+        assert!(project.join("output/welcome.html").is_file());
+        assert!(project.join("output/css/style.css").is_file());
+    }
+
+    #[test]
+    fn test_init_refuses_to_overwrite_without_force() {
+        let project_dir = tempfile::tempdir().unwrap();
+        let config = project_dir.path().join("Config.toml");
+        fs::write(&config, "[site]\ntitle = \"Mine\"\n").unwrap();
+
+        let err = init(project_dir.path(), false).unwrap_err();
+
+        assert!(err.to_string().contains("Refusing to overwrite"));
+        assert_eq!(
+            "[site]\ntitle = \"Mine\"\n",
+            fs::read_to_string(&config).unwrap()
+        );
+        assert!(!project_dir.path().join("templates").exists());
+
+        init(project_dir.path(), true).unwrap();
+
+        assert!(
+            fs::read_to_string(&config)
+                .unwrap()
+                .starts_with("# Settings of post-notes")
+        );
+    }
+}
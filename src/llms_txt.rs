@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::feed::absolute_url;
+use crate::navigation::{Navigation, TagNode};
+use crate::post_note::PostNote;
+use crate::retry;
+use crate::settings::Settings;
+
+pub const LLMS_TXT_FILE: &str = "llms.txt";
+/// Heading of the section listing the notes without tags.
+const UNTAGGED_SECTION: &str = "Other";
+
+/// Writes the `llms.txt`, a plain markdown index of all notes grouped by
+/// their top-level tag, as proposed by <https://llmstxt.org>.
+///
+/// The index requires absolute links, so it only gets written if a base url
+/// is configured.
+///
+/// # Errors
+///
+/// Returns an error if the file could not be written.
+pub fn write_llms_txt(
+    notes: &[PostNote],
+    navigation: &Navigation,
+    settings: &Settings,
+    output_path: &Path,
+) -> anyhow::Result<()> {
+    let Some(base_url) = settings.site.base_url.as_deref() else {
+        log::warn!("Skipping llms.txt, because no base url is configured.");
+        return Ok(());
+    };
+
+    let path = output_path.join(LLMS_TXT_FILE);
+    retry::write(
+        &settings.build.retry,
+        &path,
+        render_llms_txt(notes, navigation, settings, base_url),
+    )?;
+    log::info!("Created the llms.txt at: {}", path.display());
+
+    Ok(())
+}
+
+fn render_llms_txt(
+    notes: &[PostNote],
+    navigation: &Navigation,
+    settings: &Settings,
+    base_url: &str,
+) -> String {
+    let notes_by_link = notes
+        .iter()
+        .filter(|note| note.is_published())
+        .map(|note| (&*note.file_name, note))
+        .collect::<HashMap<&str, &PostNote>>();
+    let render_section = |heading: &str, files: Vec<&str>| {
+        let entries = files
+            .into_iter()
+            .filter_map(|file| notes_by_link.get(file))
+            .map(|note| {
+                let description = match note.properties.description.trim() {
+                    "" => String::new(),
+                    description => format!(": {description}"),
+                };
+                format!(
+                    "- [{}]({}){}\n",
+                    note.properties.title,
+                    absolute_url(base_url, &note.file_name),
+                    description
+                )
+            })
+            .collect::<String>();
+
+        format!("\n## {heading}\n\n{entries}")
+    };
+
+    let mut llms_txt = format!("# {}\n", settings.site.title);
+    if !settings.site.description.is_empty() {
+        llms_txt.push_str(&format!("\n> {}\n", settings.site.description));
+    }
+    for tag in &navigation.root.child_tags {
+        llms_txt.push_str(&render_section(&tag.tag, section_files(tag)));
+    }
+    // Notes without tags aren't part of the navigation.
+    let mut untagged = notes
+        .iter()
+        .filter(|note| note.is_published() && note.properties.tags.is_empty())
+        .map(|note| &*note.file_name)
+        .collect::<Vec<&str>>();
+    if !untagged.is_empty() {
+        untagged.sort_unstable();
+        llms_txt.push_str(&render_section(UNTAGGED_SECTION, untagged));
+    }
+
+    llms_txt
+}
+
+/// Files of the top-level tag and all tags nested under it.
+fn section_files(tag: &TagNode) -> Vec<&str> {
+    tag.all_files().into_iter().map(|file| &**file).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post_note;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    fn parse_public(file_name: &str, title: &str, tags: &str) -> PostNote {
+        let raw_md = format!(
+            "---\ntitle: {title}\ndescription: About {title}\ntags: {tags}\ncreated: 2025-05-23T13:35\npublic: true\n---\n"
+        );
+        post_note::parse_public(file_name, &raw_md)
+    }
+
+    #[test]
+    fn test_llms_txt_lists_notes_under_top_level_tag() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let notes = vec![
+            parse_public("async.md", "Async", "[rust/async]"),
+            parse_public("traits.md", "Traits", "[rust]"),
+            parse_public("garden.md", "Garden", "[]"),
+        ];
+        let mut settings = Settings::default();
+        settings.site.base_url = Some("https://example.com/".to_string());
+
+        write_llms_txt(
+            &notes,
+            &Navigation::from(&notes),
+            &settings,
+            output_dir.path(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            "# post-notes\n\n> Building a cute digital garden.\n\n\
+             ## rust\n\n\
+             - [Async](https://example.com/async.html): About Async\n\
+             - [Traits](https://example.com/traits.html): About Traits\n\n\
+             ## Other\n\n\
+             - [Garden](https://example.com/garden.html): About Garden\n",
+            fs::read_to_string(output_dir.path().join(LLMS_TXT_FILE)).unwrap()
+        );
+    }
+}
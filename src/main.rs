@@ -1,19 +1,72 @@
 use anyhow::{Context, Result};
 use rayon::prelude::*;
-use std::{fs, path::PathBuf};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{fs, thread};
 
 mod builder;
+mod callout;
+mod clip;
 mod content_map;
+mod date;
+mod diagnostics;
+mod error;
+mod escape;
+mod external_links;
+mod feed;
+mod formatter;
+mod graph;
+mod hash;
+mod images;
+mod init;
+mod llms_txt;
 mod navigation;
+mod parse_cache;
+mod pdf;
 mod post_note;
+mod progress;
+mod retry;
+mod serve;
 mod settings;
+mod single_page;
+mod sitemap;
+mod template_check;
+mod template_helpers;
+mod validation;
+mod volatile;
+mod watch;
 
-use builder::build;
+use builder::{build, load_templates};
 use content_map::ContentMap;
+use diagnostics::{Category, Diagnostic, Diagnostics};
+use error::Error;
+use external_links::check_external_links;
+use init::init;
 use navigation::Navigation;
-use post_note::{PostNote, PostNoteEntry};
+use parse_cache::ParseCache;
+use post_note::{
+    InternalLink, NoteIndex, PostNote, PostNoteEntry, collect_colocated_assets, find_home_note,
+    is_note_file, link_neighbors, link_series, relocate_media, relocate_notes,
+};
+use progress::Progress;
+use serve::Server;
+use tera::Tera;
+use validation::{
+    enforce_private_link_policy, mark_internal_links, strip_private_links, validate_links,
+};
+use volatile::prepare_volatile;
+use watch::watch;
 
-use crate::settings::get_settings;
+use crate::settings::{
+    Command, ContentSettings, FilterSettings, PrivateLinkPolicy, SchemaPolicy, Settings,
+    ensure_directory, get_command,
+};
+
+/// Name of the file setting front matter defaults for the notes within its
+/// directory.
+const DIRECTORY_CONFIG_FILE: &str = ".postnotes.toml";
 
 fn main() -> Result<()> {
     print!(
@@ -44,15 +97,113 @@ fn main() -> Result<()> {
     colog::init();
 
     log::info!("=== Loading Settings ===");
-    let settings = get_settings();
+    let settings = match get_command() {
+        Command::Build(settings) => *settings,
+        Command::Init { force } => {
+            init(Path::new("."), force).context("Could not scaffold the project")?;
+            return Ok(());
+        }
+    };
+    prepare_volatile(&settings.path, settings.build.clear_cache)?;
 
     println!();
 
+    let tera = load_templates(
+        &settings.path.template,
+        settings.build.builtin_template_fallback,
+    )?;
+    generate(&settings, &tera)?;
+
+    if settings.serve.enabled {
+        println!();
+        let server = Server::bind(&settings.path.output, settings.serve.port)?;
+        log::info!("=== Serving the website at {}. ===", server.url());
+        if !settings.build.watch {
+            server.run();
+            return Ok(());
+        }
+        thread::spawn(move || server.run());
+    }
+
+    if settings.build.watch {
+        println!();
+        watch(&settings, tera, |tera| generate(&settings, tera));
+    }
+
+    Ok(())
+}
+
+/// Loads all notes and builds the website from them. If enabled, the
+/// diagnostics are written to the volatile directory even if the build fails.
+fn generate(settings: &Settings, tera: &Tera) -> Result<()> {
+    let diagnostics = Diagnostics::default();
+    let result = generate_site(settings, tera, &diagnostics);
+    if settings.build.emit_diagnostics {
+        diagnostics
+            .write(&settings.path.volatile)
+            .context("Failed to write the diagnostics")?;
+    }
+
+    result
+}
+
+fn generate_site(settings: &Settings, tera: &Tera, diagnostics: &Diagnostics) -> Result<()> {
     log::info!(
         "=== Starting to load content from {}. ===",
         &settings.path.input.display()
     );
-    let post_notes = load_content(&settings.path.input).context("Failed to load content")?;
+    let LoadedContent {
+        mut post_notes,
+        private_notes,
+        stats,
+    } = load_content(settings, diagnostics).context("Failed to load content")?;
+    log::info!("Loaded notes: {stats}");
+    if settings.validation.private_links == PrivateLinkPolicy::Strip {
+        strip_private_links(&mut post_notes, &private_notes);
+    }
+    if settings.validation.link_classes.enabled {
+        mark_internal_links(&mut post_notes, &settings.validation.link_classes);
+    }
+    if let Some(pattern) = &settings.build.colocated_assets {
+        collect_colocated_assets(&mut post_notes, pattern, &settings.content.note_extensions)?;
+    }
+    if let Some(media_dir) = &settings.build.media_output_dir {
+        relocate_media(&mut post_notes, media_dir);
+    }
+    let home_note = settings
+        .build
+        .home_note
+        .as_deref()
+        .map(|home_note| find_home_note(&post_notes, home_note))
+        .transpose()?;
+    relocate_notes(
+        &mut post_notes,
+        settings.build.categorized_output,
+        settings.build.pretty_urls,
+        settings.site.base_path.as_deref(),
+        home_note.as_ref(),
+    );
+    link_neighbors(&mut post_notes);
+    link_series(&mut post_notes);
+
+    println!();
+
+    log::info!("=== Starting to validate links. ===");
+    let issues = validate_links(
+        &post_notes,
+        &private_notes,
+        &settings.validation,
+        settings.content.heading_ids,
+    );
+    for issue in &issues {
+        diagnostics.push(Diagnostic::from(issue));
+    }
+    enforce_private_link_policy(&issues, settings.validation.private_links)?;
+    if settings.external_links.enabled {
+        for issue in check_external_links(&post_notes, &settings.external_links)? {
+            diagnostics.push(Diagnostic::from(&issue));
+        }
+    }
 
     println!();
 
@@ -60,24 +211,204 @@ fn main() -> Result<()> {
         "=== Starting to generate content map with {} entrie(s). ===",
         post_notes.len()
     );
-    let content_map = ContentMap::from(&post_notes);
+    let content_map = ContentMap::new(&post_notes, &settings.content_map.fields);
 
     println!();
 
     log::info!("=== Starting to generate navigation. ===");
-    let navigation = Navigation::from(&post_notes);
+    let navigation = Navigation::new(&post_notes, &settings.navigation);
 
     println!();
 
     log::info!("=== Starting to build website. ===");
-    build(&post_notes, content_map, navigation, &settings).context("Failed to build website")?;
+    build(
+        &post_notes,
+        content_map,
+        navigation,
+        tera,
+        settings,
+        diagnostics,
+    )
+    .context("Failed to build website")?;
 
     Ok(())
 }
 
-fn load_content(location: &PathBuf) -> Result<Vec<PostNote>> {
-    Ok(fs::read_dir(location)?
-        .par_bridge()
+/// Tally of how the scanned notes were handled while loading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct LoadStats {
+    scanned: usize,
+    loaded: usize,
+    /// Loaded notes taken from the parse cache instead of being parsed.
+    cached: usize,
+    skipped_private: usize,
+    skipped_empty: usize,
+    read_errors: usize,
+    parse_errors: usize,
+    /// Notes violating the schema, whether they were kept or dropped.
+    schema_violations: usize,
+    schema_policy: SchemaPolicy,
+}
+
+impl fmt::Display for LoadStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} file(s) scanned, {} loaded ({} cached), {} skipped as private, {} skipped as empty, {} unreadable, {} failed to parse, {} violated the schema (policy: {})",
+            self.scanned,
+            self.loaded,
+            self.cached,
+            self.skipped_private,
+            self.skipped_empty,
+            self.read_errors,
+            self.parse_errors,
+            self.schema_violations,
+            self.schema_policy
+        )
+    }
+}
+
+/// The public notes and the names of the private ones.
+#[derive(Debug)]
+struct LoadedContent {
+    post_notes: Vec<PostNote>,
+    private_notes: HashSet<InternalLink>,
+    stats: LoadStats,
+}
+
+/// Why a note couldn't be loaded, the details are logged when it happens.
+enum LoadFailure {
+    Read,
+    Parse,
+}
+
+fn load_content(settings: &Settings, diagnostics: &Diagnostics) -> Result<LoadedContent> {
+    let location = &settings.path.input;
+    ensure_directory(location, "--input", "path.input")?;
+
+    let (paths, content_settings): (Vec<PathBuf>, Vec<Arc<ContentSettings>>) =
+        collect_notes(location, Arc::new(settings.content.clone()))?
+            .into_iter()
+            .unzip();
+    check_unique_links(&paths, location)?;
+
+    // Collect the names of all notes first, so wikilinks can be resolved
+    // against them while parsing.
+    let index = NoteIndex::new(&paths);
+    let parse_cache = settings
+        .build
+        .parse_cache
+        .then(|| ParseCache::new(&settings.path.volatile, &index));
+
+    let progress = Progress::new("Loading notes", paths.len(), settings.build.progress);
+    let entries = paths
+        .par_iter()
+        .zip(&content_settings)
+        .map(|(path_buf, content_settings)| {
+            let entry = load_post_note(
+                path_buf,
+                content_settings,
+                &index,
+                parse_cache.as_ref(),
+                diagnostics,
+            );
+            progress.inc();
+            entry
+        })
+        .collect::<Vec<Result<PostNoteEntry, LoadFailure>>>();
+    progress.finish();
+
+    let mut stats = LoadStats {
+        scanned: paths.len(),
+        schema_policy: settings.content.schema_policy,
+        ..Default::default()
+    };
+    let mut post_notes = Vec::new();
+    let mut private_notes = HashSet::new();
+    let mut fatal_violations = Vec::new();
+    for ((path_buf, content_settings), entry) in paths.iter().zip(&content_settings).zip(entries) {
+        match entry {
+            Ok(PostNoteEntry::Public(post_note)) => {
+                if post_note.properties.schema_violation.is_some() {
+                    stats.schema_violations += 1;
+                }
+                post_notes.push(*post_note);
+            }
+            Ok(PostNoteEntry::Private) => {
+                stats.skipped_private += 1;
+                private_notes.extend(InternalLink::try_from(path_buf.clone()));
+            }
+            Ok(PostNoteEntry::Empty) => stats.skipped_empty += 1,
+            Ok(PostNoteEntry::SchemaViolation(violation)) => {
+                stats.schema_violations += 1;
+                if content_settings.schema_policy == SchemaPolicy::Fail {
+                    fatal_violations.push(violation);
+                }
+            }
+            Err(LoadFailure::Read) => stats.read_errors += 1,
+            Err(LoadFailure::Parse) => stats.parse_errors += 1,
+        }
+    }
+    stats.loaded = post_notes.len();
+    stats.cached = parse_cache.as_ref().map_or(0, ParseCache::hits);
+    if let Some(parse_cache) = &parse_cache {
+        parse_cache.prune();
+    }
+
+    if !fatal_violations.is_empty() {
+        anyhow::bail!(
+            "{} note(s) violate the schema ({}):\n{}",
+            fatal_violations.len(),
+            stats,
+            fatal_violations.join("\n")
+        );
+    }
+
+    let post_notes = filter_notes(post_notes, &settings.filter);
+    if post_notes.is_empty() && !settings.build.allow_empty {
+        anyhow::bail!(
+            "No public notes found in {} ({}); pass `--allow-empty` or set `build.allow_empty` to build anyway",
+            location.display(),
+            stats
+        );
+    }
+
+    Ok(LoadedContent {
+        post_notes,
+        private_notes,
+        stats,
+    })
+}
+
+/// Recursively collects the notes below the directory, skipping hidden
+/// directories, together with the content settings in effect for each note.
+///
+/// A [DIRECTORY_CONFIG_FILE] adds front matter defaults for all notes within
+/// its directory and subdirectories, overriding the ones inherited.
+///
+/// # Errors
+///
+/// Returns an error if a directory can't be read or a directory config is
+/// invalid.
+fn collect_notes(
+    directory: &Path,
+    mut settings: Arc<ContentSettings>,
+) -> Result<Vec<(PathBuf, Arc<ContentSettings>)>> {
+    let config_path = directory.join(DIRECTORY_CONFIG_FILE);
+    if config_path.is_file() {
+        let defaults = config::Config::builder()
+            .add_source(config::File::from(config_path.as_path()))
+            .build()
+            .and_then(|config| config.try_deserialize::<BTreeMap<String, serde_yaml::Value>>())
+            .with_context(|| format!("Invalid directory config {}", config_path.display()))?;
+        log::debug!("Applying the directory config {}", config_path.display());
+        Arc::make_mut(&mut settings)
+            .front_matter
+            .defaults
+            .extend(defaults);
+    }
+
+    let mut entries = fs::read_dir(directory)?
         .filter_map(|entry_result| match entry_result {
             Ok(entry) => Some(entry.path()),
             Err(err) => {
@@ -85,52 +416,847 @@ fn load_content(location: &PathBuf) -> Result<Vec<PostNote>> {
                 None
             }
         })
-        .filter(|path_buf| {
-            path_buf
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .map(|ext_str| ext_str == "md")
-                .unwrap_or(false)
+        .collect::<Vec<PathBuf>>();
+    entries.sort();
+
+    let mut notes = Vec::new();
+    for path_buf in entries {
+        if path_buf.is_dir() {
+            let hidden = path_buf
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if !hidden {
+                notes.extend(collect_notes(&path_buf, settings.clone())?);
+            }
+        } else if is_note_file(&path_buf, &settings.note_extensions) {
+            notes.push((path_buf, settings.clone()));
+        }
+    }
+
+    Ok(notes)
+}
+
+/// Makes sure no two notes get the same link, like `a/intro.md` and
+/// `b/intro.md`, which would be written to the same output file and share
+/// an item in the feeds and the sitemap.
+///
+/// # Errors
+///
+/// Returns an error listing the notes sharing a link.
+fn check_unique_links(paths: &[PathBuf], location: &Path) -> Result<()> {
+    let mut notes_by_link = BTreeMap::<InternalLink, Vec<&Path>>::new();
+    for path in paths {
+        if let Ok(link) = InternalLink::try_from(path.clone()) {
+            notes_by_link.entry(link).or_default().push(path);
+        }
+    }
+
+    let clashes = notes_by_link
+        .iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(link, paths)| {
+            let paths = paths
+                .iter()
+                .map(|path| {
+                    path.strip_prefix(location)
+                        .unwrap_or(path)
+                        .display()
+                        .to_string()
+                })
+                .collect::<Vec<String>>();
+            format!("{} <- {}", &**link, paths.join(", "))
         })
-        .filter_map(|path_buf| {
-            let raw_content = match fs::read_to_string(&path_buf) {
-                Ok(raw_content) => raw_content,
-                Err(err) => {
-                    log::error!(
-                        "Could not read content of {:?}: {}",
-                        path_buf.display(),
-                        err
-                    );
-                    return None;
-                }
-            };
+        .collect::<Vec<String>>();
+    if !clashes.is_empty() {
+        anyhow::bail!(
+            "Several notes in {} share a link, rename all but one of them:\n{}",
+            location.display(),
+            clashes.join("\n")
+        );
+    }
+
+    Ok(())
+}
 
-            Some((path_buf, raw_content))
+/// Applies the configured tag filters, so everything built afterwards only
+/// reflects the included notes.
+fn filter_notes(post_notes: Vec<PostNote>, settings: &FilterSettings) -> Vec<PostNote> {
+    if settings.only_tags.is_empty() && settings.exclude_tags.is_empty() {
+        return post_notes;
+    }
+
+    let carries_any = |note: &PostNote, filter_tags: &[String]| {
+        note.properties.tags.iter().any(|tag| {
+            filter_tags
+                .iter()
+                .any(|filter_tag| tag.is_within(filter_tag))
         })
-        .filter_map(|(path_buf, raw_md)| {
-            let post_note_entry = match PostNoteEntry::new(&path_buf, &raw_md) {
-                Ok(post_note_entry) => post_note_entry,
-                Err(err) => {
-                    log::error!(
-                        "Something went wrong while parsing post note {:?}: {}",
-                        &path_buf,
-                        err
-                    );
-                    return None;
-                }
-            };
+    };
+
+    post_notes
+        .into_iter()
+        .filter(|note| {
+            let included = settings.only_tags.is_empty() || carries_any(note, &settings.only_tags);
+            let excluded = carries_any(note, &settings.exclude_tags);
+
+            if !included || excluded {
+                log::info!("Filtered out note: {}", &*note.file_name);
+            }
+
+            included && !excluded
+        })
+        .collect()
+}
+
+/// Reads a note, naming the offset of the first invalid byte if it isn't
+/// valid UTF-8.
+fn read_note(path: &Path) -> Result<String, Error> {
+    let bytes = fs::read(path).map_err(|source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    String::from_utf8(bytes).map_err(|err| Error::Parse {
+        path: path.to_path_buf(),
+        source: anyhow::anyhow!(
+            "Not valid UTF-8, invalid byte at offset {}",
+            err.utf8_error().valid_up_to()
+        ),
+    })
+}
+
+/// Reads and parses a single note, logging why it won't be built. Public
+/// notes are taken from and added to the parse cache if there's one.
+fn load_post_note(
+    path_buf: &Path,
+    settings: &ContentSettings,
+    index: &NoteIndex,
+    parse_cache: Option<&ParseCache>,
+    diagnostics: &Diagnostics,
+) -> Result<PostNoteEntry, LoadFailure> {
+    let raw_md = match read_note(path_buf) {
+        Ok(raw_content) => raw_content,
+        Err(err) => {
+            let err = anyhow::Error::from(err);
+            log::error!("Could not read content: {:#}", err);
+            diagnostics.push(Diagnostic::new(
+                Category::ReadError,
+                format!("{err:#}"),
+                path_buf.display(),
+            ));
+            return Err(LoadFailure::Read);
+        }
+    };
+
+    let cached = parse_cache.and_then(|cache| cache.get(path_buf, &raw_md, settings));
+    let from_cache = cached.is_some();
+    let parsed = match cached {
+        Some(post_note) => Ok(PostNoteEntry::Public(Box::new(post_note))),
+        None => PostNoteEntry::new(path_buf, &raw_md, settings, index),
+    };
+    let post_note_entry = match parsed {
+        Ok(post_note_entry) => post_note_entry,
+        Err(err) => {
+            let err = anyhow::Error::from(err);
+            log::error!(
+                "Something went wrong while parsing post note {:?}: {:#}",
+                &path_buf,
+                err
+            );
+            diagnostics.push(Diagnostic::new(
+                Category::ParseError,
+                format!("{err:#}"),
+                path_buf.display(),
+            ));
+            return Err(LoadFailure::Parse);
+        }
+    };
+
+    match &post_note_entry {
+        PostNoteEntry::Public(post_note) => {
+            log::info!("Loaded public note: {:?}", &path_buf);
+            if let Some(cache) = parse_cache.filter(|_| !from_cache) {
+                cache.insert(path_buf, &raw_md, settings, post_note);
+            }
+            if let Some(violation) = &post_note.properties.schema_violation {
+                diagnostics.push(Diagnostic::new(
+                    Category::SchemaViolation,
+                    violation,
+                    path_buf.display(),
+                ));
+            }
+        }
+        PostNoteEntry::Private => log::info!("Skipping private note: {:?}", &path_buf),
+        PostNoteEntry::Empty => log::info!("Skipping empty note: {:?}", &path_buf),
+        PostNoteEntry::SchemaViolation(violation) => {
+            log::error!("Skipping note violating the schema: {violation}");
+            diagnostics.push(Diagnostic::new(
+                Category::SchemaViolation,
+                violation,
+                path_buf.display(),
+            ));
+        }
+    }
+
+    Ok(post_note_entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Pattern;
+    use pretty_assertions::assert_eq;
+
+    fn parse_public(file_name: &str, tags: &str) -> PostNote {
+        let raw_md = format!(
+            "---\ntitle: Note\ndescription: Description\ntags: {tags}\ncreated: 2025-05-23T13:35\npublic: true\n---\n"
+        );
+        post_note::parse_public(file_name, &raw_md)
+    }
 
-            let post_note = match post_note_entry {
-                PostNoteEntry::Public(post_note) => post_note,
-                PostNoteEntry::Private => {
-                    log::info!("Skipping private note: {:?}", &path_buf);
-                    return None;
+    #[test]
+    fn test_filter_notes_by_tag() {
+        let notes = vec![
+            parse_public("post.md", "[blog/rust]"),
+            parse_public("recipe.md", "[cooking]"),
+            parse_public("draft.md", "[blog, draft]"),
+            parse_public("announcement.md", "[Blog/News]"),
+            parse_public("sketch.md", "[Blog, Draft]"),
+        ];
+        let settings = FilterSettings {
+            only_tags: vec!["blog".to_string()],
+            exclude_tags: vec!["DRAFT".to_string()],
+        };
+
+        let produced = filter_notes(notes, &settings);
+
+        assert_eq!(
+            vec!["post.html", "announcement.html"],
+            produced
+                .iter()
+                .map(|note| &*note.file_name)
+                .collect::<Vec<&str>>()
+        );
+    }
+
+    #[test]
+    fn test_load_content_from_missing_directory() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let missing = input_dir.path().join("notes");
+        let mut settings = Settings::default();
+        settings.path.input = missing.clone();
+
+        let err = load_content(&settings, &Diagnostics::default()).unwrap_err();
+
+        assert!(err.to_string().contains(&missing.display().to_string()));
+        assert!(err.to_string().contains("--input"));
+    }
+
+    #[test]
+    fn test_load_content_without_public_notes() {
+        let input_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            input_dir.path().join("private.md"),
+            "---\ntitle: Private\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: false\n---\n",
+        )
+        .unwrap();
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+
+        let err = load_content(&settings, &Diagnostics::default()).unwrap_err();
+        assert!(err.to_string().contains("No public notes found"));
+        assert!(
+            err.to_string()
+                .contains("1 file(s) scanned, 0 loaded (0 cached), 1 skipped as private")
+        );
+
+        settings.build.allow_empty = true;
+        assert!(
+            load_content(&settings, &Diagnostics::default())
+                .unwrap()
+                .post_notes
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_load_content_with_all_notes_filtered_out() {
+        let input_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            input_dir.path().join("draft.md"),
+            "---\ntitle: Draft\ndescription: Description\ntags: [draft]\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+        )
+        .unwrap();
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+        settings.filter.exclude_tags = vec!["draft".to_string()];
+
+        let err = load_content(&settings, &Diagnostics::default()).unwrap_err();
+
+        assert!(err.to_string().contains("No public notes found"));
+    }
+
+    #[test]
+    fn test_read_note_with_invalid_utf8() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let path = input_dir.path().join("broken.md");
+        fs::write(&path, b"---\ntitle: \xff\n").unwrap();
+
+        let Err(Error::Parse {
+            path: produced,
+            source,
+        }) = read_note(&path)
+        else {
+            panic!("Expected a parse error");
+        };
+
+        assert_eq!(path, produced);
+        assert_eq!(
+            "Not valid UTF-8, invalid byte at offset 11",
+            source.to_string()
+        );
+    }
+
+    #[test]
+    fn test_read_missing_note_is_io_error() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let path = input_dir.path().join("missing.md");
+
+        let Err(Error::Io {
+            path: produced,
+            source,
+        }) = read_note(&path)
+        else {
+            panic!("Expected an I/O error");
+        };
+
+        assert_eq!(path, produced);
+        assert_eq!(std::io::ErrorKind::NotFound, source.kind());
+    }
+
+    #[test]
+    fn test_load_content_stats() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let front_matter = |public: bool| {
+            format!(
+                "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: {public}\n---\n"
+            )
+        };
+        fs::write(input_dir.path().join("public.md"), front_matter(true)).unwrap();
+        fs::write(input_dir.path().join("private.md"), front_matter(false)).unwrap();
+        fs::write(
+            input_dir.path().join("malformed.md"),
+            "---\ntitle: [\n---\n",
+        )
+        .unwrap();
+        fs::write(input_dir.path().join("binary.md"), [0xff, 0xfe]).unwrap();
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+
+        let LoadedContent {
+            post_notes,
+            private_notes,
+            stats,
+        } = load_content(&settings, &Diagnostics::default()).unwrap();
+
+        assert_eq!(1, post_notes.len());
+        assert!(private_notes.contains("private.html"));
+        assert_eq!(
+            LoadStats {
+                scanned: 4,
+                loaded: 1,
+                cached: 0,
+                skipped_private: 1,
+                skipped_empty: 0,
+                read_errors: 1,
+                parse_errors: 1,
+                schema_violations: 0,
+                schema_policy: SchemaPolicy::Skip,
+            },
+            stats
+        );
+    }
+
+    #[test]
+    fn test_configured_note_extensions_are_loaded() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let raw_md = "---\ntitle: Note\npublic: true\n---\nContent";
+        for file in ["note.md", "long.markdown", "draft.txt"] {
+            fs::write(input_dir.path().join(file), raw_md).unwrap();
+        }
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+
+        let loaded = load_content(&settings, &Diagnostics::default()).unwrap();
+
+        assert_eq!(1, loaded.stats.scanned);
+
+        settings.content.note_extensions = vec!["md".to_string(), "Markdown".to_string()];
+        let loaded = load_content(&settings, &Diagnostics::default()).unwrap();
+
+        let mut links = loaded
+            .post_notes
+            .iter()
+            .map(|note| &*note.file_name)
+            .collect::<Vec<&str>>();
+        links.sort();
+        assert_eq!(vec!["long.html", "note.html"], links);
+        assert_eq!("<p>Content</p>", &*loaded.post_notes[0].html_content);
+    }
+
+    /// Loads a valid note and one missing the required `description` with
+    /// the given schema policy.
+    fn load_with_schema_policy(schema_policy: SchemaPolicy) -> Result<LoadedContent> {
+        let input_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            input_dir.path().join("valid.md"),
+            "---\ntitle: Valid\ndescription: Description\npublic: true\n---\nContent",
+        )
+        .unwrap();
+        fs::write(
+            input_dir.path().join("invalid.md"),
+            "---\ntitle: Invalid\npublic: true\n---\nContent",
+        )
+        .unwrap();
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+        settings.content.schema.required = vec!["description".to_string()];
+        settings.content.schema_policy = schema_policy;
+
+        load_content(&settings, &Diagnostics::default())
+    }
+
+    #[test]
+    fn test_schema_policy_warn_keeps_the_note() {
+        let LoadedContent {
+            post_notes, stats, ..
+        } = load_with_schema_policy(SchemaPolicy::Warn).unwrap();
+
+        assert_eq!(2, post_notes.len());
+        assert_eq!(2, stats.loaded);
+        assert_eq!(1, stats.schema_violations);
+        assert_eq!(
+            "2 file(s) scanned, 2 loaded (0 cached), 0 skipped as private, 0 skipped as empty, 0 unreadable, 0 failed to parse, 1 violated the schema (policy: warn)",
+            stats.to_string()
+        );
+    }
+
+    #[test]
+    fn test_schema_policy_skip_drops_the_note() {
+        let LoadedContent {
+            post_notes, stats, ..
+        } = load_with_schema_policy(SchemaPolicy::Skip).unwrap();
+
+        assert_eq!("valid.html", &*post_notes[0].file_name);
+        assert_eq!(1, stats.loaded);
+        assert_eq!(1, stats.schema_violations);
+        assert_eq!(0, stats.parse_errors);
+    }
+
+    #[test]
+    fn test_schema_policy_fail_aborts_the_build() {
+        let err = load_with_schema_policy(SchemaPolicy::Fail).unwrap_err();
+
+        assert_eq!(
+            "1 note(s) violate the schema (2 file(s) scanned, 1 loaded (0 cached), 0 skipped as private, 0 skipped as empty, 0 unreadable, 0 failed to parse, 1 violated the schema (policy: fail)):\ninvalid.html is missing the required field(s) description",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_unchanged_note_is_loaded_from_parse_cache() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let volatile_dir = tempfile::tempdir().unwrap();
+        let note = input_dir.path().join("note.md");
+        fs::write(
+            &note,
+            "---\ntitle: Note\ntags: [Rust/Async]\npublic: true\ndifficulty: easy\n---\n# Heading\n\n```rust\nfn main() {}\n```",
+        )
+        .unwrap();
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+        settings.path.volatile = volatile_dir.path().to_path_buf();
+        settings.build.parse_cache = true;
+
+        let parsed = load_content(&settings, &Diagnostics::default()).unwrap();
+        let cached = load_content(&settings, &Diagnostics::default()).unwrap();
+
+        assert_eq!(0, parsed.stats.cached);
+        assert_eq!(1, cached.stats.cached);
+        let (parsed, cached) = (&parsed.post_notes[0], &cached.post_notes[0]);
+        assert_eq!(
+            serde_json::to_value(parsed).unwrap(),
+            serde_json::to_value(cached).unwrap()
+        );
+        assert_eq!("Rust/Async", cached.properties.tags[0].display());
+        assert_eq!(note, cached.source_path);
+
+        // A changed note is parsed again.
+        fs::write(&note, "---\ntitle: Changed\npublic: true\n---\nContent").unwrap();
+        let changed = load_content(&settings, &Diagnostics::default()).unwrap();
+
+        assert_eq!(0, changed.stats.cached);
+        assert_eq!("Changed", changed.post_notes[0].properties.title);
+        // Only the entry of the changed note is kept.
+        assert_eq!(
+            1,
+            fs::read_dir(volatile_dir.path().join("parse-cache"))
+                .unwrap()
+                .count()
+        );
+    }
+
+    /// Builds the fixture written by `write_fixture` twice and asserts both
+    /// output trees are byte-identical file for file.
+    fn assert_deterministic_build(write_fixture: impl Fn(&Path)) {
+        let input_dir = tempfile::tempdir().unwrap();
+        let volatile_dir = tempfile::tempdir().unwrap();
+        write_fixture(input_dir.path());
+        let tera = load_templates(Path::new("templates"), false).unwrap();
+
+        let build = || {
+            let output_dir = tempfile::tempdir().unwrap();
+            let mut settings = Settings::default();
+            settings.path.input = input_dir.path().to_path_buf();
+            settings.path.output = output_dir.path().to_path_buf();
+            settings.path.volatile = volatile_dir.path().to_path_buf();
+            settings.site.base_url = Some("https://example.com/".to_string());
+            settings.build.emit_note_json = true;
+            settings.build.emit_tags_json = true;
+            settings.build.fingerprint_assets = true;
+            settings.build.single_page = true;
+            settings.feed.per_tag = true;
+            settings.feed.json = true;
+            settings.llms_txt.enabled = true;
+            settings.content_map.fields.push("content_hash".to_string());
+
+            generate(&settings, &tera).unwrap();
+            output_dir
+        };
+        let first = build();
+        let second = build();
+
+        let tree = |root: &Path| {
+            fn visit(directory: &Path, root: &Path, tree: &mut BTreeMap<PathBuf, Vec<u8>>) {
+                for entry in fs::read_dir(directory).unwrap() {
+                    let path = entry.unwrap().path();
+                    if path.is_dir() {
+                        visit(&path, root, tree);
+                    } else {
+                        let content = fs::read(&path).unwrap();
+                        tree.insert(path.strip_prefix(root).unwrap().to_path_buf(), content);
+                    }
                 }
+            }
+
+            let mut tree = BTreeMap::new();
+            visit(root, root, &mut tree);
+            tree
+        };
+        let (first, second) = (tree(first.path()), tree(second.path()));
+        assert_eq!(
+            first.keys().collect::<Vec<&PathBuf>>(),
+            second.keys().collect::<Vec<&PathBuf>>()
+        );
+        for (path, content) in &first {
+            assert!(
+                content == &second[path],
+                "{} differs between builds",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_is_deterministic() {
+        assert_deterministic_build(|input: &Path| {
+            let note = |title: &str, tags: &str, created: &str, body: &str| {
+                format!(
+                    "---\ntitle: {title}\ndescription: About {title}\ntags: {tags}\ncreated: {created}\npublic: true\n---\n{body}"
+                )
             };
+            fs::create_dir_all(input.join("media")).unwrap();
+            fs::create_dir_all(input.join("guides")).unwrap();
+            fs::write(input.join("media/cover.png"), "cover").unwrap();
+            fs::write(input.join("media/diagram.svg"), "<svg></svg>").unwrap();
+            fs::write(
+                input.join("intro.md"),
+                note(
+                    "Intro",
+                    "[rust, blog]",
+                    "2025-05-20T10:00",
+                    "## Start\n\nSee [[setup]] and [[async#Tasks]].\n\n![[media/cover.png]]",
+                ),
+            )
+            .unwrap();
+            fs::write(
+                input.join("guides/setup.md"),
+                note(
+                    "Setup",
+                    "[rust/tooling, guides]",
+                    "2025-05-21T10:00",
+                    "## Install\n\n![[media/diagram.svg]]",
+                ),
+            )
+            .unwrap();
+            fs::write(
+                input.join("async.md"),
+                note(
+                    "Async",
+                    "[rust/async, blog]",
+                    "2025-05-21T10:00",
+                    "## Tasks\n\nA paragraph. ^tasks",
+                ),
+            )
+            .unwrap();
+            fs::write(
+                input.join("recipes.md"),
+                note(
+                    "Recipes",
+                    "[cooking]",
+                    "2025-05-22T10:00",
+                    "- [[intro]]\n- [[setup]]",
+                ),
+            )
+            .unwrap();
+        });
+    }
 
-            log::info!("Loaded public note: {:?}", &path_buf);
+    #[test]
+    fn test_colocated_assets_are_copied() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        let note = |title: &str| {
+            format!(
+                "---\ntitle: {title}\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n[Data](./data.json) [Style](./style.css)"
+            )
+        };
+        fs::create_dir_all(input_dir.path().join("chart")).unwrap();
+        fs::create_dir_all(input_dir.path().join("map")).unwrap();
+        fs::write(input_dir.path().join("chart/chart.md"), note("Chart")).unwrap();
+        fs::write(input_dir.path().join("chart/data.json"), "[1, 2]").unwrap();
+        fs::write(input_dir.path().join("chart/style.css"), "svg {}").unwrap();
+        fs::write(input_dir.path().join("chart/notes.txt"), "Not copied").unwrap();
+        fs::write(input_dir.path().join("map/map.md"), note("Map")).unwrap();
+        fs::write(input_dir.path().join("map/data.json"), "[3, 4]").unwrap();
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+        settings.path.output = output_dir.path().to_path_buf();
+        settings.build.colocated_assets = Some(Pattern::new(r"\.(css|json)$").unwrap());
+        let tera = load_templates(Path::new("templates"), false).unwrap();
 
-            Some(*post_note)
-        })
-        .collect())
+        generate(&settings, &tera).unwrap();
+
+        let output = |path: &str| fs::read_to_string(output_dir.path().join(path)).unwrap();
+        assert_eq!("[1, 2]", output("chart/data.json"));
+        assert_eq!("[3, 4]", output("map/data.json"));
+        assert_eq!("svg {}", output("chart/style.css"));
+        assert!(!output_dir.path().join("chart/notes.txt").exists());
+        assert!(output_dir.path().join("css/search.css").exists());
+        assert!(output("chart.html").contains(
+            "<a href=\"./chart/data.json\">Data</a> <a href=\"./chart/style.css\">Style</a>"
+        ));
+    }
+
+    #[test]
+    fn test_colocated_asset_must_not_replace_a_page() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        let note = |title: &str| {
+            format!(
+                "---\ntitle: {title}\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nContent"
+            )
+        };
+        fs::write(input_dir.path().join("note.md"), note("Note")).unwrap();
+        fs::write(input_dir.path().join("index.html"), "<p>Export</p>").unwrap();
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+        settings.path.output = output_dir.path().to_path_buf();
+        settings.build.pretty_urls = true;
+        settings.build.colocated_assets = Some(Pattern::new(r"\.html$").unwrap());
+        let tera = load_templates(Path::new("templates"), false).unwrap();
+
+        let err = generate(&settings, &tera).unwrap_err();
+
+        assert!(
+            format!("{err:#}").contains("would replace the generated note/index.html"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn test_colocated_asset_must_not_replace_a_static_asset() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            input_dir.path().join("css.md"),
+            "---\ntitle: Css\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nContent",
+        )
+        .unwrap();
+        fs::write(input_dir.path().join("search.css"), "body {}").unwrap();
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+        settings.path.output = output_dir.path().to_path_buf();
+        settings.build.colocated_assets = Some(Pattern::new(r"\.css$").unwrap());
+        let tera = load_templates(Path::new("templates"), false).unwrap();
+
+        let err = generate(&settings, &tera).unwrap_err();
+
+        assert!(
+            format!("{err:#}").contains("would replace the static asset css/search.css"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn test_include_private_builds_private_notes_unlisted() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        let note = |title: &str, public: bool| {
+            format!(
+                "---\ntitle: {title}\ndescription: Description\ntags: [rust]\ncreated: 2025-05-23T13:35\npublic: {public}\n---\nContent"
+            )
+        };
+        fs::write(input_dir.path().join("public.md"), note("Public", true)).unwrap();
+        fs::write(input_dir.path().join("draft.md"), note("Draft", false)).unwrap();
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+        settings.path.output = output_dir.path().to_path_buf();
+        settings.path.assets = Vec::new();
+        settings.site.base_url = Some("https://example.com/".to_string());
+        settings.content.include_private = true;
+        let tera = load_templates(Path::new("templates"), false).unwrap();
+
+        generate(&settings, &tera).unwrap();
+
+        let output = |path: &str| fs::read_to_string(output_dir.path().join(path)).unwrap();
+        let noindex = "<meta name=\"robots\" content=\"noindex\">";
+        assert!(output("draft.html").contains(noindex));
+        assert!(!output("public.html").contains(noindex));
+        assert!(output("sitemap.xml").contains("public.html"));
+        assert!(!output("sitemap.xml").contains("draft.html"));
+        assert!(!output("map.json").contains("draft.html"));
+    }
+
+    #[test]
+    fn test_notes_sharing_a_link_fail_before_the_feeds_are_written() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        let note = "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nContent";
+        fs::write(input_dir.path().join("My Note.md"), note).unwrap();
+        fs::write(input_dir.path().join("my-note.md"), note).unwrap();
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+        settings.path.output = output_dir.path().to_path_buf();
+        settings.site.base_url = Some("https://example.com/".to_string());
+        settings.feed.enabled = true;
+        settings.sitemap.enabled = true;
+        let tera = load_templates(Path::new("templates"), false).unwrap();
+
+        let err = generate(&settings, &tera).unwrap_err();
+
+        assert!(format!("{err:#}").contains("share a link"), "{err:#}");
+        assert!(!output_dir.path().join("feed.xml").exists());
+        assert!(!output_dir.path().join("sitemap.xml").exists());
+    }
+
+    #[test]
+    fn test_notes_with_the_same_name_in_different_directories() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let note = "---\ntitle: Intro\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nContent";
+        for dir in ["a", "b"] {
+            fs::create_dir_all(input_dir.path().join(dir)).unwrap();
+            fs::write(input_dir.path().join(dir).join("intro.md"), note).unwrap();
+        }
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+
+        let err = load_content(&settings, &Diagnostics::default()).unwrap_err();
+
+        assert!(err.to_string().contains("share a link"));
+        assert!(err.to_string().contains(&format!(
+            "intro.html <- {}, {}",
+            Path::new("a/intro.md").display(),
+            Path::new("b/intro.md").display()
+        )));
+    }
+
+    #[test]
+    fn test_diagnostics_are_written_when_the_build_fails() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let volatile_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            input_dir.path().join("broken.md"),
+            "---\ntitle: Broken\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\n---\n",
+        )
+        .unwrap();
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+        settings.path.volatile = volatile_dir.path().join(".post_notes");
+        let tera = load_templates(Path::new("templates"), false).unwrap();
+
+        generate(&settings, &tera).unwrap_err();
+
+        assert!(!settings.path.volatile.join("diagnostics.json").exists());
+
+        settings.build.emit_diagnostics = true;
+        generate(&settings, &tera).unwrap_err();
+
+        let produced: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(settings.path.volatile.join("diagnostics.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!("parse_error", produced[0]["category"]);
+        assert!(
+            produced[0]["source"]
+                .as_str()
+                .unwrap()
+                .ends_with("broken.md")
+        );
+    }
+
+    #[test]
+    fn test_directory_config_sets_public_default() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let note = "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\n---\nContent";
+        fs::create_dir_all(input_dir.path().join("journal/drafts")).unwrap();
+        fs::write(
+            input_dir.path().join("journal/.postnotes.toml"),
+            "public = true",
+        )
+        .unwrap();
+        fs::write(
+            input_dir.path().join("journal/drafts/.postnotes.toml"),
+            "public = false",
+        )
+        .unwrap();
+        fs::write(input_dir.path().join("journal/entry.md"), note).unwrap();
+        fs::write(input_dir.path().join("journal/drafts/draft.md"), note).unwrap();
+        fs::write(
+            input_dir.path().join("journal/override.md"),
+            note.replace("created:", "public: false\ncreated:"),
+        )
+        .unwrap();
+        fs::write(input_dir.path().join("root.md"), note).unwrap();
+        let mut settings = Settings::default();
+        settings.path.input = input_dir.path().to_path_buf();
+
+        let LoadedContent {
+            post_notes,
+            private_notes,
+            stats,
+        } = load_content(&settings, &Diagnostics::default()).unwrap();
+
+        assert_eq!(
+            vec!["entry.html"],
+            post_notes
+                .iter()
+                .map(|note| &*note.file_name)
+                .collect::<Vec<&str>>()
+        );
+        assert!(private_notes.contains("draft.html"));
+        assert!(private_notes.contains("override.html"));
+        // Outside of the configured directories `public` is still required.
+        assert_eq!(1, stats.parse_errors);
+    }
 }
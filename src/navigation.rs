@@ -1,11 +1,13 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
 
 use crate::post_note::{InternalLink, PostNote, Tag};
+use crate::settings::NavigationSettings;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
 pub struct RawTagNode {
     pub tag: Tag,
     pub child_tags: HashMap<Tag, RawTagNode>,
@@ -18,56 +20,246 @@ impl Hash for RawTagNode {
     }
 }
 
-impl Default for RawTagNode {
-    fn default() -> Self {
-        RawTagNode {
-            tag: Tag::from("#"),
-            child_tags: HashMap::new(),
-            files: HashSet::new(),
-        }
-    }
-}
+/// Weight and title of the notes, which their files are sorted by.
+type FileOrder<'a> = HashMap<&'a InternalLink, (Option<i64>, &'a str)>;
 
 impl From<RawTagNode> for TagNode {
     fn from(raw_tag_node: RawTagNode) -> Self {
-        let mut child_tags = raw_tag_node
+        raw_tag_node.into_tag_node("", &NavigationSettings::default(), &FileOrder::new())
+    }
+}
+
+impl RawTagNode {
+    /// Converts the node and its descendants, sorting the children
+    /// alphabetically except for the pinned tag paths, which come first.
+    /// Files are sorted by their weight (files without one come last), then
+    /// by their title and their link.
+    fn into_tag_node(
+        self,
+        path: &str,
+        settings: &NavigationSettings,
+        file_order: &FileOrder,
+    ) -> TagNode {
+        let child_path = |tag: &Tag| {
+            if path.is_empty() {
+                tag.to_string()
+            } else {
+                format!("{path}/{}", &**tag)
+            }
+        };
+
+        let mut child_tags = self
             .child_tags
-            .into_iter()
-            .map(|value| value.1.into())
+            .into_values()
+            .map(|child| {
+                let child_path = child_path(&child.tag);
+                child.into_tag_node(&child_path, settings, file_order)
+            })
             .collect::<Vec<TagNode>>();
-        child_tags.sort_unstable();
-        let mut files = raw_tag_node
-            .files
-            .into_iter()
-            .collect::<Vec<InternalLink>>();
-        files.sort_unstable();
-        Self {
-            tag: raw_tag_node.tag,
+        child_tags.sort_by_cached_key(|child| {
+            let pin = settings
+                .pinned_tags
+                .iter()
+                .position(|pinned| {
+                    Tag::from(pinned.trim_matches('/')) == Tag::from(child_path(&child.tag))
+                })
+                .unwrap_or(usize::MAX);
+            (pin, child.tag.clone())
+        });
+        let mut files = self.files.into_iter().collect::<Vec<InternalLink>>();
+        let order_key = |file: &InternalLink| {
+            let (weight, title) = file_order.get(file).copied().unwrap_or_default();
+            (weight.is_none(), weight, title)
+        };
+        files.sort_unstable_by(|a, b| order_key(a).cmp(&order_key(b)).then_with(|| a.cmp(b)));
+
+        let label = if settings.original_tag_case {
+            self.tag.display().to_string()
+        } else {
+            self.tag.to_string()
+        };
+
+        // Merge a tag without files into its only child, e.g. `projects` and
+        // `2024/q1` into `projects/2024/q1`. The root is never merged.
+        if settings.collapse_chains
+            && !path.is_empty()
+            && files.is_empty()
+            && let [child] = &mut child_tags[..]
+        {
+            let child = std::mem::take(child);
+            let mut collapsed = vec![child.tag];
+            collapsed.extend(child.collapsed);
+            return TagNode {
+                tag: self.tag,
+                label: format!("{label}/{}", child.label),
+                collapsed,
+                child_tags: child.child_tags,
+                files: child.files,
+            };
+        }
+
+        TagNode {
+            tag: self.tag,
+            label,
+            collapsed: Vec::new(),
             child_tags,
             files,
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub struct TagNode {
+    /// Only the root may be unlabeled, its tag is omitted then.
+    #[serde(default, skip_serializing_if = "is_unlabeled")]
     pub tag: Tag,
+    /// The tag as displayed, see [NavigationSettings::original_tag_case].
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub label: String,
+    /// The tags merged into this node by [NavigationSettings::collapse_chains],
+    /// in the order they follow the tag of the node.
+    #[serde(skip)]
+    pub collapsed: Vec<Tag>,
     pub child_tags: Vec<TagNode>,
     pub files: Vec<InternalLink>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+fn is_unlabeled(tag: &Tag) -> bool {
+    tag.is_empty()
+}
+
+impl TagNode {
+    /// Finds the descendant node for a tag path like `rust/async`. Within a
+    /// collapsed chain, like `projects/2024/q1`, every tag path of the chain
+    /// finds the collapsed node.
+    pub fn find(&self, tag_path: &str) -> Option<&TagNode> {
+        let parts = tag_path
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .map(Tag::from)
+            .collect::<Vec<Tag>>();
+        if parts.is_empty() {
+            return Some(self);
+        }
+
+        self.child_tags.iter().find_map(|child| {
+            let chain = child.chain().collect::<Vec<&Tag>>();
+            let matched = chain.len().min(parts.len());
+            if chain[..matched].iter().copied().ne(&parts[..matched]) {
+                return None;
+            }
+
+            let rest = parts[matched..]
+                .iter()
+                .map(|part| &**part)
+                .collect::<Vec<&str>>()
+                .join("/");
+            child.find(&rest)
+        })
+    }
+
+    /// The tag of the node followed by the tags collapsed into it.
+    fn chain(&self) -> impl Iterator<Item = &Tag> {
+        std::iter::once(&self.tag).chain(&self.collapsed)
+    }
+
+    /// The tag path from the parent of the node, like `projects/2024/q1` for
+    /// a collapsed chain.
+    fn chain_path(&self) -> String {
+        self.chain()
+            .map(|tag| &**tag)
+            .collect::<Vec<&str>>()
+            .join("/")
+    }
+
+    /// Collects the files of this node and all of its descendants.
+    pub fn all_files(&self) -> BTreeSet<&InternalLink> {
+        let mut files = self.files.iter().collect::<BTreeSet<&InternalLink>>();
+
+        for child in &self.child_tags {
+            files.extend(child.all_files());
+        }
+
+        files
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
 pub struct Navigation {
     pub root: TagNode,
+    /// Notes grouped by their category.
+    pub categories: BTreeMap<String, BTreeSet<InternalLink>>,
+    /// Serialize the children and files of the root directly instead of
+    /// wrapping them in the root node.
+    #[serde(skip)]
+    pub hide_root: bool,
+}
+
+impl Serialize for Navigation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.hide_root {
+            let mut state = serializer.serialize_struct("Navigation", 3)?;
+            state.serialize_field("child_tags", &self.root.child_tags)?;
+            state.serialize_field("files", &self.root.files)?;
+            state.serialize_field("categories", &self.categories)?;
+            state.end()
+        } else {
+            let mut state = serializer.serialize_struct("Navigation", 2)?;
+            state.serialize_field("root", &self.root)?;
+            state.serialize_field("categories", &self.categories)?;
+            state.end()
+        }
+    }
 }
 
 impl From<&Vec<PostNote>> for Navigation {
     fn from(notes: &Vec<PostNote>) -> Self {
-        let mut root = RawTagNode::default();
+        Self::new(notes, &NavigationSettings::default())
+    }
+}
+
+impl Navigation {
+    pub fn new(notes: &[PostNote], settings: &NavigationSettings) -> Self {
+        let mut root = RawTagNode {
+            tag: Tag::from(settings.root_label.as_str()),
+            ..Default::default()
+        };
+        let mut categories = BTreeMap::<String, BTreeSet<InternalLink>>::new();
+        let file_order = notes
+            .iter()
+            .map(|note| {
+                let weight = note
+                    .properties
+                    .extra
+                    .get(&settings.weight_field)
+                    .and_then(serde_json::Value::as_i64);
+                (&note.file_name, (weight, note.properties.title.as_str()))
+            })
+            .collect::<FileOrder>();
 
         for note in notes {
+            if let Some(category) = &note.properties.category {
+                categories
+                    .entry(category.clone())
+                    .or_default()
+                    .insert(note.file_name.clone());
+            }
+
+            if note.properties.untagged
+                && let Some(label) = &settings.uncategorized
+            {
+                root.child_tags
+                    .entry(Tag::from(label.as_str()))
+                    .or_insert_with(|| RawTagNode {
+                        tag: Tag::from(label.as_str()),
+                        ..Default::default()
+                    })
+                    .files
+                    .insert(note.file_name.clone());
+            }
+
             for tag in &note.properties.tags {
-                let parts: Vec<&str> = tag.split('/').filter(|p| !p.is_empty()).collect();
+                let parts = tag.parts();
 
                 if parts.is_empty() {
                     continue;
@@ -75,16 +267,15 @@ impl From<&Vec<PostNote>> for Navigation {
 
                 let mut current_node = &mut root;
 
-                for part in &parts {
-                    let tag_part = Tag::from(*part);
-
-                    current_node = current_node
-                        .child_tags
-                        .entry(tag_part.clone())
-                        .or_insert_with(|| RawTagNode {
-                            tag: tag_part,
-                            ..Default::default()
-                        });
+                for part in parts {
+                    current_node =
+                        current_node
+                            .child_tags
+                            .entry(part.clone())
+                            .or_insert_with(|| RawTagNode {
+                                tag: part,
+                                ..Default::default()
+                            });
                 }
 
                 current_node.files.insert(note.file_name.clone());
@@ -93,6 +284,352 @@ impl From<&Vec<PostNote>> for Navigation {
             }
         }
 
-        Navigation { root: root.into() }
+        Navigation {
+            root: root.into_tag_node("", settings, &file_order),
+            categories,
+            hide_root: settings.hide_root,
+        }
+    }
+}
+
+impl TagNode {
+    fn collect_flat_tags(&self, prefix: &str, flat_tags: &mut BTreeMap<String, usize>) {
+        for child in &self.child_tags {
+            let path = if prefix.is_empty() {
+                child.chain_path()
+            } else {
+                format!("{prefix}/{}", child.chain_path())
+            };
+
+            if !child.files.is_empty() {
+                flat_tags.insert(path.clone(), child.files.len());
+            }
+            child.collect_flat_tags(&path, flat_tags);
+        }
+    }
+}
+
+impl Navigation {
+    /// Every full tag path carried by at least one note, mapped to the number
+    /// of notes carrying it.
+    pub fn flat_tags(&self) -> BTreeMap<String, usize> {
+        let mut flat_tags = BTreeMap::new();
+        self.root.collect_flat_tags("", &mut flat_tags);
+
+        flat_tags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post_note;
+    use crate::settings::ContentSettings;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn parse_public(file_name: &str, tags: &str) -> PostNote {
+        let raw_md = format!(
+            "---\ntitle: Note\ndescription: Description\ntags: {tags}\ncreated: 2025-05-23T13:35\npublic: true\n---\n"
+        );
+        post_note::parse_public(file_name, &raw_md)
+    }
+
+    #[test]
+    fn test_navigation_root_label() {
+        let notes = vec![parse_public("note.md", "[rust]")];
+        let settings = NavigationSettings {
+            root_label: "garden".to_string(),
+            ..Default::default()
+        };
+
+        let produced = serde_json::to_value(Navigation::new(&notes, &settings)).unwrap();
+
+        assert_eq!(
+            json!({
+                "root": {
+                    "tag": "garden",
+                    "label": "garden",
+                    "child_tags": [{ "tag": "rust", "label": "rust", "child_tags": [], "files": ["note.html"] }],
+                    "files": [],
+                },
+                "categories": {},
+            }),
+            produced
+        );
+    }
+
+    #[test]
+    fn test_navigation_root_without_label() {
+        let notes = vec![parse_public("note.md", "[rust]")];
+
+        let produced = serde_json::to_value(Navigation::from(&notes)).unwrap();
+
+        assert!(!produced.to_string().contains("\"#\""));
+        assert_eq!(
+            json!({
+                "root": {
+                    "child_tags": [{ "tag": "rust", "label": "rust", "child_tags": [], "files": ["note.html"] }],
+                    "files": [],
+                },
+                "categories": {},
+            }),
+            produced
+        );
+    }
+
+    #[test]
+    fn test_hash_tag_does_not_merge_into_root() {
+        let notes = vec![parse_public("hash.md", "[\"#\"]")];
+
+        let produced = Navigation::from(&notes).root;
+
+        assert!(produced.files.is_empty());
+        assert_eq!(
+            Some(vec![&InternalLink::from("hash".to_string())]),
+            produced
+                .find("#")
+                .map(|node| node.files.iter().collect::<Vec<&InternalLink>>())
+        );
+    }
+
+    #[test]
+    fn test_navigation_without_root() {
+        let notes = vec![parse_public("note.md", "[rust]")];
+        let settings = NavigationSettings {
+            hide_root: true,
+            ..Default::default()
+        };
+
+        let produced = serde_json::to_value(Navigation::new(&notes, &settings)).unwrap();
+
+        assert_eq!(
+            json!({
+                "child_tags": [{ "tag": "rust", "label": "rust", "child_tags": [], "files": ["note.html"] }],
+                "files": [],
+                "categories": {},
+            }),
+            produced
+        );
+    }
+
+    #[test]
+    fn test_tags_keep_original_case_for_display() {
+        let notes = vec![
+            parse_public("a.md", "[Rust/Async]"),
+            parse_public("b.md", "[rust]"),
+        ];
+        let settings = NavigationSettings {
+            original_tag_case: true,
+            ..Default::default()
+        };
+
+        let navigation = Navigation::new(&notes, &settings);
+        let produced = serde_json::to_value(&navigation).unwrap();
+
+        assert_eq!(
+            json!({
+                "root": {
+                    "child_tags": [{
+                        "tag": "rust",
+                        "label": "Rust",
+                        "child_tags": [{
+                            "tag": "async",
+                            "label": "Async",
+                            "child_tags": [],
+                            "files": ["a.html"],
+                        }],
+                        "files": ["b.html"],
+                    }],
+                    "files": [],
+                },
+                "categories": {},
+            }),
+            produced
+        );
+        assert_eq!("rust/async", &*notes[0].properties.tags[0]);
+        // Feeds and shards use the tags of the notes, which the navigation
+        // links to as they are.
+        assert!(
+            navigation
+                .root
+                .find(&notes[0].properties.tags[0])
+                .is_some_and(|node| node.files == [InternalLink::from("a.html".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_pinned_tags_come_first() {
+        let notes = vec![
+            parse_public("a.md", "[blog/rust, blog/async, cooking]"),
+            parse_public("b.md", "[start-here]"),
+        ];
+        let settings = NavigationSettings {
+            pinned_tags: vec!["start-here".to_string(), "blog/rust".to_string()],
+            ..Default::default()
+        };
+
+        let produced = Navigation::new(&notes, &settings).root;
+
+        assert_eq!(
+            vec!["start-here", "blog", "cooking"],
+            produced
+                .child_tags
+                .iter()
+                .map(|child| &*child.tag)
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(
+            vec!["rust", "async"],
+            produced.child_tags[1]
+                .child_tags
+                .iter()
+                .map(|child| &*child.tag)
+                .collect::<Vec<&str>>()
+        );
+    }
+
+    #[test]
+    fn test_files_are_sorted_by_weight_then_title() {
+        let parse = |file_name: &str, title: &str, weight: &str| {
+            let raw_md = format!(
+                "---\ntitle: {title}\ntags: [rust]\n{weight}created: 2025-05-23T13:35\npublic: true\n---\n"
+            );
+            post_note::parse_public(file_name, &raw_md)
+        };
+        let notes = vec![
+            parse("a.md", "Zebra", ""),
+            parse("b.md", "Apple", ""),
+            parse("c.md", "Second", "weight: 2\n"),
+            parse("d.md", "First", "weight: 1\n"),
+        ];
+
+        let produced = Navigation::from(&notes).root;
+
+        assert_eq!(
+            vec!["d.html", "c.html", "b.html", "a.html"],
+            produced.child_tags[0]
+                .files
+                .iter()
+                .map(|file| &**file)
+                .collect::<Vec<&str>>()
+        );
+    }
+
+    #[test]
+    fn test_single_child_chains_collapse() {
+        let notes = vec![
+            parse_public("q1.md", "[Projects/2024/Q1]"),
+            parse_public("rust.md", "[rust, rust/async]"),
+        ];
+        let settings = NavigationSettings {
+            collapse_chains: true,
+            original_tag_case: true,
+            ..Default::default()
+        };
+
+        let produced = Navigation::new(&notes, &settings);
+
+        assert_eq!(
+            json!({
+                "root": {
+                    "child_tags": [
+                        {
+                            "tag": "projects",
+                            "label": "Projects/2024/Q1",
+                            "child_tags": [],
+                            "files": ["q1.html"],
+                        },
+                        {
+                            "tag": "rust",
+                            "label": "rust",
+                            "child_tags": [{ "tag": "async", "label": "async", "child_tags": [], "files": ["rust.html"] }],
+                            "files": ["rust.html"],
+                        },
+                    ],
+                    "files": [],
+                },
+                "categories": {},
+            }),
+            serde_json::to_value(&produced).unwrap()
+        );
+        assert_eq!(
+            Some("Projects/2024/Q1"),
+            produced.root.find("projects/2024").map(|node| &*node.label)
+        );
+        assert_eq!(Some(&1), produced.flat_tags().get("projects/2024/q1"));
+        assert_eq!(
+            Some("async"),
+            produced.root.find("rust/async").map(|node| &*node.tag)
+        );
+    }
+
+    #[test]
+    fn test_untagged_notes_in_uncategorized_bucket() {
+        let notes = vec![parse_public("a.md", "[rust]"), parse_public("b.md", "[]")];
+        let settings = NavigationSettings {
+            uncategorized: Some("uncategorized".to_string()),
+            ..Default::default()
+        };
+
+        let produced = Navigation::new(&notes, &settings).root;
+
+        assert_eq!(
+            Some(vec![&InternalLink::from("b".to_string())]),
+            produced
+                .find("uncategorized")
+                .map(|node| node.files.iter().collect::<Vec<&InternalLink>>())
+        );
+        assert!(
+            Navigation::from(&notes)
+                .root
+                .find("uncategorized")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_configured_tag_separators_share_a_parent() {
+        let settings = ContentSettings {
+            tag_separators: vec!['.'],
+            ..Default::default()
+        };
+        let notes = [("a.md", "rust.async"), ("b.md", "rust/async")].map(|(file_name, tag)| {
+            let raw_md = format!(
+                "---\ntitle: Note\ndescription: Description\ntags: [{tag}]\ncreated: 2025-05-23T13:35\npublic: true\n---\n"
+            );
+            post_note::parse_public_with(file_name, &raw_md, &settings)
+        });
+
+        let produced = Navigation::new(&notes, &NavigationSettings::default()).root;
+
+        assert_eq!(1, produced.child_tags.len());
+        assert_eq!(
+            Some(vec![
+                InternalLink::from("a".to_string()),
+                InternalLink::from("b".to_string()),
+            ]),
+            produced.find("rust/async").map(|node| node.files.clone())
+        );
+    }
+
+    #[test]
+    fn test_flat_tags_count_notes_per_tag() {
+        let notes = vec![
+            parse_public("a.md", "[blog/rust, cooking]"),
+            parse_public("b.md", "[blog/rust, blog/rust]"),
+            parse_public("c.md", "[blog]"),
+        ];
+
+        let produced = Navigation::from(&notes).flat_tags();
+
+        assert_eq!(
+            BTreeMap::from([
+                ("blog".to_string(), 1),
+                ("blog/rust".to_string(), 2),
+                ("cooking".to_string(), 1),
+            ]),
+            produced
+        );
     }
 }
@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hash::content_hash;
+use crate::post_note::{NoteIndex, PostNote, Properties, Tag};
+use crate::settings::{ContentSettings, DateSource};
+
+/// Directory within the volatile directory the parsed notes are kept in.
+const PARSE_CACHE_DIR: &str = "parse-cache";
+
+/// Parsed notes of previous builds, stored as `<key>.json` within the
+/// volatile directory. The key hashes everything the parsing depends on, so
+/// a changed note, changed settings or a new version never hit a stale entry.
+#[derive(Debug)]
+pub struct ParseCache {
+    dir: PathBuf,
+    /// Fingerprint of the notes wikilinks are resolved against.
+    index: String,
+    hits: AtomicUsize,
+    /// Entries of the notes of the current build, see [ParseCache::prune].
+    used: Mutex<HashSet<PathBuf>>,
+}
+
+/// The parts of a [PostNote] which aren't part of its serialized form.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedNote {
+    note: PostNote,
+    /// Tags in the casing they were declared with.
+    tags: Vec<String>,
+    extra: BTreeMap<String, serde_json::Value>,
+    untagged: bool,
+    schema_violation: Option<String>,
+}
+
+impl ParseCache {
+    pub fn new(volatile: &Path, index: &NoteIndex) -> Self {
+        Self {
+            dir: volatile.join(PARSE_CACHE_DIR),
+            index: index.fingerprint(),
+            hits: AtomicUsize::new(0),
+            used: Mutex::default(),
+        }
+    }
+
+    /// Number of notes loaded from the cache so far.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the note parsed by a previous build, if the note and the
+    /// settings didn't change since.
+    pub fn get(&self, path: &Path, raw_md: &str, settings: &ContentSettings) -> Option<PostNote> {
+        let entry = self.entry_path(path, raw_md, settings)?;
+        let cached = fs::read(&entry).ok()?;
+        let cached = match serde_json::from_slice::<CachedNote>(&cached) {
+            Ok(cached) => cached,
+            Err(err) => {
+                log::debug!("Ignoring the cache entry {}: {}", entry.display(), err);
+                return None;
+            }
+        };
+        self.hits.fetch_add(1, Ordering::Relaxed);
+
+        Some(cached.into_note(path))
+    }
+
+    /// Stores the parsed note, a failure only costs the next build the time
+    /// to parse it again.
+    pub fn insert(&self, path: &Path, raw_md: &str, settings: &ContentSettings, note: &PostNote) {
+        let Some(entry) = self.entry_path(path, raw_md, settings) else {
+            return;
+        };
+        let cached = CachedNote::new(note);
+
+        if let Err(err) = self.write(&entry, &cached) {
+            log::warn!(
+                "Could not cache the parsed note {}: {:#}",
+                path.display(),
+                err
+            );
+        }
+    }
+
+    /// Removes the entries no note of the current build used, like the ones
+    /// of changed or deleted notes, so the cache doesn't grow without bound.
+    pub fn prune(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let used = self
+            .used
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let mut pruned = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !used.contains(&path) && fs::remove_file(&path).is_ok() {
+                pruned += 1;
+            }
+        }
+        if pruned > 0 {
+            log::debug!("Pruned {} stale parse cache entrie(s)", pruned);
+        }
+    }
+
+    fn write(&self, entry: &Path, cached: &CachedNote) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(entry, serde_json::to_vec(cached)?)?;
+
+        Ok(())
+    }
+
+    fn entry_path(&self, path: &Path, raw_md: &str, settings: &ContentSettings) -> Option<PathBuf> {
+        // Dates taken from the modification time don't show in the content.
+        let modified = settings
+            .date_source_priority
+            .contains(&DateSource::ModifiedTime)
+            .then(|| {
+                fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            })
+            .flatten()
+            .map(|modified| format!("{modified:?}"));
+        let key = serde_json::to_vec(&(
+            env!("CARGO_PKG_VERSION"),
+            path,
+            raw_md,
+            settings,
+            &self.index,
+            modified,
+        ))
+        .ok()?;
+
+        let entry = self.dir.join(format!("{}.json", content_hash(&key)));
+        self.used
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(entry.clone());
+
+        Some(entry)
+    }
+}
+
+impl CachedNote {
+    fn new(note: &PostNote) -> Self {
+        Self {
+            note: note.clone(),
+            tags: note
+                .properties
+                .tags
+                .iter()
+                .map(|tag| tag.display().to_string())
+                .collect(),
+            extra: note.properties.extra.clone(),
+            untagged: note.properties.untagged,
+            schema_violation: note.properties.schema_violation.clone(),
+        }
+    }
+
+    /// Rebuilds the note parsed from `path`, including the fields which
+    /// aren't serialized.
+    fn into_note(self, path: &Path) -> PostNote {
+        let Self {
+            note,
+            tags,
+            extra,
+            untagged,
+            schema_violation,
+        } = self;
+
+        PostNote {
+            properties: Properties {
+                tags: tags.into_iter().map(Tag::from).collect(),
+                extra,
+                untagged,
+                schema_violation,
+                ..note.properties
+            },
+            source_path: path.to_path_buf(),
+            ..note
+        }
+    }
+}
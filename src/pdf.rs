@@ -0,0 +1,107 @@
+use std::path::Path;
+use std::process::Command;
+
+use rayon::prelude::*;
+
+use crate::post_note::PostNote;
+use crate::settings::PdfSettings;
+
+/// Converts every rendered note into a PDF beside it, e.g. `rust.pdf` next
+/// to `rust.html`, by calling the configured renderer as
+/// `<renderer> [args...] <note.html> <note.pdf>`.
+///
+/// A note which couldn't be converted only logs a warning, the build goes
+/// on. Returns the number of PDFs written.
+pub fn write_pdfs(notes: &[PostNote], settings: &PdfSettings, output_path: &Path) -> usize {
+    let Some(renderer) = settings.renderer.as_deref() else {
+        return 0;
+    };
+
+    let written = notes
+        .par_iter()
+        .filter(|note| {
+            let html_path = output_path.join(note.file_name.output_path());
+            let pdf_path = html_path.with_extension("pdf");
+            let output = Command::new(renderer)
+                .args(&settings.args)
+                .arg(&html_path)
+                .arg(&pdf_path)
+                .output();
+
+            match output {
+                Ok(output) if output.status.success() => true,
+                Ok(output) => {
+                    log::warn!(
+                        "Could not render the PDF of {}, {} exited with {}: {}",
+                        &*note.file_name,
+                        renderer,
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    );
+                    false
+                }
+                Err(err) => {
+                    log::warn!(
+                        "Could not render the PDF of {}, failed to run {}: {}",
+                        &*note.file_name,
+                        renderer,
+                        err
+                    );
+                    false
+                }
+            }
+        })
+        .count();
+    log::info!("Rendered {} of {} PDF(s).", written, notes.len());
+
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post_note;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    fn parse_public(file_name: &str) -> PostNote {
+        let raw_md = "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nContent";
+        post_note::parse_public(file_name, raw_md)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pdf_is_written_beside_the_note() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let output_dir = tempfile::tempdir().unwrap();
+        let renderer = output_dir.path().join("renderer.sh");
+        fs::write(
+            &renderer,
+            "#!/bin/sh\n[ \"$1\" = \"--quiet\" ] && cp \"$2\" \"$3\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&renderer, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(output_dir.path().join("note.html"), "<p>Content</p>").unwrap();
+        let notes = vec![parse_public("note.md"), parse_public("unrendered.md")];
+        let settings = PdfSettings {
+            renderer: Some(renderer.to_str().unwrap().to_string()),
+            args: vec!["--quiet".to_string()],
+        };
+
+        let produced = write_pdfs(&notes, &settings, output_dir.path());
+
+        assert_eq!(1, produced);
+        assert_eq!(
+            "<p>Content</p>",
+            fs::read_to_string(output_dir.path().join("note.pdf")).unwrap()
+        );
+        assert!(!output_dir.path().join("unrendered.pdf").exists());
+
+        let settings = PdfSettings {
+            renderer: Some("./missing-renderer".to_string()),
+            args: Vec::new(),
+        };
+        assert_eq!(0, write_pdfs(&notes, &settings, output_dir.path()));
+    }
+}
@@ -1,35 +1,255 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
 use comrak::nodes::NodeValue;
-use comrak::{Arena, Options, format_html, parse_document};
+use comrak::{Arena, Options, parse_document};
+use percent_encoding::{AsciiSet, CONTROLS, percent_decode_str, utf8_percent_encode};
 use regex::Regex;
-use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::{Borrow, Cow};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::callout::convert_callouts;
+use crate::clip::clip_sections;
+use crate::date::{format_date, now, parse_date};
+use crate::error::Error;
+use crate::escape::decode_entities;
+use crate::formatter::{
+    FormatState, NoteFormatter, TocEntry, add_heading_ids, block_id, heading_ids, heading_slug,
+    table_of_contents,
+};
+use crate::hash::content_hash;
+use crate::settings::{
+    ContentSettings, DateSource, EmptyNotePolicy, EmptyTagsPolicy, HeadingIdScope,
+    LinkClassSettings, MediaTitlePlacement, Schema, SchemaPolicy, TaskListMode,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Properties {
     pub title: String,
+    #[serde(default)]
     pub description: String,
     pub image: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_tags")]
     pub tags: Vec<Tag>,
+    /// Single category of the note, with categorized output enabled the note
+    /// is placed under `<category>/<slug>.html`.
+    pub category: Option<String>,
+    /// Resolved according to the configured date source priority, so it may
+    /// be missing from the front matter. Falls back to the current time.
+    #[serde(default)]
     pub created: String,
     pub modified: Option<String>,
     pub public: bool,
+    /// Language of the note, e.g. `de`. Falls back to the language of the
+    /// website.
+    pub lang: Option<String>,
+    /// Publish the sections removed by the clip rules, like `## Questions`.
+    #[serde(default)]
+    pub keep_questions: bool,
+    /// Pin the note to the `featured` notes of the templates.
+    #[serde(default)]
+    pub featured: bool,
+    /// Position among the featured notes, lower ones come first.
+    pub featured_order: Option<i64>,
+    /// Related resources listed as references, like
+    /// `links: [{title: Docs, url: "https://docs.rs"}]`. Links to other
+    /// notes are resolved like any other link to a note. Values of another
+    /// shape are ignored with a warning.
+    #[serde(default, deserialize_with = "deserialize_links")]
+    pub links: Option<Vec<ResourceLink>>,
+    /// The series the note is a part of, like
+    /// `series: {name: Rust basics, part: 2}`, see [link_series].
+    pub series: Option<Series>,
+    /// Whether the note declares an empty list of tags, as opposed to not
+    /// declaring tags at all.
+    #[serde(skip)]
+    pub untagged: bool,
+    /// Why the note violates the schema, if it's built anyway because of
+    /// [SchemaPolicy::Warn].
+    #[serde(skip)]
+    pub schema_violation: Option<String>,
+    /// Any front matter fields not covered above. Nested maps and sequences
+    /// are kept as they are and exposed to templates as `properties.extra`.
+    #[serde(flatten, serialize_with = "serialize_extra")]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Membership of a note in a series of notes read in order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Series {
+    pub name: String,
+    /// Position within the series, starting at 1.
+    pub part: u32,
+}
+
+/// An entry of the structured `links` of a note.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLink {
+    pub title: String,
+    pub url: String,
+}
+
+/// Accepts the tags as a sequence, a single tag or a string of tags separated
+/// by commas and/or whitespace, like `tags: rust` or `tags: "rust, blog"`.
+fn deserialize_tags<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<Tag>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawTags {
+        Sequence(Vec<String>),
+        Scalar(String),
+    }
+
+    Ok(match RawTags::deserialize(deserializer)? {
+        RawTags::Sequence(tags) => tags.into_iter().map(Tag::from).collect(),
+        RawTags::Scalar(tags) => tags
+            .split(|char: char| char == ',' || char.is_whitespace())
+            .filter(|tag| !tag.is_empty())
+            .map(Tag::from)
+            .collect(),
+    })
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-pub struct Tag(String);
+/// Accepts the `links` if they're a list of `{title, url}` maps and ignores
+/// them otherwise, so notes using `links` for something else still load.
+fn deserialize_links<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Vec<ResourceLink>>, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    if value.is_null() {
+        return Ok(None);
+    }
+
+    match serde_json::from_value(value) {
+        Ok(links) => Ok(Some(links)),
+        Err(err) => {
+            log::warn!(
+                "Ignoring the front matter field `links`, expected a list of `title` and `url` pairs: {err}"
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Serializes the flattened extras under a nested `extra` key instead of
+/// merging them into the surrounding [Properties].
+fn serialize_extra<S: Serializer>(
+    extra: &BTreeMap<String, serde_json::Value>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry("extra", extra)?;
+    map.end()
+}
+
+/// A tag (path) like `rust/async`. Tags are lowercased to be compared and
+/// used in urls, the casing they were declared with is only kept for
+/// display, see [Tag::display].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(from = "String")]
+pub struct Tag {
+    normalized: String,
+    display: String,
+}
 
 impl From<&str> for Tag {
     fn from(tag: &str) -> Self {
-        Self(tag.trim().to_lowercase())
+        Self {
+            normalized: tag.trim().to_lowercase(),
+            display: tag.trim().to_string(),
+        }
     }
 }
 
 impl From<String> for Tag {
     fn from(tag: String) -> Self {
-        Self(tag.trim().to_lowercase())
+        Self::from(tag.as_str())
+    }
+}
+
+impl PartialEq for Tag {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized == other.normalized
+    }
+}
+
+impl Eq for Tag {}
+
+impl PartialOrd for Tag {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Tag {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.normalized.cmp(&other.normalized)
+    }
+}
+
+impl Hash for Tag {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized.hash(state);
+    }
+}
+
+/// Tags are serialized in their normalized form.
+impl Serialize for Tag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.normalized)
+    }
+}
+
+impl Tag {
+    /// Treats the separators like `/`, so `rust.async` becomes `rust/async`
+    /// with `.` as a separator.
+    pub fn with_separators(self, separators: &[char]) -> Self {
+        if separators.is_empty() {
+            return self;
+        }
+
+        Self {
+            normalized: self.normalized.replace(separators, "/"),
+            display: self.display.replace(separators, "/"),
+        }
+    }
+
+    /// Whether this tag equals the given tag path or is one of its
+    /// descendants, e.g. `rust/async` is within `rust`.
+    pub fn is_within(&self, ancestor: &str) -> bool {
+        let ancestor = Tag::from(ancestor.trim_matches('/'));
+
+        self.normalized == ancestor.normalized
+            || self
+                .normalized
+                .strip_prefix(&ancestor.normalized)
+                .is_some_and(|rest| rest.starts_with('/'))
+    }
+
+    /// The tag in the casing it was declared with, e.g. `Rust/Async`.
+    pub fn display(&self) -> &str {
+        &self.display
+    }
+
+    /// The parts of the tag path, e.g. `Rust` and `Async` for `Rust/Async`,
+    /// normalized like the whole tag instead of once more on their own.
+    pub fn parts(&self) -> Vec<Tag> {
+        self.normalized
+            .split('/')
+            .zip(self.display.split('/'))
+            .filter(|(normalized, _)| !normalized.is_empty())
+            .map(|(normalized, display)| Tag {
+                normalized: normalized.to_string(),
+                display: display.to_string(),
+            })
+            .collect()
     }
 }
 
@@ -37,18 +257,25 @@ impl Deref for Tag {
     type Target = str;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.normalized
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// Rendered html, trimmed by every constructor so it never starts or ends
+/// with whitespace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Html(String);
 
 impl TryFrom<Vec<u8>> for Html {
     type Error = anyhow::Error;
 
     fn try_from(html_buf: Vec<u8>) -> Result<Self> {
-        Ok(Self(String::from_utf8(html_buf)?))
+        String::from_utf8(html_buf).map(Self::from).map_err(|err| {
+            anyhow::anyhow!(
+                "Invalid UTF-8, invalid byte at offset {}",
+                err.utf8_error().valid_up_to()
+            )
+        })
     }
 }
 
@@ -72,16 +299,24 @@ pub struct InternalLink(String);
 impl TryFrom<PathBuf> for InternalLink {
     type Error = anyhow::Error;
 
-    fn try_from(mut path_buf: PathBuf) -> Result<Self> {
-        path_buf.set_extension("html");
-
-        let file_name = path_buf
-            .file_name()
+    fn try_from(path_buf: PathBuf) -> Result<Self> {
+        let file_stem = path_buf
+            .file_stem()
             .context("Could not determine file name")?
-            .to_string_lossy()
-            .to_string();
+            .to_string_lossy();
+        let slug = slugify(&file_stem);
+
+        if slug.is_empty() {
+            anyhow::bail!("Could not derive a link from the file name {:?}", path_buf);
+        }
 
-        Ok(Self(file_name))
+        Ok(Self(format!("{}.html", slug)))
+    }
+}
+
+impl Borrow<str> for InternalLink {
+    fn borrow(&self) -> &str {
+        &self.0
     }
 }
 
@@ -92,18 +327,98 @@ impl From<String> for InternalLink {
             .map(|(head, _tail)| (head, &link[head.len()..]))
             .unwrap_or((&link[..], ""));
 
-        let mut full = path_part.trim_start_matches('/').to_string();
+        let path_part = path_part.trim_start_matches('/');
+        let path_part = path_part
+            .strip_suffix(".html")
+            .or_else(|| path_part.strip_suffix(".md"))
+            .unwrap_or(path_part);
 
-        if !full.ends_with(".html") {
-            full.push_str(".html");
-        }
+        let mut full = path_part
+            .split('/')
+            .map(slugify)
+            .collect::<Vec<String>>()
+            .join("/");
 
+        full.push_str(".html");
         full.push_str(rest);
 
         Self(full)
     }
 }
 
+/// Turns a note name into a URL-safe slug: lowercased, whitespace replaced
+/// by hyphens and everything except alphanumerics, `-`, `_` and `.` removed.
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+
+    for char in name.trim().chars().flat_map(char::to_lowercase) {
+        if char.is_alphanumeric() || matches!(char, '_' | '.') {
+            slug.push(char);
+        } else if (char.is_whitespace() || char == '-') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.trim_matches('-').to_string()
+}
+
+impl InternalLink {
+    /// The linked file without any `#anchor` or `?query` suffix.
+    pub fn target(&self) -> &str {
+        self.0
+            .split_once(['#', '?'])
+            .map_or(&self.0[..], |(target, _)| target)
+    }
+
+    /// Prefix leading from the linked file back to the output root: the base
+    /// path if one is configured, otherwise a relative path like `../` for
+    /// `guides/note.html`.
+    pub fn root_path(&self, base_path: Option<&str>) -> String {
+        if let Some(base_path) = base_path {
+            let base_path = base_path.trim_matches('/');
+            return if base_path.is_empty() {
+                "/".to_string()
+            } else {
+                format!("/{base_path}/")
+            };
+        }
+
+        match self.target().matches('/').count() {
+            0 => "./".to_string(),
+            depth => "../".repeat(depth),
+        }
+    }
+
+    /// Path of the rendered file relative to the output directory, links
+    /// ending in `/` are written to their `index.html`.
+    pub fn output_path(&self) -> PathBuf {
+        let target = self.target();
+        match target.strip_suffix('/') {
+            Some(directory) => Path::new(directory).join("index.html"),
+            None => PathBuf::from(target),
+        }
+    }
+
+    /// Returns the link to be used in hrefs, with its `#anchor`
+    /// percent-encoded, e.g. `note.html#Some%20Heading`.
+    pub fn encoded(&self) -> String {
+        match self.0.split_once('#') {
+            Some((head, anchor)) => format!(
+                "{head}#{}",
+                utf8_percent_encode(anchor, FRAGMENT_ENCODE_SET)
+            ),
+            None => self.0.clone(),
+        }
+    }
+
+    /// The `#anchor` of the link without the leading `#`, if any.
+    pub fn anchor(&self) -> Option<&str> {
+        self.0
+            .split_once('#')
+            .map(|(_, anchor)| anchor.split_once('?').map_or(anchor, |(anchor, _)| anchor))
+    }
+}
+
 impl Deref for InternalLink {
     type Target = str;
 
@@ -112,12 +427,39 @@ impl Deref for InternalLink {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// Characters which have to be percent-encoded within a url path.
+const PATH_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Characters which have to be percent-encoded within a url fragment.
+const FRAGMENT_ENCODE_SET: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
+
+/// Path of a media file relative to the input directory.
+///
+/// Always stores the decoded path as found on disk, use [MediaLink::encoded]
+/// when linking to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MediaLink(String);
 
 impl From<String> for MediaLink {
     fn from(image: String) -> Self {
-        Self(image)
+        Self(percent_decode_str(&image).decode_utf8_lossy().into_owned())
+    }
+}
+
+impl MediaLink {
+    /// Returns the percent-encoded path to be used in links.
+    pub fn encoded(&self) -> String {
+        utf8_percent_encode(&self.0, PATH_ENCODE_SET).to_string()
     }
 }
 
@@ -129,13 +471,57 @@ impl Deref for MediaLink {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// Number of hex digits of the [PostNote::content_hash].
+const CONTENT_HASH_LENGTH: usize = 16;
+/// Number of hex digits of the path hash telling apart media files with the
+/// same name, see [media_output_paths].
+const MEDIA_HASH_LENGTH: usize = 8;
+/// Page the home note is written to, see [find_home_note].
+const HOME_PAGE: &str = "index.html";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PostNote {
     pub file_name: InternalLink,
     pub properties: Properties,
     pub internal_links: Vec<InternalLink>,
     pub media_links: Vec<MediaLink>,
     pub html_content: Html,
+    /// Ids of the blocks marked with `^block-id`, e.g. `^intro`.
+    pub block_ids: Vec<String>,
+    /// Ids of the headings as found in the page, see [heading_id].
+    pub heading_ids: Vec<String>,
+    /// The headings within the configured toc levels.
+    pub toc: Vec<TocEntry>,
+    /// The next older note, see [link_neighbors].
+    pub prev: Option<InternalLink>,
+    /// The next newer note, see [link_neighbors].
+    pub next: Option<InternalLink>,
+    /// The previous part of the series of the note, see [link_series].
+    pub series_prev: Option<InternalLink>,
+    /// The next part of the series of the note, see [link_series].
+    pub series_next: Option<InternalLink>,
+    /// Position of the note within its series, starting at 1.
+    pub series_index: Option<usize>,
+    /// Number of notes in the series of the note.
+    pub series_total: Option<usize>,
+    /// Short hash of the rendered html and the properties, stable across
+    /// builds, e.g. for versioning or ETags. See [PostNote::update_content_hash].
+    pub content_hash: String,
+    /// The markdown file the note was parsed from.
+    #[serde(skip)]
+    pub source_path: PathBuf,
+    /// Files next to the note which get copied along with it, see
+    /// [collect_colocated_assets].
+    #[serde(skip)]
+    pub colocated_assets: Vec<ColocatedAsset>,
+}
+
+/// A file next to a note which gets copied along with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColocatedAsset {
+    pub source: PathBuf,
+    /// Path of the copy within the output directory.
+    pub output: String,
 }
 
 impl PostNote {
@@ -145,113 +531,1050 @@ impl PostNote {
         internal_links: Vec<InternalLink>,
         media_links: Vec<MediaLink>,
         html_content: Html,
+        block_ids: Vec<String>,
+        heading_ids: Vec<String>,
     ) -> Self {
-        Self {
+        let mut note = Self {
             file_name,
             properties,
             media_links,
             internal_links,
             html_content,
+            block_ids,
+            heading_ids,
+            toc: Vec::new(),
+            prev: None,
+            next: None,
+            series_prev: None,
+            series_next: None,
+            series_index: None,
+            series_total: None,
+            content_hash: String::new(),
+            source_path: PathBuf::new(),
+            colocated_assets: Vec::new(),
+        };
+        note.update_content_hash();
+
+        note
+    }
+
+    /// Whether the note is public. Private notes are only built with
+    /// `include_private` and are kept out of the feeds, the sitemap, the
+    /// `llms.txt` and the content map.
+    pub fn is_published(&self) -> bool {
+        self.properties.public
+    }
+
+    /// Recomputes the [PostNote::content_hash], which has to happen whenever
+    /// the html or the properties change.
+    pub fn update_content_hash(&mut self) {
+        let properties = serde_json::to_vec(&self.properties).unwrap_or_default();
+        let mut content = self.html_content.as_bytes().to_vec();
+        content.extend(properties);
+
+        self.content_hash = content_hash(&content)[..CONTENT_HASH_LENGTH].to_string();
+    }
+
+    /// Replaces the links pointing to one of the targets with their text and
+    /// drops them from the internal and structured links.
+    pub fn strip_links(&mut self, targets: &HashSet<InternalLink>) {
+        let html = ANCHOR_RE.replace_all(&self.html_content, |caps: &regex::Captures| {
+            let target = caps[1].split(['#', '?']).next().unwrap_or_default();
+            if targets.contains(target) {
+                caps[2].to_string()
+            } else {
+                caps[0].to_string()
+            }
+        });
+        self.html_content = Html(html.into_owned());
+        self.internal_links
+            .retain(|link| !targets.contains(link.target()));
+        if let Some(resource_links) = &mut self.properties.links {
+            resource_links.retain(|link| {
+                !targets.contains(link.url.split(['#', '?']).next().unwrap_or_default())
+            });
         }
+        self.update_content_hash();
+    }
+
+    /// Adds the class of existing or missing notes to the links pointing to
+    /// other notes, depending on whether their target is one of `existing`.
+    pub fn mark_links(&mut self, existing: &HashSet<InternalLink>, classes: &LinkClassSettings) {
+        let targets = self
+            .internal_links
+            .iter()
+            .map(InternalLink::target)
+            .collect::<HashSet<&str>>();
+
+        let html = ANCHOR_START_RE.replace_all(&self.html_content, |caps: &regex::Captures| {
+            let target = caps[1].split(['#', '?']).next().unwrap_or_default();
+            if !targets.contains(target) {
+                return caps[0].to_string();
+            }
+
+            let class = if existing.contains(target) {
+                &classes.existing
+            } else {
+                &classes.missing
+            };
+            format!("{} class=\"{}\"", &caps[0], class)
+        });
+        self.html_content = Html(html.into_owned());
+        self.update_content_hash();
     }
 }
 
+/// Matches the opening of anchors within the rendered html.
+static ANCHOR_START_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<a href="([^"]*)""#).expect("Invalid anchor start pattern"));
+
+/// Matches anchors within the rendered html.
+static ANCHOR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)<a href="([^"]*)"[^>]*>(.*?)</a>"#).expect("Invalid anchor pattern")
+});
+
 pub enum PostNoteEntry {
     Public(Box<PostNote>),
     Private,
+    /// The note has no content and empty notes are configured to be skipped.
+    Empty,
+    /// The note violates the schema and the schema policy drops it or
+    /// aborts the build.
+    SchemaViolation(String),
+}
+
+/// File names of all notes, collected before any note gets parsed so
+/// wikilinks can be resolved against them.
+#[derive(Debug, Clone, Default)]
+pub struct NoteIndex {
+    links: HashSet<String>,
+    loose: HashMap<String, InternalLink>,
+}
+
+impl NoteIndex {
+    pub fn new(paths: &[PathBuf]) -> Self {
+        let mut index = NoteIndex::default();
+        for link in paths
+            .iter()
+            .filter_map(|path| InternalLink::try_from(path.clone()).ok())
+        {
+            index.loose.insert(loose_key(&link), link.clone());
+            index.links.insert(link.0);
+        }
+
+        index
+    }
+
+    /// Hash of the names of all notes, so results depending on which notes
+    /// exist can be told apart.
+    pub fn fingerprint(&self) -> String {
+        let mut links = self.links.iter().map(String::as_str).collect::<Vec<&str>>();
+        links.sort_unstable();
+
+        content_hash(links.join("\n").as_bytes())
+    }
+
+    /// Whether a note is linked as the target of the link.
+    fn contains(&self, link: &InternalLink) -> bool {
+        self.links.contains(link.target())
+    }
+
+    /// Rewrites the link to the canonical link of a note if its target
+    /// doesn't exist but matches a note loosely.
+    fn resolve(&self, link: InternalLink) -> InternalLink {
+        if self.links.contains(link.target()) {
+            return link;
+        }
+
+        match self.loose.get(&loose_key(&link)) {
+            Some(canonical) => {
+                let rest = &link[link.target().len()..];
+                log::debug!("Resolved the wikilink {} to {}", &*link, &**canonical);
+                InternalLink(format!("{}{}", &**canonical, rest))
+            }
+            None => link,
+        }
+    }
+}
+
+/// Only keeps the lowercased alphanumerics of the link target, so links
+/// differing in case, spacing or punctuation match.
+fn loose_key(link: &InternalLink) -> String {
+    let target = link.target();
+    target
+        .strip_suffix(".html")
+        .unwrap_or(target)
+        .chars()
+        .filter(|char| char.is_alphanumeric() || *char == '/')
+        .flat_map(char::to_lowercase)
+        .collect()
 }
 
 impl PostNoteEntry {
-    pub fn new(file_name: &Path, raw_md: &str) -> Result<PostNoteEntry> {
-        let (pre_processed_raw_md, media) = match pre_process_media_wikilinks(raw_md) {
+    /// Parses the note, wikilinks are resolved against the notes of the index
+    /// if loose wikilinks are enabled.
+    pub fn new(
+        path: &Path,
+        raw_md: &str,
+        settings: &ContentSettings,
+        index: &NoteIndex,
+    ) -> Result<PostNoteEntry, Error> {
+        Self::parse(path, raw_md, settings, index).map_err(|source| Error::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    fn parse(
+        path: &Path,
+        raw_md: &str,
+        settings: &ContentSettings,
+        index: &NoteIndex,
+    ) -> Result<PostNoteEntry> {
+        let (pre_processed_raw_md, mut media) = match pre_process_media_wikilinks(
+            raw_md,
+            &settings.media_directories,
+            settings.media_titles,
+        ) {
             Ok((md, media)) => (md, media),
             Err(err) => {
                 log::warn!("Could not pre-process media wikilinks: {}", err);
                 (Cow::from(raw_md), Vec::new())
             }
         };
+        // Only the images written as media wikilinks may become figures.
+        let wikilink_media = media
+            .iter()
+            .map(|link| format!("./{}", link.encoded()))
+            .collect::<HashSet<String>>();
+        let pre_processed_raw_md = if settings.math.latex {
+            Cow::from(pre_process_latex_math(&pre_processed_raw_md).into_owned())
+        } else {
+            pre_processed_raw_md
+        };
 
         let arena = Arena::new();
         let mut options = Options::default();
 
         options.extension.table = true;
-        options.extension.math_dollars = true;
+        options.extension.math_dollars = settings.math.dollars || settings.math.latex;
+        options.extension.math_code = settings.math.code;
         options.extension.wikilinks_title_after_pipe = true;
         options.extension.front_matter_delimiter = Some("---".to_owned());
+        options.extension.alerts = settings.callouts.github;
+        options.extension.description_lists = settings.extensions.description_lists;
+        options.extension.superscript = settings.extensions.superscript;
+        options.extension.tasklist = settings.task_lists != TaskListMode::Disabled;
+        options.render.unsafe_ = settings.render.unsafe_;
+        options.render.hardbreaks = settings.render.hardbreaks;
+        options.render.width = settings.render.width;
+        options.render.escape = settings.render.escape;
 
         let root = parse_document(&arena, &pre_processed_raw_md, &options);
+        if settings.callouts.obsidian {
+            convert_callouts(root);
+        }
 
-        let file_name = InternalLink::try_from(file_name.to_path_buf())?;
+        let file_name = InternalLink::try_from(path.to_path_buf())?;
         let mut maybe_properties: Option<Properties> = Option::None;
         let mut links: Vec<InternalLink> = Vec::new();
+        let mut clip = false;
 
         for node in root.descendants() {
             match &mut node.data.borrow_mut().value {
                 NodeValue::FrontMatter(raw_front_matter) => {
-                    let raw_yml = raw_front_matter.replace("---", "").replace("\\n", "");
-                    let front_matter: Properties = serde_yaml::from_str(&raw_yml)?;
+                    let raw_yml =
+                        strip_front_matter_delimiters(raw_front_matter).replace("\\n", "");
+                    let mut raw_front_matter: serde_yaml::Value = serde_yaml::from_str(&raw_yml)?;
+                    if let serde_yaml::Value::Mapping(fields) = &mut raw_front_matter {
+                        rename_fields(&file_name, fields, &settings.front_matter.rename);
+                        for (field, value) in &settings.front_matter.defaults {
+                            fields
+                                .entry(serde_yaml::Value::from(field.as_str()))
+                                .or_insert_with(|| value.clone());
+                        }
+                    }
+                    let mut front_matter: Properties =
+                        serde_yaml::from_value(raw_front_matter.clone())?;
 
-                    if !front_matter.public {
+                    if !front_matter.public && !settings.include_private {
                         return Ok(Self::Private);
                     }
+                    if settings.front_matter.decode_entities {
+                        front_matter.title = decode_entities(&front_matter.title).into_owned();
+                        front_matter.description =
+                            decode_entities(&front_matter.description).into_owned();
+                    }
+
+                    if let Some(violation) =
+                        check_schema(&file_name, &raw_front_matter, &settings.schema)
+                    {
+                        match settings.schema_policy {
+                            SchemaPolicy::Warn => {
+                                log::warn!("{violation}, building it anyway.");
+                                front_matter.schema_violation = Some(violation);
+                            }
+                            SchemaPolicy::Skip | SchemaPolicy::Fail => {
+                                return Ok(Self::SchemaViolation(violation));
+                            }
+                        }
+                    }
+
+                    let declares_tags = raw_front_matter
+                        .get("tags")
+                        .is_some_and(|tags| !tags.is_null());
+                    if declares_tags && front_matter.tags.is_empty() {
+                        match settings.empty_tags {
+                            EmptyTagsPolicy::Allow => {}
+                            EmptyTagsPolicy::Warn => {
+                                log::warn!("{} declares no tags.", &*file_name)
+                            }
+                            EmptyTagsPolicy::Reject => {
+                                anyhow::bail!("{} declares an empty list of tags", &*file_name)
+                            }
+                        }
+                        front_matter.untagged = true;
+                    }
+
+                    // Drop duplicated tags while keeping the order they were declared in.
+                    let mut seen_tags = HashSet::new();
+                    front_matter.tags = front_matter
+                        .tags
+                        .into_iter()
+                        .map(|tag| tag.with_separators(&settings.tag_separators))
+                        .filter(|tag| seen_tags.insert(tag.clone()))
+                        .collect();
+                    clip = !front_matter.keep_questions;
 
                     maybe_properties = Some(front_matter);
                 }
 
+                NodeValue::Image(image) => {
+                    if let Some(media_link) =
+                        markdown_media(&image.url, &settings.media_directories)
+                    {
+                        media.push(media_link);
+                    }
+                }
+
                 NodeValue::WikiLink(link) => {
-                    let internal_link = InternalLink::from(link.url.to_owned());
-                    link.url = internal_link.to_string();
+                    if let Some(media_link) =
+                        media_wikilink(&link.url, &settings.wikilink_media_extensions)
+                    {
+                        link.url = format!("./{}", media_link.encoded());
+                        media.push(media_link);
+                        continue;
+                    }
+                    // Targets with any other extension, like `[[data.csv]]`,
+                    // are linked as they are.
+                    let Some(note_link) =
+                        note_wikilink(&link.url, &settings.note_extensions, index)
+                    else {
+                        continue;
+                    };
+
+                    let mut internal_link = InternalLink::from(note_link);
+                    if settings.loose_wikilinks {
+                        internal_link = index.resolve(internal_link);
+                    }
+                    link.url = internal_link.encoded();
                     links.push(internal_link);
                 }
 
-                // Clip everything that comes after `## Questions`. This is done because I'm to
-                // busy to think of a propper way to render my anki cards.
-                NodeValue::Heading(heading) => {
-                    if heading.level == 2
-                        && let Some(first_child) = node.first_child()
-                    {
-                        let borrowed = first_child.data.borrow();
-                        if let NodeValue::Text(ref text) = borrowed.value
-                            && text == "Questions"
-                        {
-                            let mut next_sibling = node.next_sibling();
-
-                            while let Some(sibling) = next_sibling {
-                                next_sibling = sibling.next_sibling();
-                                sibling.detach();
-                            }
+                _ => {}
+            }
 
-                            if let Some(previous_sibling) = node.previous_sibling() {
-                                previous_sibling.detach();
-                            }
+            // Clip right after the front matter, before the rest of the note
+            // is processed, so links within the clipped sections aren't
+            // picked up.
+            if clip {
+                clip_sections(root, &settings.clip);
+                clip = false;
+            }
+        }
 
-                            node.detach();
+        let mut properties = maybe_properties.context("Could not determine properties!")?;
 
-                            break;
-                        }
+        let is_empty = root
+            .children()
+            .all(|child| matches!(child.data.borrow().value, NodeValue::FrontMatter(_)));
+        if is_empty {
+            match settings.empty_notes {
+                EmptyNotePolicy::Allow => log::debug!("The note {} is empty.", &*file_name),
+                EmptyNotePolicy::Warn => log::warn!("The note {} is empty.", &*file_name),
+                EmptyNotePolicy::Skip => return Ok(Self::Empty),
+            }
+        }
+
+        properties.created =
+            resolve_created(path, &properties.created, settings).unwrap_or_else(|| {
+                log::warn!(
+                    "Could not determine the created date of {}, using the current time.",
+                    &*file_name
+                );
+                format_date(now())
+            });
+
+        // Register a local cover image as media so it gets copied like any other media file.
+        if let Some(image) = properties
+            .image
+            .as_deref()
+            .map(str::trim)
+            .filter(|image| !image.is_empty() && !is_remote_url(image))
+        {
+            let image = image.trim_start_matches("./").trim_start_matches('/');
+            let media_link = MediaLink::from(image.to_string());
+            properties.image = Some(media_link.encoded());
+            media.push(media_link);
+        }
+
+        for resource_link in properties.links.iter_mut().flatten() {
+            if is_remote_url(&resource_link.url) {
+                continue;
+            }
+            let mut internal_link = InternalLink::from(resource_link.url.to_owned());
+            if settings.loose_wikilinks {
+                internal_link = index.resolve(internal_link);
+            }
+            resource_link.url = internal_link.encoded();
+            links.push(internal_link);
+        }
+
+        let block_ids = root.descendants().filter_map(block_id).collect();
+
+        let mut html_buf = Vec::new();
+        NoteFormatter::format_document(
+            root,
+            &options,
+            &mut html_buf,
+            FormatState::new(settings, wikilink_media),
+        )?;
+
+        let html = Html::try_from(html_buf).with_context(|| {
+            format!("The rendered HTML of {} is not valid UTF-8", path.display())
+        })?;
+        let html = Html::from(add_heading_ids(
+            &html,
+            &heading_id_prefix(path, settings.heading_ids),
+        ));
+        let heading_ids = heading_ids(&html);
+
+        let mut note = PostNote::new(
+            file_name,
+            properties,
+            links,
+            media,
+            html,
+            block_ids,
+            heading_ids,
+        );
+        if settings.toc {
+            note.toc = table_of_contents(
+                &note.html_content,
+                settings.toc_min_level,
+                settings.toc_max_level,
+            );
+        }
+        note.source_path = path.to_path_buf();
+
+        Ok(Self::Public(Box::new(note)))
+    }
+}
+
+/// Removes the `---` lines opening and closing the front matter block, any
+/// other `---` within the block is kept.
+fn strip_front_matter_delimiters(raw_front_matter: &str) -> &str {
+    let block = raw_front_matter.trim();
+    let block = block.strip_prefix("---").unwrap_or(block);
+    let block = block.strip_suffix("---").unwrap_or(block);
+
+    block.trim_matches(['\r', '\n'])
+}
+
+/// Renames the alternate field names of the front matter to their canonical
+/// names. If both are present the canonical field is kept.
+fn rename_fields(
+    file_name: &str,
+    fields: &mut serde_yaml::Mapping,
+    renames: &BTreeMap<String, String>,
+) {
+    for (alternate, canonical) in renames {
+        let Some(value) = fields.remove(alternate.as_str()) else {
+            continue;
+        };
+
+        if fields.contains_key(canonical.as_str()) {
+            log::warn!(
+                "{} declares both {} and {}, using {}.",
+                file_name,
+                alternate,
+                canonical,
+                canonical
+            );
+            continue;
+        }
+        fields.insert(serde_yaml::Value::from(canonical.as_str()), value);
+    }
+}
+
+/// Fields which fall back to a default if they are missing and not required.
+const DEFAULTED_PROPERTIES: &[&str] = &["description", "tags"];
+
+/// Returns the violation if a required field is missing from the front
+/// matter and warns about optional fields replaced by their default.
+fn check_schema(
+    file_name: &str,
+    front_matter: &serde_yaml::Value,
+    schema: &Schema,
+) -> Option<String> {
+    let is_missing = |field: &str| {
+        front_matter
+            .get(field)
+            .is_none_or(serde_yaml::Value::is_null)
+    };
+
+    let missing = schema
+        .required
+        .iter()
+        .filter(|field| is_missing(field))
+        .map(String::as_str)
+        .collect::<Vec<&str>>();
+    if !missing.is_empty() {
+        return Some(format!(
+            "{} is missing the required field(s) {}",
+            file_name,
+            missing.join(", ")
+        ));
+    }
+
+    let defaulted = DEFAULTED_PROPERTIES
+        .iter()
+        .filter(|field| is_missing(field))
+        .copied()
+        .collect::<Vec<&str>>();
+    if !defaulted.is_empty() {
+        log::warn!(
+            "{} is missing the field(s) {}, using the defaults.",
+            file_name,
+            defaulted.join(", ")
+        );
+    }
+
+    None
+}
+
+/// Determines the created date from the first available source of the
+/// configured priority.
+fn resolve_created(
+    path: &Path,
+    front_matter_created: &str,
+    settings: &ContentSettings,
+) -> Option<String> {
+    settings
+        .date_source_priority
+        .iter()
+        .find_map(|source| match source {
+            DateSource::FrontMatter => Some(front_matter_created.trim())
+                .filter(|created| !created.is_empty())
+                .map(str::to_string),
+            DateSource::Filename => filename_date(path, &settings.filename_date_pattern),
+            DateSource::ModifiedTime => fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .map(|modified| format_date(DateTime::<Local>::from(modified).naive_local())),
+        })
+}
+
+/// Extracts the date from the file name using the configured pattern.
+fn filename_date(path: &Path, pattern: &Regex) -> Option<String> {
+    let file_stem = path.file_stem()?.to_string_lossy();
+    let caps = pattern.captures(&file_stem)?;
+    let raw_date = caps.name("date").or_else(|| caps.get(1))?.as_str();
+
+    parse_date(raw_date).map(format_date)
+}
+
+/// Prefix of the heading ids of the note parsed from `path`: none, or with
+/// ids unique across the site the slug of the note, e.g. `intro--`.
+fn heading_id_prefix(path: &Path, scope: HeadingIdScope) -> String {
+    match scope {
+        HeadingIdScope::Page => String::new(),
+        HeadingIdScope::Site => InternalLink::try_from(path.to_path_buf())
+            .map(|link| format!("{}--", link.trim_end_matches(".html")))
+            .unwrap_or_default(),
+    }
+}
+
+/// Id the heading gets within the page of the note parsed from `path`, e.g.
+/// `getting-started` or `intro--getting-started`. Repeated headings get a
+/// counter appended, see [add_heading_ids].
+pub fn heading_id(path: &Path, heading: &str, scope: HeadingIdScope) -> String {
+    format!(
+        "{}{}",
+        heading_id_prefix(path, scope),
+        heading_slug(heading)
+    )
+}
+
+/// Whether the link points to a remote resource instead of a local file.
+pub fn is_remote_url(link: &str) -> bool {
+    link.starts_with("http://") || link.starts_with("https://")
+}
+
+/// Matches `href` and `src` attributes within the rendered html.
+pub static URL_ATTRIBUTE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\b(href|src)="([^"]*)""#).expect("Invalid url attribute pattern")
+});
+
+/// Links every note to its older (`prev`) and newer (`next`) neighbor by
+/// created date, notes created at the same time are ordered by their link.
+pub fn link_neighbors(notes: &mut [PostNote]) {
+    let mut order = (0..notes.len()).collect::<Vec<usize>>();
+    order.sort_by_cached_key(|&index| {
+        let note = &notes[index];
+        (parse_date(&note.properties.created), note.file_name.clone())
+    });
+
+    for (position, &index) in order.iter().enumerate() {
+        let neighbor = |position: Option<usize>| {
+            position
+                .and_then(|position| order.get(position))
+                .map(|&neighbor| notes[neighbor].file_name.clone())
+        };
+        let prev = neighbor(position.checked_sub(1));
+        let next = neighbor(Some(position + 1));
+
+        notes[index].prev = prev;
+        notes[index].next = next;
+    }
+}
+
+/// Links the notes of every series to their previous and next part and
+/// numbers them, ordered by their `part` and then by link. Series with
+/// missing or duplicate part numbers are linked anyway, but warned about.
+pub fn link_series(notes: &mut [PostNote]) {
+    let mut series = BTreeMap::<String, Vec<(u32, usize)>>::new();
+    for (index, note) in notes.iter().enumerate() {
+        if let Some(Series { name, part }) = &note.properties.series {
+            series.entry(name.clone()).or_default().push((*part, index));
+        }
+    }
+
+    for (name, mut parts) in series {
+        parts.sort_by(|(a_part, a), (b_part, b)| {
+            a_part
+                .cmp(b_part)
+                .then_with(|| notes[*a].file_name.cmp(&notes[*b].file_name))
+        });
+        let numbers = parts.iter().map(|(part, _)| *part).collect::<Vec<u32>>();
+        if numbers.windows(2).any(|pair| pair[0] == pair[1]) {
+            log::warn!("The series {name:?} has duplicate parts: {numbers:?}");
+        } else if numbers
+            .iter()
+            .zip(1..)
+            .any(|(part, expected)| *part != expected)
+        {
+            log::warn!("The series {name:?} has gaps between its parts: {numbers:?}");
+        }
+
+        let total = parts.len();
+        for (position, &(_, index)) in parts.iter().enumerate() {
+            let neighbor = |position: Option<usize>| {
+                position
+                    .and_then(|position| parts.get(position))
+                    .map(|&(_, neighbor)| notes[neighbor].file_name.clone())
+            };
+            let series_prev = neighbor(position.checked_sub(1));
+            let series_next = neighbor(Some(position + 1));
+
+            let note = &mut notes[index];
+            note.series_prev = series_prev;
+            note.series_next = series_next;
+            note.series_index = Some(position + 1);
+            note.series_total = Some(total);
+        }
+    }
+}
+
+/// Output paths of all media files if they are collected in `media_dir`
+/// instead of mirroring the input directories, keyed by their input path.
+///
+/// Files keep their name unless several files share it, then each of them
+/// gets the start of the hash of its input path appended, e.g.
+/// `cover.1a2b3c4d.png`, so the paths don't depend on the order of the notes.
+pub fn media_output_paths(notes: &[PostNote], media_dir: &str) -> HashMap<String, String> {
+    let media_paths = notes
+        .iter()
+        .flat_map(|note| note.media_links.iter().map(|link| &**link))
+        .collect::<HashSet<&str>>();
+    let file_name = |path: &str| {
+        Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string())
+    };
+
+    let mut by_name = HashMap::<String, Vec<&str>>::new();
+    for path in media_paths {
+        by_name
+            .entry(file_name(path).to_lowercase())
+            .or_default()
+            .push(path);
+    }
+
+    let media_dir = media_dir.trim_matches('/');
+    by_name
+        .into_values()
+        .flat_map(|paths| {
+            let collides = paths.len() > 1;
+            paths.into_iter().map(move |path| {
+                let name = file_name(path);
+                let name = match (collides, name.rsplit_once('.')) {
+                    (false, _) => name,
+                    (true, Some((stem, extension))) => {
+                        format!("{stem}.{}.{extension}", path_hash(path))
                     }
+                    (true, None) => format!("{name}.{}", path_hash(path)),
+                };
+
+                (path.to_string(), format!("{media_dir}/{name}"))
+            })
+        })
+        .collect()
+}
+
+fn path_hash(path: &str) -> String {
+    content_hash(path.as_bytes())[..MEDIA_HASH_LENGTH].to_string()
+}
+
+/// Points the media links of all notes, including their cover images, to
+/// the files collected in `media_dir`, see [media_output_paths]. Links like
+/// `./media/chart.png` and `media/chart.png` are both relocated.
+pub fn relocate_media(notes: &mut [PostNote], media_dir: &str) {
+    let output_paths = media_output_paths(notes, media_dir);
+    let relocate = |encoded: &str| {
+        output_paths
+            .get(&*MediaLink::from(encoded.to_string()))
+            .map(|path| MediaLink::from(path.clone()).encoded())
+    };
+
+    for note in notes.iter_mut() {
+        let html = URL_ATTRIBUTE_RE.replace_all(&note.html_content, |caps: &regex::Captures| {
+            let relative = caps[2].strip_prefix("./").unwrap_or(&caps[2]);
+            let split = relative.find(['#', '?']).unwrap_or(relative.len());
+            let (target, rest) = relative.split_at(split);
+
+            match relocate(target) {
+                Some(path) => format!("{}=\"./{path}{rest}\"", &caps[1]),
+                None => caps[0].to_string(),
+            }
+        });
+        note.html_content = Html(html.into_owned());
+
+        if let Some(image) = note.properties.image.as_deref().and_then(relocate) {
+            note.properties.image = Some(image);
+        }
+        note.update_content_hash();
+    }
+}
+
+/// Collects the files next to each note whose name matches the pattern and
+/// points the `./<name>` links of the note to their copies. The copies are
+/// placed in a directory named after their note, e.g. `setup/data.json` for
+/// `guides/setup.md`, so files with the same name next to different notes
+/// don't collide.
+///
+/// # Errors
+///
+/// Returns an error if the directory of a note can't be read.
+pub fn collect_colocated_assets(
+    notes: &mut [PostNote],
+    pattern: &Regex,
+    note_extensions: &[String],
+) -> Result<()> {
+    for note in notes.iter_mut() {
+        let Some(directory) = note.source_path.parent() else {
+            continue;
+        };
+        let read_dir = fs::read_dir(directory)
+            .with_context(|| format!("Could not read the directory {}", directory.display()))?;
+
+        let mut files = read_dir
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && !is_note_file(path, note_extensions))
+            .filter_map(|path| {
+                let name = path.file_name()?.to_string_lossy().into_owned();
+                (!name.starts_with('.') && pattern.is_match(&name)).then_some((name, path))
+            })
+            .collect::<Vec<(String, PathBuf)>>();
+        files.sort();
+
+        let slug = note.file_name.trim_end_matches(".html").to_string();
+        note.colocated_assets = files
+            .into_iter()
+            .map(|(name, source)| ColocatedAsset {
+                source,
+                output: format!("{slug}/{name}"),
+            })
+            .collect();
+        if note.colocated_assets.is_empty() {
+            continue;
+        }
+
+        let html = URL_ATTRIBUTE_RE.replace_all(&note.html_content, |caps: &regex::Captures| {
+            let Some(relative) = caps[2].strip_prefix("./") else {
+                return caps[0].to_string();
+            };
+            let split = relative.find(['#', '?']).unwrap_or(relative.len());
+            let (target, rest) = relative.split_at(split);
+            let target = percent_decode_str(target).decode_utf8_lossy();
+
+            let asset = note.colocated_assets.iter().find(|asset| {
+                asset
+                    .source
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy() == target)
+            });
+            match asset {
+                Some(asset) => {
+                    let output = MediaLink::from(asset.output.clone()).encoded();
+                    format!("{}=\"./{output}{rest}\"", &caps[1])
                 }
+                None => caps[0].to_string(),
+            }
+        });
+        note.html_content = Html(html.into_owned());
+        note.update_content_hash();
+    }
 
-                _ => {}
+    Ok(())
+}
+
+/// Returns the link of the configured home note, which becomes the landing
+/// page of the website.
+///
+/// # Errors
+///
+/// Returns an error if there is no public note with that name, or if another
+/// note is already written to the `index.html`.
+pub fn find_home_note(notes: &[PostNote], home_note: &str) -> Result<InternalLink> {
+    let link = InternalLink::from(home_note.to_string());
+    if !notes.iter().any(|note| note.file_name == link) {
+        anyhow::bail!("The home note {home_note} doesn't exist or isn't public");
+    }
+    if link.target() != HOME_PAGE && notes.iter().any(|note| &*note.file_name == HOME_PAGE) {
+        anyhow::bail!("The home note {home_note} conflicts with the note written to {HOME_PAGE}");
+    }
+
+    Ok(link)
+}
+
+/// Moves notes to their final output location and rewrites the links of all
+/// notes, so they still point to the moved notes and resolve relative to the
+/// new location:
+/// - The `home_note` is placed at `index.html`.
+/// - With `categorized` enabled, notes with a category are placed under
+///   `<category>/<slug>.html`.
+/// - With `pretty_urls` enabled, notes are linked as `<slug>/` and written to
+///   `<slug>/index.html`.
+/// - With a `base_path`, links are written root-relative with that prefix.
+pub fn relocate_notes(
+    notes: &mut [PostNote],
+    categorized: bool,
+    pretty_urls: bool,
+    base_path: Option<&str>,
+    home_note: Option<&InternalLink>,
+) {
+    let moved = notes
+        .iter()
+        .filter_map(|note| {
+            let mut link = note.file_name.clone();
+            if home_note == Some(&note.file_name) {
+                link = InternalLink(HOME_PAGE.to_string());
+            } else {
+                if categorized && let Some(category) = note.properties.category.as_deref() {
+                    link = InternalLink::from(format!("{}/{}", category, &*link));
+                }
+                if pretty_urls {
+                    link = InternalLink(format!("{}/", link.trim_end_matches(".html")));
+                }
             }
+
+            (link != note.file_name).then(|| (note.file_name.to_string(), link))
+        })
+        .collect::<HashMap<String, InternalLink>>();
+
+    if moved.is_empty() && base_path.is_none() {
+        return;
+    }
+
+    let relocate = |target: &str| {
+        moved
+            .get(target)
+            .map_or(target.to_string(), |link| link.to_string())
+    };
+
+    for note in notes.iter_mut() {
+        if let Some(relocated) = moved.get(&*note.file_name) {
+            note.file_name = relocated.clone();
         }
 
-        let properties = maybe_properties.context("Could not determine properties!")?;
+        let root = note.file_name.root_path(base_path);
+        let targets = note
+            .internal_links
+            .iter()
+            .map(|link| link.target().to_string())
+            .collect::<HashSet<String>>();
 
-        let mut html_buf = Vec::new();
-        format_html(root, &options, &mut html_buf)?;
+        let relocate_url = |url: &str| {
+            let split = url.find(['#', '?']).unwrap_or(url.len());
+            let (target, rest) = url.split_at(split);
+
+            if let Some(relative) = url.strip_prefix("./") {
+                format!("{root}{relative}")
+            } else if targets.contains(target) {
+                format!("{root}{}{rest}", relocate(target))
+            } else {
+                url.to_string()
+            }
+        };
+
+        let html = URL_ATTRIBUTE_RE.replace_all(&note.html_content, |caps: &regex::Captures| {
+            format!("{}=\"{}\"", &caps[1], relocate_url(&caps[2]))
+        });
+        note.html_content = Html(html.into_owned());
+        for resource_link in note.properties.links.iter_mut().flatten() {
+            resource_link.url = relocate_url(&resource_link.url);
+        }
+
+        for link in note.internal_links.iter_mut() {
+            let rest = &link[link.target().len()..];
+            *link = InternalLink(format!("{}{}", relocate(link.target()), rest));
+        }
+        note.update_content_hash();
+    }
+}
+
+/// Whether the file has one of the extensions of notes, ignoring case.
+pub fn is_note_file(path: &Path, note_extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            note_extensions
+                .iter()
+                .any(|note_extension| note_extension.eq_ignore_ascii_case(extension))
+        })
+}
 
-        let html = Html::try_from(html_buf)?;
+/// Returns the wikilink without the note extension if it points to a note:
+/// its target has no extension, `.html` or one of the note extensions, or
+/// it names a note like `[[v1.2]]`.
+fn note_wikilink(url: &str, note_extensions: &[String], index: &NoteIndex) -> Option<String> {
+    let (target, rest) = url.split_at(url.find(['#', '?']).unwrap_or(url.len()));
+    let Some(extension) = Path::new(target)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .filter(|extension| !extension.is_empty() && extension.chars().all(char::is_alphanumeric))
+    else {
+        return Some(url.to_string());
+    };
 
-        Ok(Self::Public(Box::new(PostNote::new(
-            file_name, properties, links, media, html,
-        ))))
+    if extension.eq_ignore_ascii_case("html") {
+        return Some(url.to_string());
+    }
+    if note_extensions
+        .iter()
+        .any(|note_extension| note_extension.eq_ignore_ascii_case(extension))
+    {
+        let stem = &target[..target.len() - extension.len() - 1];
+        return Some(format!("{stem}{rest}"));
     }
+
+    index
+        .contains(&InternalLink::from(url.to_string()))
+        .then(|| url.to_string())
+}
+
+/// Returns the media file a wikilink points to, if the extension of its target
+/// is one of the media extensions.
+fn media_wikilink(url: &str, extensions: &[String]) -> Option<MediaLink> {
+    let target = url.split(['#', '?']).next().unwrap_or_default();
+    let extension = Path::new(target).extension()?.to_str()?;
+
+    extensions
+        .iter()
+        .any(|media_extension| media_extension.eq_ignore_ascii_case(extension))
+        .then(|| {
+            MediaLink::from(
+                target
+                    .trim_start_matches("./")
+                    .trim_start_matches('/')
+                    .to_string(),
+            )
+        })
+}
+
+/// Returns the media file a markdown image like `![](media/chart.png)`
+/// points to, if it's within one of the media directories.
+fn markdown_media(url: &str, media_directories: &[String]) -> Option<MediaLink> {
+    if is_remote_url(url) {
+        return None;
+    }
+    let target = url.split(['#', '?']).next().unwrap_or_default();
+    let media_link = MediaLink::from(target.trim_start_matches("./").to_string());
+
+    media_directories
+        .iter()
+        .any(|directory| {
+            media_link
+                .strip_prefix(directory.trim_matches('/'))
+                .is_some_and(|rest| rest.starts_with('/'))
+        })
+        .then_some(media_link)
+}
+
+/// Matches code (which is kept as is) and LaTeX-style math delimiters. Math
+/// never spans a blank line, so a stray delimiter doesn't swallow the
+/// following paragraphs.
+static LATEX_MATH_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?ms)(^```.*?^```|`[^`\n]*`)|\\\(((?:[^\n]|\n[^\n])+?)\\\)|\\\[((?:[^\n]|\n[^\n])+?)\\\]",
+    )
+    .expect("Invalid LaTeX math pattern")
+});
+
+/// Rewrites `\(inline\)` and `\[display\]` math to dollar math, which comrak
+/// understands. Code blocks and spans are left untouched.
+fn pre_process_latex_math(raw_md: &str) -> Cow<'_, str> {
+    LATEX_MATH_RE.replace_all(raw_md, |caps: &regex::Captures| {
+        if let Some(inline) = caps.get(2) {
+            format!("${}$", inline.as_str())
+        } else if let Some(display) = caps.get(3) {
+            format!("$${}$$", display.as_str())
+        } else {
+            caps[0].to_string()
+        }
+    })
 }
 
 // This is probably going to be a temporary solution.
-fn pre_process_media_wikilinks(raw_md: &str) -> Result<(Cow<'_, str>, Vec<MediaLink>)> {
-    let re = Regex::new(r"!\[\[(media/[^|\]]+)(?:\|([^\[\]]+))?\]\]")?;
+fn pre_process_media_wikilinks<'a>(
+    raw_md: &'a str,
+    media_directories: &[String],
+    titles: MediaTitlePlacement,
+) -> Result<(Cow<'a, str>, Vec<MediaLink>)> {
+    if media_directories.is_empty() {
+        return Ok((Cow::from(raw_md), Vec::new()));
+    }
+
+    let directories = media_directories
+        .iter()
+        .map(|directory| regex::escape(directory.trim_matches('/')))
+        .collect::<Vec<String>>()
+        .join("|");
+    let re = Regex::new(&format!(
+        r"!\[\[((?:{directories})/[^|\]]+)(?:\|([^\[\]]+))?\]\]"
+    ))?;
     let mut media_links = Vec::new();
 
     let pre_processed_raw_md = re.replace_all(raw_md, |caps: &regex::Captures| {
@@ -260,8 +1583,910 @@ fn pre_process_media_wikilinks(raw_md: &str) -> Result<(Cow<'_, str>, Vec<MediaL
 
         media_links.push(link.clone());
 
-        format!("![{}](./{})", title, link.to_string().replace(" ", "%20"))
+        match titles {
+            MediaTitlePlacement::Figcaption if !title.is_empty() => format!(
+                "![{}](./{} \"{}\")",
+                title,
+                link.encoded(),
+                title.replace('\\', "\\\\").replace('"', "\\\"")
+            ),
+            _ => format!("![{}](./{})", title, link.encoded()),
+        }
     });
 
     Ok((pre_processed_raw_md, media_links))
 }
+
+/// Parses the markdown as the public note at `file_name`, for tests.
+#[cfg(test)]
+pub fn parse_public(file_name: &str, raw_md: &str) -> PostNote {
+    parse_public_with(file_name, raw_md, &ContentSettings::default())
+}
+
+/// Parses the markdown as the public note at `file_name` with the given
+/// settings, for tests.
+#[cfg(test)]
+pub fn parse_public_with(file_name: &str, raw_md: &str, settings: &ContentSettings) -> PostNote {
+    match PostNoteEntry::new(
+        Path::new(file_name),
+        raw_md,
+        settings,
+        &NoteIndex::default(),
+    )
+    .unwrap()
+    {
+        PostNoteEntry::Public(post_note) => *post_note,
+        _ => panic!("Expected a public note"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{ClipMatch, ClipRule, ClipScope};
+    use pretty_assertions::assert_eq;
+    use tera::{Context, Tera};
+
+    #[test]
+    fn test_nested_extra_front_matter_is_reachable_in_templates() {
+        let note = parse_public(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\nauthor:\n  name: Tim\n  url: https://example.com\n---\nContent",
+        );
+        let mut context = Context::new();
+        context.insert("note", &note);
+        let produced = Tera::one_off(
+            "{{ note.properties.extra.author.name }} {{ note.properties.extra.author.url }}",
+            &context,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!("Tim https://example.com", produced);
+    }
+
+    #[test]
+    fn test_duplicate_tags_are_removed() {
+        let note = parse_public(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\ntags: [rust, cooking, Rust, rust]\ncreated: 2025-05-23T13:35\npublic: true\n---\n",
+        );
+
+        assert_eq!(
+            vec![Tag::from("rust"), Tag::from("cooking")],
+            note.properties.tags
+        );
+    }
+
+    #[test]
+    fn test_filename_date_is_used_without_front_matter_date() {
+        let settings = ContentSettings::default();
+        let raw_md = "---\ntitle: Note\ndescription: Description\ntags: []\npublic: true\n---\n";
+
+        let produced = match PostNoteEntry::new(
+            Path::new("2024-01-15-note.md"),
+            raw_md,
+            &settings,
+            &NoteIndex::default(),
+        )
+        .unwrap()
+        {
+            PostNoteEntry::Public(post_note) => post_note,
+            _ => panic!("Expected a public note"),
+        };
+
+        assert_eq!("2024-01-15T00:00", produced.properties.created);
+        assert_ne!(
+            "2024-01-15T00:00",
+            parse_public_with("note.md", raw_md, &settings)
+                .properties
+                .created
+        );
+    }
+
+    #[test]
+    fn test_triple_dashes_in_front_matter_and_body() {
+        let note = parse_public(
+            "note.md",
+            "---\ntitle: Before --- after\ndescription: \"---\"\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nAbove\n\n---\n\nBelow",
+        );
+
+        assert_eq!("Before --- after", note.properties.title);
+        assert_eq!("---", note.properties.description);
+        assert_eq!(
+            "<p>Above</p>\n<hr />\n<p>Below</p>",
+            note.html_content.trim()
+        );
+    }
+
+    #[test]
+    fn test_html_constructors_trim_alike() {
+        let raw_html = "\n<p>Content</p>\n\n";
+
+        let from_bytes = Html::try_from(raw_html.as_bytes().to_vec()).unwrap();
+        let from_string = Html::from(raw_html.to_string());
+
+        assert_eq!("<p>Content</p>", &*from_bytes);
+        assert_eq!(&*from_string, &*from_bytes);
+    }
+
+    #[test]
+    fn test_heading_id_scope() {
+        let raw_md = "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n# Setup\n\n## Setup & *Usage*\n\n## Setup";
+        let site_wide = ContentSettings {
+            heading_ids: HeadingIdScope::Site,
+            ..Default::default()
+        };
+
+        let per_page = parse_public("note.md", raw_md);
+        let site = parse_public_with("note.md", raw_md, &site_wide);
+
+        assert_eq!(
+            "<h1 id=\"setup\">Setup</h1>\n<h2 id=\"setup-usage\">Setup &amp; <em>Usage</em></h2>\n<h2 id=\"setup-1\">Setup</h2>",
+            per_page.html_content.trim()
+        );
+        assert_eq!(
+            "<h1 id=\"note--setup\">Setup</h1>\n<h2 id=\"note--setup-usage\">Setup &amp; <em>Usage</em></h2>\n<h2 id=\"note--setup-1\">Setup</h2>",
+            site.html_content.trim()
+        );
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        let raw_md = "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nContent";
+
+        let produced = parse_public("note.md", raw_md).content_hash;
+
+        assert_eq!(CONTENT_HASH_LENGTH, produced.len());
+        assert_eq!(produced, parse_public("note.md", raw_md).content_hash);
+        assert_ne!(
+            produced,
+            parse_public("note.md", &raw_md.replace("Content", "Changed")).content_hash
+        );
+        assert_ne!(
+            produced,
+            parse_public("note.md", &raw_md.replace("title: Note", "title: Other")).content_hash
+        );
+    }
+
+    #[test]
+    fn test_title_only_note_builds_with_defaults() {
+        let produced = parse_public("note.md", "---\ntitle: Note\npublic: true\n---\nContent");
+
+        assert_eq!("", produced.properties.description);
+        assert!(produced.properties.tags.is_empty());
+        assert!(parse_date(&produced.properties.created).is_some());
+    }
+
+    #[test]
+    fn test_front_matter_fields_are_renamed() {
+        let mut settings = ContentSettings::default();
+        settings.front_matter.rename = BTreeMap::from([
+            ("summary".to_string(), "description".to_string()),
+            ("date".to_string(), "created".to_string()),
+            ("name".to_string(), "title".to_string()),
+        ]);
+
+        let produced = parse_public_with(
+            "note.md",
+            "---\ntitle: Title\nname: Name\nsummary: Summary\ndate: 2024-01-15T13:35\npublic: true\n---\nContent",
+            &settings,
+        );
+
+        assert_eq!("Title", produced.properties.title);
+        assert_eq!("Summary", produced.properties.description);
+        assert_eq!("2024-01-15T13:35", produced.properties.created);
+        assert!(!produced.properties.extra.contains_key("name"));
+    }
+
+    #[test]
+    fn test_empty_tags_are_distinguished_from_missing_tags() {
+        let settings = ContentSettings {
+            empty_tags: EmptyTagsPolicy::Reject,
+            ..Default::default()
+        };
+
+        let Err(Error::Parse { path, source }) = PostNoteEntry::new(
+            Path::new("note.md"),
+            "---\ntitle: Note\ntags: []\npublic: true\n---\nContent",
+            &settings,
+            &NoteIndex::default(),
+        ) else {
+            panic!("Expected the empty tags to be rejected");
+        };
+        assert_eq!(Path::new("note.md"), path);
+        assert_eq!(
+            "note.html declares an empty list of tags",
+            source.to_string()
+        );
+
+        let produced = parse_public_with(
+            "note.md",
+            "---\ntitle: Note\npublic: true\n---\nContent",
+            &settings,
+        );
+        assert!(produced.properties.tags.is_empty());
+        assert!(!produced.properties.untagged);
+
+        let produced = parse_public(
+            "note.md",
+            "---\ntitle: Note\ntags: []\npublic: true\n---\nContent",
+        );
+        assert!(produced.properties.untagged);
+    }
+
+    #[test]
+    fn test_title_only_note_violates_strict_schema() {
+        let settings = ContentSettings {
+            schema: Schema {
+                required: ["description", "tags", "created"]
+                    .map(String::from)
+                    .to_vec(),
+            },
+            ..Default::default()
+        };
+        let raw_md = "---\ntitle: Note\npublic: true\n---\nContent";
+
+        let Ok(PostNoteEntry::SchemaViolation(produced)) = PostNoteEntry::new(
+            Path::new("note.md"),
+            raw_md,
+            &settings,
+            &NoteIndex::default(),
+        ) else {
+            panic!("Expected a schema violation");
+        };
+
+        assert_eq!(
+            "note.html is missing the required field(s) description, tags, created",
+            produced
+        );
+    }
+
+    #[test]
+    fn test_toc_only_lists_headings_within_the_levels() {
+        let settings = ContentSettings {
+            toc: true,
+            toc_min_level: 2,
+            ..Default::default()
+        };
+        let raw_md =
+            "---\ntitle: Note\npublic: true\n---\n# Title\n\n## Setup *fast*\n\n### Install\n";
+
+        let produced = parse_public_with("note.md", raw_md, &settings);
+
+        assert_eq!(
+            vec![
+                TocEntry {
+                    level: 2,
+                    id: "setup-fast".to_string(),
+                    title: "Setup fast".to_string(),
+                },
+                TocEntry {
+                    level: 3,
+                    id: "install".to_string(),
+                    title: "Install".to_string(),
+                },
+            ],
+            produced.toc
+        );
+        assert_eq!(vec!["title", "setup-fast", "install"], produced.heading_ids);
+        assert!(parse_public("note.md", raw_md).toc.is_empty());
+    }
+
+    #[test]
+    fn test_structured_links_are_parsed_and_resolved() {
+        let raw_md = "---\ntitle: Note\npublic: true\nlinks:\n  - title: Docs\n    url: https://docs.rs\n  - title: Other\n    url: Other Note.md\n---\nContent";
+
+        let produced = parse_public("note.md", raw_md);
+
+        assert_eq!(
+            Some(vec![
+                ResourceLink {
+                    title: "Docs".to_string(),
+                    url: "https://docs.rs".to_string(),
+                },
+                ResourceLink {
+                    title: "Other".to_string(),
+                    url: "other-note.html".to_string(),
+                },
+            ]),
+            produced.properties.links
+        );
+        assert_eq!(
+            vec![InternalLink::from("other-note.html".to_string())],
+            produced.internal_links
+        );
+    }
+
+    #[test]
+    fn test_links_of_another_shape_are_ignored() {
+        for links in [
+            "links: https://docs.rs",
+            "links:\n  - docs: https://docs.rs",
+        ] {
+            let raw_md = format!("---\ntitle: Note\npublic: true\n{links}\n---\nContent");
+
+            let produced = parse_public("note.md", &raw_md);
+
+            assert_eq!(None, produced.properties.links);
+        }
+    }
+
+    #[test]
+    fn test_internal_links_are_slugified() {
+        let produced = InternalLink::try_from(PathBuf::from("notes/My Note?.md")).unwrap();
+        assert_eq!("my-note.html", &*produced);
+
+        let note = parse_public(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n[[My Note]] [[My  Note#Some Heading|Title]]",
+        );
+        assert_eq!(
+            vec!["my-note.html", "my-note.html#Some Heading"],
+            note.internal_links
+                .iter()
+                .map(|link| &**link)
+                .collect::<Vec<&str>>()
+        );
+        assert!(note.html_content.contains("href=\"my-note.html\""));
+        assert!(
+            note.html_content
+                .contains("href=\"my-note.html#Some%20Heading\"")
+        );
+    }
+
+    #[test]
+    fn test_keep_questions_overrides_clipping() {
+        let raw_md = |keep_questions: bool| {
+            format!(
+                "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\nkeep_questions: {keep_questions}\n---\nIntro\n\n## Questions\n\nWhat?"
+            )
+        };
+
+        let clipped = parse_public("note.md", &raw_md(false));
+        let kept = parse_public("note.md", &raw_md(true));
+
+        assert!(!clipped.html_content.contains("What?"));
+        assert!(
+            kept.html_content
+                .contains("<h2 id=\"questions\">Questions</h2>")
+        );
+        assert!(kept.html_content.contains("<p>What?</p>"));
+    }
+
+    #[test]
+    fn test_clip_rule_removes_section_only() {
+        let settings = ContentSettings {
+            clip: vec![ClipRule::new(2, "Private", ClipMatch::Prefix, ClipScope::Section).unwrap()],
+            ..ContentSettings::default()
+        };
+
+        let produced = parse_public_with(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nIntro\n\n## Private notes\n\nSecret [[secret]]\n\n### Details\n\nMore secrets\n\n## Public\n\nShared\n\n## Questions\n\nWhat?",
+            &settings,
+        );
+
+        assert_eq!(
+            "<p>Intro</p>\n<h2 id=\"public\">Public</h2>\n<p>Shared</p>\n<h2 id=\"questions\">Questions</h2>\n<p>What?</p>",
+            produced.html_content.trim()
+        );
+        assert!(produced.internal_links.is_empty());
+    }
+
+    #[test]
+    fn test_clip_rule_removes_everything_after_heading() {
+        let settings = ContentSettings {
+            clip: vec![
+                ClipRule::new(3, r"^(Anki|Cards)$", ClipMatch::Regex, ClipScope::ToEnd).unwrap(),
+            ],
+            ..ContentSettings::default()
+        };
+
+        let produced = parse_public_with(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nIntro\n\n---\n\n### Cards\n\nFront\n\n## Answers\n\nBack",
+            &settings,
+        );
+
+        assert_eq!("<p>Intro</p>", produced.html_content.trim());
+    }
+
+    #[test]
+    fn test_empty_note_policy() {
+        let raw_md = "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n\n## Questions\n\nWhat?";
+        let mut settings = ContentSettings::default();
+
+        assert!(matches!(
+            PostNoteEntry::new(
+                Path::new("note.md"),
+                raw_md,
+                &settings,
+                &NoteIndex::default()
+            )
+            .unwrap(),
+            PostNoteEntry::Public(_)
+        ));
+
+        settings.empty_notes = EmptyNotePolicy::Skip;
+        assert!(matches!(
+            PostNoteEntry::new(
+                Path::new("note.md"),
+                raw_md,
+                &settings,
+                &NoteIndex::default()
+            )
+            .unwrap(),
+            PostNoteEntry::Empty
+        ));
+    }
+
+    #[test]
+    fn test_block_id_marker_becomes_addressable() {
+        let note = parse_public(
+            "note.md",
+            "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nSome *intro* text ^intro\n\n- tight ^item\n- list\n\nSee [[note#^intro]]",
+        );
+
+        assert_eq!(vec!["^intro", "^item"], note.block_ids);
+        assert!(
+            note.html_content
+                .contains("<p id=\"^intro\">Some <em>intro</em> text</p>")
+        );
+        assert!(note.html_content.contains("<li id=\"^item\">tight</li>"));
+        assert_eq!("note.html", note.internal_links[0].target());
+        assert_eq!(Some("^intro"), note.internal_links[0].anchor());
+    }
+
+    #[test]
+    fn test_hardbreaks_render_soft_breaks_as_br() {
+        let raw_md = "---\ntitle: Poem\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nRoses are red\nViolets are blue";
+        let mut settings = ContentSettings::default();
+
+        let produced = parse_public_with("note.md", raw_md, &settings);
+        assert_eq!(
+            "<p>Roses are red\nViolets are blue</p>",
+            produced.html_content.trim()
+        );
+
+        settings.render.hardbreaks = true;
+        let produced = parse_public_with("note.md", raw_md, &settings);
+        assert_eq!(
+            "<p>Roses are red<br />\nViolets are blue</p>",
+            produced.html_content.trim()
+        );
+    }
+
+    #[test]
+    fn test_description_list() {
+        let raw_md = "---\ntitle: Glossary\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nTerm\n: Definition";
+        let mut settings = ContentSettings::default();
+        settings.extensions.description_lists = true;
+
+        let produced = parse_public_with("note.md", raw_md, &settings);
+
+        assert_eq!(
+            "<dl>\n<dt>Term</dt>\n<dd>Definition</dd>\n</dl>",
+            produced.html_content.trim()
+        );
+    }
+
+    #[test]
+    fn test_superscript_leaves_math_alone() {
+        let raw_md = "---\ntitle: Math\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nx^2^ and $x^2^$";
+        let mut settings = ContentSettings::default();
+        settings.extensions.superscript = true;
+
+        let produced = parse_public_with("note.md", raw_md, &settings);
+
+        assert_eq!(
+            "<p>x<sup>2</sup> and <span data-math-style=\"inline\">x^2^</span></p>",
+            produced.html_content.trim()
+        );
+    }
+
+    #[test]
+    fn test_task_list_modes() {
+        let raw_md = "---\ntitle: Todo\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n- [x] Done\n- [ ] Open";
+        let mut settings = ContentSettings {
+            task_lists: TaskListMode::Static,
+            ..Default::default()
+        };
+
+        let produced = parse_public_with("note.md", raw_md, &settings);
+        assert_eq!(
+            "<ul>\n<li><input type=\"checkbox\" checked=\"\" disabled=\"\" /> Done</li>\n<li><input type=\"checkbox\" disabled=\"\" /> Open</li>\n</ul>",
+            produced.html_content.trim()
+        );
+
+        settings.task_lists = TaskListMode::Interactive;
+        let produced = parse_public_with("note.md", raw_md, &settings);
+        assert_eq!(
+            "<ul>\n<li><input type=\"checkbox\" checked=\"\" data-task-id=\"task-1\" /> Done</li>\n<li><input type=\"checkbox\" data-task-id=\"task-2\" /> Open</li>\n</ul>",
+            produced.html_content.trim()
+        );
+    }
+
+    #[test]
+    fn test_tables_are_wrapped() {
+        let raw_md = "---\ntitle: Tables\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n| a |\n| - |\n| 1 |\n\n> | b |\n> | - |";
+        let settings = ContentSettings {
+            table_wrapper: Some("table-wrapper".to_string()),
+            ..Default::default()
+        };
+
+        let produced = parse_public_with("note.md", raw_md, &settings);
+
+        assert_eq!(
+            "<div class=\"table-wrapper\">\n<table>\n<thead>\n<tr>\n<th>a</th>\n</tr>\n</thead>\n<tbody>\n<tr>\n<td>1</td>\n</tr>\n</tbody>\n</table>\n</div>\n<blockquote>\n<div class=\"table-wrapper\">\n<table>\n<thead>\n<tr>\n<th>b</th>\n</tr>\n</thead>\n</table>\n</div>\n</blockquote>",
+            &*produced.html_content
+        );
+        assert!(
+            !parse_public_with("note.md", raw_md, &ContentSettings::default())
+                .html_content
+                .contains("<div")
+        );
+    }
+
+    #[test]
+    fn test_captioned_media_wikilink_is_a_figure() {
+        let raw_md = "---\ntitle: Figures\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n![[media/cat.png|A \"sleepy\" cat]]\n\n![[media/dog.png]]\n\nInline ![[media/bird.png|Bird]] image\n\n![[media/fish.png|Fish]] ^fish\n\n![Chart](chart.png \"Chart\")";
+        let settings = ContentSettings {
+            media_titles: MediaTitlePlacement::Figcaption,
+            ..Default::default()
+        };
+
+        let produced = parse_public_with("note.md", raw_md, &settings);
+
+        assert_eq!(
+            "<figure><img src=\"./media/cat.png\" alt=\"A &quot;sleepy&quot; cat\" title=\"A &quot;sleepy&quot; cat\" /><figcaption>A &quot;sleepy&quot; cat</figcaption></figure>\n<p><img src=\"./media/dog.png\" alt=\"\" /></p>\n<p>Inline <img src=\"./media/bird.png\" alt=\"Bird\" title=\"Bird\" /> image</p>\n<figure id=\"^fish\"><img src=\"./media/fish.png\" alt=\"Fish\" title=\"Fish\" /><figcaption>Fish</figcaption></figure>\n<p><img src=\"chart.png\" alt=\"Chart\" title=\"Chart\" /></p>",
+            &*produced.html_content
+        );
+        assert!(
+            !parse_public_with("note.md", raw_md, &ContentSettings::default())
+                .html_content
+                .contains("<figure>")
+        );
+    }
+
+    #[test]
+    fn test_media_wikilinks_in_custom_directory() {
+        let (produced_md, produced_links) = pre_process_media_wikilinks(
+            "![[attachments/my image.png|Title]] ![[media/other.png]]",
+            &["attachments".to_string()],
+            MediaTitlePlacement::Alt,
+        )
+        .unwrap();
+
+        assert_eq!(
+            "![Title](./attachments/my%20image.png) ![[media/other.png]]",
+            produced_md
+        );
+        assert_eq!(
+            vec!["attachments/my image.png"],
+            produced_links
+                .iter()
+                .map(|link| &**link)
+                .collect::<Vec<&str>>()
+        );
+    }
+
+    #[test]
+    fn test_neighbors_by_created_date() {
+        let mut notes = [
+            ("middle", "2025-05-24T13:35"),
+            ("oldest", "2025-05-23T13:35"),
+            ("newest", "2025-05-25T13:35"),
+        ]
+        .map(|(name, created)| PostNote {
+            file_name: InternalLink::from(name.to_string()),
+            ..parse_public("note.md", &format!(
+                "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: {created}\npublic: true\n---\n"
+            ))
+        })
+        .to_vec();
+
+        link_neighbors(&mut notes);
+
+        assert_eq!(
+            Some(InternalLink::from("oldest".to_string())),
+            notes[0].prev
+        );
+        assert_eq!(
+            Some(InternalLink::from("newest".to_string())),
+            notes[0].next
+        );
+        assert_eq!(None, notes[1].prev);
+        assert_eq!(None, notes[2].next);
+    }
+
+    #[test]
+    fn test_series_links_its_parts() {
+        let mut notes = [
+            ("second", "Rust basics", 2),
+            ("other", "Cooking", 1),
+            ("third", "Rust basics", 3),
+            ("first", "Rust basics", 1),
+        ]
+        .map(|(name, series, part)| PostNote {
+            file_name: InternalLink::from(name.to_string()),
+            ..parse_public("note.md", &format!(
+                "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\nseries:\n  name: {series}\n  part: {part}\n---\n"
+            ))
+        })
+        .to_vec();
+
+        link_series(&mut notes);
+
+        let middle = &notes[0];
+        assert_eq!(
+            Some(Series {
+                name: "Rust basics".to_string(),
+                part: 2
+            }),
+            middle.properties.series
+        );
+        assert_eq!(
+            Some(InternalLink::from("first".to_string())),
+            middle.series_prev
+        );
+        assert_eq!(
+            Some(InternalLink::from("third".to_string())),
+            middle.series_next
+        );
+        assert_eq!(
+            (Some(2), Some(3)),
+            (middle.series_index, middle.series_total)
+        );
+        assert_eq!(None, notes[3].series_prev);
+        assert_eq!(None, notes[2].series_next);
+        assert_eq!(
+            (None, None, Some(1), Some(1)),
+            (
+                notes[1].series_prev.clone(),
+                notes[1].series_next.clone(),
+                notes[1].series_index,
+                notes[1].series_total
+            )
+        );
+    }
+
+    #[test]
+    fn test_categorized_note_is_moved() {
+        let mut notes = vec![
+            parse_public(
+                "note.md",
+                "---\ntitle: Guide\ndescription: Description\ncategory: guides\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n[[Other]] ![](./media/a.png)",
+            ),
+            PostNote {
+                file_name: InternalLink::from("other".to_string()),
+                ..parse_public(
+                    "note.md",
+                    "---\ntitle: Other\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n[[note]]",
+                )
+            },
+        ];
+
+        relocate_notes(&mut notes, true, false, None, None);
+
+        assert_eq!("guides/note.html", &*notes[0].file_name);
+        assert_eq!(
+            "<p><a href=\"../other.html\" data-wikilink=\"true\">Other</a> <img src=\"../media/a.png\" alt=\"\" /></p>",
+            notes[0].html_content.trim()
+        );
+        assert_eq!("other.html", &*notes[1].file_name);
+        assert_eq!(
+            vec!["guides/note.html"],
+            notes[1]
+                .internal_links
+                .iter()
+                .map(|link| &**link)
+                .collect::<Vec<&str>>()
+        );
+        assert!(
+            notes[1]
+                .html_content
+                .contains("<a href=\"./guides/note.html\" data-wikilink=\"true\">note</a>")
+        );
+    }
+
+    #[test]
+    fn test_wikilinks_resolve_loosely() {
+        let index = NoteIndex::new(&[PathBuf::from("My Note.md"), PathBuf::from("Rust_Tips.md")]);
+        let raw_md = "---\ntitle: Test\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n[[MY NOTE]] [[rust tips#Intro]] [[Missing]]";
+        let links = |settings: &ContentSettings| match PostNoteEntry::new(
+            Path::new("note.md"),
+            raw_md,
+            settings,
+            &index,
+        )
+        .unwrap()
+        {
+            PostNoteEntry::Public(post_note) => post_note
+                .internal_links
+                .iter()
+                .map(|link| link.to_string())
+                .collect::<Vec<String>>(),
+            _ => panic!("Expected a public note"),
+        };
+
+        assert_eq!(
+            vec!["my-note.html", "rust-tips.html#Intro", "missing.html"],
+            links(&ContentSettings::default())
+        );
+        assert_eq!(
+            vec!["my-note.html", "rust_tips.html#Intro", "missing.html"],
+            links(&ContentSettings {
+                loose_wikilinks: true,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_latex_math_delimiters() {
+        let raw_md = "---\ntitle: Test\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nInline \\(x^2\\) costs \\$5 `\\(code\\)`\n\n\\[\\sum x\\]";
+        let mut settings = ContentSettings::default();
+        settings.math.latex = true;
+
+        let produced = parse_public_with("note.md", raw_md, &settings);
+
+        assert_eq!(
+            "<p>Inline <span data-math-style=\"inline\">x^2</span> costs $5 <code>\\(code\\)</code></p>\n<p><span data-math-style=\"display\">\\sum x</span></p>",
+            produced.html_content.trim()
+        );
+    }
+
+    #[test]
+    fn test_stray_latex_math_delimiter_stays_within_its_paragraph() {
+        let raw_md = "---\ntitle: Test\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nA stray \\[ and $$ open\n\nthe next \\] paragraph, \\[a\nb\\]";
+        let mut settings = ContentSettings::default();
+        settings.math.latex = true;
+
+        let produced = parse_public_with("note.md", raw_md, &settings);
+
+        assert_eq!(
+            "<p>A stray [ and $$ open</p>\n<p>the next ] paragraph, <span data-math-style=\"display\">a\nb</span></p>",
+            produced.html_content.trim()
+        );
+    }
+
+    #[test]
+    fn test_escaped_dollar_stays_literal() {
+        let raw_md = "---\ntitle: Test\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nFrom \\$5 to \\$6 and $x$";
+
+        let produced = parse_public("note.md", raw_md);
+
+        assert_eq!(
+            "<p>From $5 to $6 and <span data-math-style=\"inline\">x</span></p>",
+            produced.html_content.trim()
+        );
+    }
+
+    #[test]
+    fn test_pretty_urls_adjust_relative_links() {
+        let mut notes = vec![parse_public(
+            "note.md",
+            "---\ntitle: Test\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n![[media/pic.png]] [[note#Intro]]",
+        )];
+
+        relocate_notes(&mut notes, false, true, None, None);
+
+        assert_eq!("note/", &*notes[0].file_name);
+        assert_eq!(
+            PathBuf::from("note/index.html"),
+            notes[0].file_name.output_path()
+        );
+        assert_eq!(
+            "<p><img src=\"../media/pic.png\" alt=\"\" /> <a href=\"../note/#Intro\" data-wikilink=\"true\">note#Intro</a></p>",
+            notes[0].html_content.trim()
+        );
+    }
+
+    #[test]
+    fn test_github_alert_renders_warning_class() {
+        let raw_md = "---\ntitle: Test\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n> [!WARNING]\n> Careful";
+
+        let produced = parse_public("note.md", raw_md);
+
+        assert_eq!(
+            "<div class=\"markdown-alert markdown-alert-warning\">\n<p class=\"markdown-alert-title\">Warning</p>\n<p>Careful</p>\n</div>",
+            produced.html_content.trim()
+        );
+    }
+
+    #[test]
+    fn test_obsidian_callouts() {
+        let raw_md = "---\ntitle: Test\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n> [!bug]- Known issue\n> Crashes\n\n> [!info]\n> Details\n\n> [!unknown]\n> Quote";
+
+        let produced = parse_public("note.md", raw_md);
+
+        assert_eq!(
+            "<div class=\"markdown-alert markdown-alert-caution\">\n<p class=\"markdown-alert-title\">Known issue</p>\n<p>Crashes</p>\n</div>\n<div class=\"markdown-alert markdown-alert-note\">\n<p class=\"markdown-alert-title\">Info</p>\n<p>Details</p>\n</div>\n<blockquote>\n<p>[!unknown]\nQuote</p>\n</blockquote>",
+            produced.html_content.trim()
+        );
+    }
+
+    #[test]
+    fn test_tags_as_scalar_sequence_or_separated_string() {
+        let parse_tags = |tags: &str| {
+            let raw_md = format!(
+                "---\ntitle: Test\ndescription: Description\ntags: {tags}\ncreated: 2025-05-23T13:35\npublic: true\n---\n"
+            );
+            parse_public("note.md", &raw_md)
+                .properties
+                .tags
+                .iter()
+                .map(|tag| tag.to_string())
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(vec!["rust"], parse_tags("rust"));
+        assert_eq!(vec!["a", "b"], parse_tags("[a, b]"));
+        assert_eq!(vec!["a", "b"], parse_tags("\"a, b\""));
+        assert_eq!(vec!["a", "b/c"], parse_tags("a b/c"));
+    }
+
+    #[test]
+    fn test_wikilinks_to_media_and_markdown_targets() {
+        let raw_md = "---\ntitle: Test\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n[[docs/Report.PDF|Report]] [[Other.md]] [[Intro.markdown]] [[v1.2]] [[data.csv]]";
+        let settings = ContentSettings {
+            note_extensions: vec!["md".to_string(), "markdown".to_string()],
+            ..Default::default()
+        };
+        let index = NoteIndex::new(&[PathBuf::from("v1.2.md")]);
+
+        let produced =
+            match PostNoteEntry::new(Path::new("note.md"), raw_md, &settings, &index).unwrap() {
+                PostNoteEntry::Public(post_note) => *post_note,
+                _ => panic!("Expected a public note"),
+            };
+
+        assert_eq!(
+            vec!["docs/Report.PDF"],
+            produced
+                .media_links
+                .iter()
+                .map(|link| &**link)
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(
+            vec!["other.html", "intro.html", "v1.2.html"],
+            produced
+                .internal_links
+                .iter()
+                .map(|link| &**link)
+                .collect::<Vec<&str>>()
+        );
+        assert!(
+            produced
+                .html_content
+                .contains("<a href=\"./docs/Report.PDF\" data-wikilink=\"true\">Report</a>")
+        );
+        assert!(
+            produced
+                .html_content
+                .contains("<a href=\"data.csv\" data-wikilink=\"true\">data.csv</a>")
+        );
+    }
+
+    #[test]
+    fn test_base_path_prefixes_links() {
+        let raw_md = "---\ntitle: Test\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n[[Other]] ![[media/pic.png]]";
+
+        let mut notes = vec![parse_public("note.md", raw_md)];
+        relocate_notes(&mut notes, false, false, Some("/notes"), None);
+        assert_eq!(
+            "<p><a href=\"/notes/other.html\" data-wikilink=\"true\">Other</a> <img src=\"/notes/media/pic.png\" alt=\"\" /></p>",
+            notes[0].html_content.trim()
+        );
+
+        let mut notes = vec![parse_public("note.md", &raw_md.replace("Other", "Note"))];
+        relocate_notes(&mut notes, false, true, Some("notes/"), None);
+        assert!(
+            notes[0]
+                .html_content
+                .contains("<a href=\"/notes/note/\" data-wikilink=\"true\">Note</a>")
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::io::{IsTerminal, stderr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+const PROGRESS_TEMPLATE: &str = "{msg} [{bar:40}] {pos}/{len}";
+
+/// Tracks how many items of a (parallel) build phase have been processed and
+/// optionally shows a progress bar for it. The processed items are logged
+/// once the phase is finished.
+pub struct Progress {
+    phase: String,
+    total: usize,
+    processed: AtomicUsize,
+    bar: Option<ProgressBar>,
+}
+
+impl Progress {
+    /// Creates the progress of a phase with `total` items. The progress bar is
+    /// only shown if enabled, stderr is a terminal and logging isn't turned
+    /// off.
+    pub fn new(phase: &str, total: usize, enabled: bool) -> Self {
+        let show_bar =
+            enabled && stderr().is_terminal() && log::max_level() != log::LevelFilter::Off;
+        let bar = show_bar.then(|| {
+            let bar =
+                ProgressBar::with_draw_target(Some(total as u64), ProgressDrawTarget::stderr())
+                    .with_message(phase.to_string());
+            if let Ok(style) = ProgressStyle::with_template(PROGRESS_TEMPLATE) {
+                bar.set_style(style.progress_chars("=> "));
+            }
+            bar
+        });
+
+        Self {
+            phase: phase.to_string(),
+            total,
+            processed: AtomicUsize::new(0),
+            bar,
+        }
+    }
+
+    /// Marks one more item as processed.
+    pub fn inc(&self) {
+        self.processed.fetch_add(1, Ordering::Relaxed);
+        if let Some(bar) = &self.bar {
+            bar.inc(1);
+        }
+    }
+
+    /// The processed items so far, e.g. `Loading notes: 12 of 64 item(s) processed.`
+    pub fn report(&self) -> String {
+        format!(
+            "{}: {} of {} item(s) processed.",
+            self.phase,
+            self.processed.load(Ordering::Relaxed),
+            self.total
+        )
+    }
+
+    /// Finishes the phase, logs the processed items and returns their number.
+    pub fn finish(self) -> usize {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+        log::info!("{}", self.report());
+
+        self.processed.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post_note::{NoteIndex, PostNoteEntry};
+    use crate::settings::ContentSettings;
+    use pretty_assertions::assert_eq;
+    use rayon::prelude::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_progress_counts_parallel_items() {
+        let fixtures = (0..64)
+            .map(|index| {
+                (
+                    PathBuf::from(format!("note-{index}.md")),
+                    format!(
+                        "---\ntitle: Note {index}\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: {}\n---\n",
+                        index % 2 == 0
+                    ),
+                )
+            })
+            .collect::<Vec<(PathBuf, String)>>();
+        let progress = Progress::new("Loading notes", fixtures.len(), true);
+
+        let public_notes = fixtures
+            .par_iter()
+            .filter_map(|(path, raw_md)| {
+                let entry = PostNoteEntry::new(
+                    path,
+                    raw_md,
+                    &ContentSettings::default(),
+                    &NoteIndex::default(),
+                )
+                .ok();
+                progress.inc();
+                match entry {
+                    Some(PostNoteEntry::Public(post_note)) => Some(post_note),
+                    _ => None,
+                }
+            })
+            .count();
+
+        assert_eq!(32, public_notes);
+        assert_eq!(
+            "Loading notes: 64 of 64 item(s) processed.",
+            progress.report()
+        );
+        assert_eq!(64, progress.finish());
+    }
+}
@@ -0,0 +1,114 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::settings::RetrySettings;
+
+/// Runs the filesystem operation, retrying it with an exponential backoff as
+/// long as it fails with a transient error and retries are left. Other
+/// errors, like a missing source file, are returned right away.
+pub fn with_retry<T>(
+    settings: &RetrySettings,
+    mut operation: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let mut backoff = Duration::from_millis(settings.backoff_ms);
+    let mut attempt = 0;
+
+    loop {
+        match operation() {
+            Err(err) if attempt < settings.attempts && is_transient(&err, settings) => {
+                attempt += 1;
+                log::debug!(
+                    "Retrying after a transient error ({}/{}): {}",
+                    attempt,
+                    settings.attempts,
+                    err
+                );
+                thread::sleep(backoff);
+                backoff = backoff.saturating_mul(2);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// [fs::write] retried on transient errors.
+pub fn write(
+    settings: &RetrySettings,
+    path: impl AsRef<Path>,
+    contents: impl AsRef<[u8]>,
+) -> io::Result<()> {
+    with_retry(settings, || fs::write(&path, &contents))
+}
+
+/// [fs::copy] retried on transient errors.
+pub fn copy(
+    settings: &RetrySettings,
+    from: impl AsRef<Path>,
+    to: impl AsRef<Path>,
+) -> io::Result<u64> {
+    with_retry(settings, || fs::copy(&from, &to))
+}
+
+fn is_transient(err: &io::Error, settings: &RetrySettings) -> bool {
+    match err.kind() {
+        ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::ResourceBusy => true,
+        ErrorKind::PermissionDenied => settings.permission_denied,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn settings() -> RetrySettings {
+        RetrySettings {
+            attempts: 3,
+            backoff_ms: 1,
+            permission_denied: false,
+        }
+    }
+
+    #[test]
+    fn test_transient_errors_are_retried() {
+        let mut calls = 0;
+
+        let produced = with_retry(&settings(), || {
+            calls += 1;
+            match calls {
+                1 => Err(io::Error::from(ErrorKind::WouldBlock)),
+                2 => Err(io::Error::from(ErrorKind::Interrupted)),
+                _ => Ok("written"),
+            }
+        });
+
+        assert_eq!("written", produced.unwrap());
+        assert_eq!(3, calls);
+    }
+
+    #[test]
+    fn test_permanent_errors_fail_fast() {
+        let mut calls = 0;
+
+        let produced = with_retry(&settings(), || {
+            calls += 1;
+            Err::<(), _>(io::Error::from(ErrorKind::NotFound))
+        });
+
+        assert_eq!(ErrorKind::NotFound, produced.unwrap_err().kind());
+        assert_eq!(1, calls);
+
+        let mut calls = 0;
+        let produced = with_retry(&settings(), || {
+            calls += 1;
+            Err::<(), _>(io::Error::from(ErrorKind::ResourceBusy))
+        });
+
+        assert_eq!(ErrorKind::ResourceBusy, produced.unwrap_err().kind());
+        assert_eq!(4, calls);
+    }
+}
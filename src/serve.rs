@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::anyhow;
+use percent_encoding::percent_decode_str;
+use tiny_http::{Header, Response};
+
+/// Page served for every path that doesn't resolve to a file.
+const NOT_FOUND_PAGE: &str = "404.html";
+/// Page served for a path pointing to a directory.
+const INDEX_PAGE: &str = "index.html";
+
+/// Minimal HTTP server to preview the built website locally.
+pub struct Server {
+    server: tiny_http::Server,
+    root: PathBuf,
+}
+
+impl Server {
+    /// Binds the server to the port on localhost, `0` picks a free port.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the port can't be bound.
+    pub fn bind(root: &Path, port: u16) -> anyhow::Result<Self> {
+        let server = tiny_http::Server::http(("127.0.0.1", port))
+            .map_err(|err| anyhow!("Could not serve on port {port}: {err}"))?;
+
+        Ok(Server {
+            server,
+            root: root.to_path_buf(),
+        })
+    }
+
+    pub fn url(&self) -> String {
+        match self.server.server_addr().to_ip() {
+            Some(addr) => format!("http://localhost:{}", addr.port()),
+            None => self.server.server_addr().to_string(),
+        }
+    }
+
+    /// Answers requests until the process is stopped.
+    pub fn run(&self) {
+        for request in self.server.incoming_requests() {
+            let (status, path) = self.resolve(request.url());
+            let response = match path.as_deref().map(fs::read) {
+                Some(Ok(content)) => Response::from_data(content)
+                    .with_status_code(status)
+                    .with_header(content_type(path.as_deref())),
+                _ => Response::from_data("Not Found")
+                    .with_status_code(404)
+                    .with_header(content_type(None)),
+            };
+
+            log::info!("{} {} {}", request.method(), request.url(), status);
+            if let Err(err) = request.respond(response) {
+                log::warn!("Could not answer request: {err}");
+            }
+        }
+    }
+
+    /// Maps the requested url to a file below the root, directories fall back
+    /// to their [INDEX_PAGE] and missing files to the [NOT_FOUND_PAGE]. The
+    /// path is percent-decoded before it's checked to stay below the root.
+    fn resolve(&self, url: &str) -> (u16, Option<PathBuf>) {
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        let path = percent_decode_str(path).decode_utf8_lossy();
+        let relative = Path::new(path.trim_start_matches('/'));
+
+        let is_safe = relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if is_safe {
+            let mut path = self.root.join(relative);
+            if path.is_dir() {
+                path.push(INDEX_PAGE);
+            }
+            if path.is_file() {
+                return (200, Some(path));
+            }
+        }
+
+        let not_found = self.root.join(NOT_FOUND_PAGE);
+        (404, not_found.is_file().then_some(not_found))
+    }
+}
+
+fn content_type(path: Option<&Path>) -> Header {
+    let extension = path
+        .and_then(|path| path.extension())
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+
+    let mime = match extension.as_deref() {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("pdf") => "application/pdf",
+        Some(_) => "application/octet-stream",
+        None => "text/plain; charset=utf-8",
+    };
+
+    Header::from_bytes("Content-Type", mime).expect("Content type header is valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+
+    fn get(url: &str, path: &str) -> String {
+        let address = url.trim_start_matches("http://");
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET {path} HTTP/1.0\r\nHost: {address}\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serve_existing_and_missing_pages() {
+        let output_dir = tempfile::tempdir().unwrap();
+        fs::write(output_dir.path().join("note.html"), "<p>Note</p>").unwrap();
+        fs::write(output_dir.path().join("404.html"), "<p>Missing</p>").unwrap();
+
+        let server = Server::bind(output_dir.path(), 0).unwrap();
+        let url = server.url();
+        thread::spawn(move || server.run());
+
+        let produced = get(&url, "/note.html");
+        assert!(produced.starts_with("HTTP/1.0 200"));
+        assert!(produced.contains("Content-Type: text/html; charset=utf-8"));
+        assert!(produced.ends_with("<p>Note</p>"));
+
+        let produced = get(&url, "/missing.html");
+        assert!(produced.starts_with("HTTP/1.0 404"));
+        assert!(produced.ends_with("<p>Missing</p>"));
+
+        let produced = get(&url, "/../note.html");
+        assert_eq!(Some("HTTP/1.0 404 Not Found"), produced.lines().next());
+
+        let produced = get(&url, "/%2E%2E/note.html");
+        assert_eq!(Some("HTTP/1.0 404 Not Found"), produced.lines().next());
+    }
+
+    #[test]
+    fn test_serve_percent_encoded_pages() {
+        let output_dir = tempfile::tempdir().unwrap();
+        fs::write(output_dir.path().join("my note.html"), "<p>Spaced</p>").unwrap();
+        fs::write(output_dir.path().join("café.html"), "<p>Café</p>").unwrap();
+
+        let server = Server::bind(output_dir.path(), 0).unwrap();
+        let url = server.url();
+        thread::spawn(move || server.run());
+
+        assert!(get(&url, "/my%20note.html").ends_with("<p>Spaced</p>"));
+        assert!(get(&url, "/caf%C3%A9.html").ends_with("<p>Café</p>"));
+    }
+}
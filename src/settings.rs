@@ -1,16 +1,57 @@
-use anyhow::Error;
+use anyhow::{Error, anyhow};
 use clap::Parser;
 use config::{Config, File};
+use derive_more::Display;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::default::Default;
-use std::path::PathBuf;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
 
-const CONFIG_PATH: &str = "./Config.toml";
+use crate::content_map;
+
+/// Config file loaded by default, and scaffolded by `--init`.
+pub const CONFIG_PATH: &str = "./Config.toml";
+/// Value of `--config` which disables loading a config file.
+const NO_CONFIG: &str = "none";
+/// Dotted keys of settings holding secrets, which are masked by
+/// `--print-config`, e.g. `deploy.token`.
+const SECRET_SETTINGS: &[&str] = &[];
+const REDACTED: &str = "<redacted>";
 
 const DEFAULT_INPUT_PATH: &str = "./notes";
 const DEFAULT_OUTPUT_PATH: &str = "./output";
 const DEFAULT_TEMPLATE_PATH: &str = "./templates";
 const DEFAULT_ASSET_PATH: &str = "./assets";
+const DEFAULT_VOLATILE_PATH: &str = "./.post_notes";
+
+const DEFAULT_MEDIA_DIRECTORY: &str = "media";
+/// Front matter fields other tools use for the modification date.
+const DEFAULT_MODIFIED_ALIASES: &[&str] = &["date_modified", "lastmod", "updated"];
+/// Extension of the files loaded as notes by default.
+const DEFAULT_NOTE_EXTENSION: &str = "md";
+const DEFAULT_WIKILINK_MEDIA_EXTENSIONS: &[&str] = &[
+    "pdf", "png", "jpg", "jpeg", "gif", "webp", "avif", "svg", "mp3", "mp4", "webm", "zip",
+];
+/// Heading of the section clipped from every note by default.
+const DEFAULT_CLIP_HEADING: &str = "Questions";
+const DEFAULT_FILENAME_DATE_PATTERN: &str = r"^(\d{4}-\d{2}-\d{2})";
+
+const DEFAULT_IO_THREADS: usize = 4;
+const DEFAULT_LARGE_NOTE_SIZE: &str = "1MB";
+const DEFAULT_RETRY_ATTEMPTS: u32 = 3;
+const DEFAULT_RETRY_BACKOFF_MS: u64 = 50;
+
+const DEFAULT_SERVE_PORT: u16 = 8080;
+const DEFAULT_LINK_CHECK_CONCURRENCY: usize = 8;
+const DEFAULT_LINK_CHECK_TIMEOUT_MS: u64 = 10_000;
+
+const DEFAULT_SITE_TITLE: &str = "post-notes";
+const DEFAULT_SITE_DESCRIPTION: &str = "Building a cute digital garden.";
+/// Front matter field the files of a tag are sorted by.
+const DEFAULT_WEIGHT_FIELD: &str = "weight";
 
 /// All settings that can be cofnigured regarding the directories which will be
 /// referenced during the site generation.
@@ -24,6 +65,10 @@ pub struct PathSettings {
     pub template: PathBuf,
     /// Asset directory paths.
     pub assets: Vec<PathBuf>,
+    /// Directory for generated files which aren't part of the website, like
+    /// the diagnostics and the parse cache. Has to differ from the other
+    /// directories, since it may be cleared.
+    pub volatile: PathBuf,
 }
 
 impl Default for PathSettings {
@@ -33,10 +78,942 @@ impl Default for PathSettings {
             output: PathBuf::from(DEFAULT_OUTPUT_PATH),
             template: PathBuf::from(DEFAULT_TEMPLATE_PATH),
             assets: vec![PathBuf::from(DEFAULT_ASSET_PATH)],
+            volatile: PathBuf::from(DEFAULT_VOLATILE_PATH),
+        }
+    }
+}
+
+/// Sources a note's created date can be determined from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateSource {
+    /// The `created` field of the front matter.
+    FrontMatter,
+    /// A date prefix of the file name, e.g. `2024-01-15-title.md`.
+    Filename,
+    /// The modification time of the file.
+    ModifiedTime,
+}
+
+/// How notes without any content besides their front matter are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyNotePolicy {
+    /// Build the note anyway.
+    #[default]
+    Allow,
+    /// Build the note, but log a warning.
+    Warn,
+    /// Don't build the note.
+    Skip,
+}
+
+/// How notes declaring an empty list of tags (`tags: []`) are handled. Notes
+/// not declaring tags at all are covered by the [Schema] instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyTagsPolicy {
+    /// Build the note as intentionally untagged.
+    #[default]
+    Allow,
+    /// Build the note, but log a warning.
+    Warn,
+    /// Fail the note.
+    Reject,
+}
+
+/// How notes violating the [Schema] are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaPolicy {
+    /// Build the note, but log a warning.
+    #[display("warn")]
+    Warn,
+    /// Drop the note and continue the build.
+    #[default]
+    #[display("skip")]
+    Skip,
+    /// Abort the build.
+    #[display("fail")]
+    Fail,
+}
+
+/// All settings regarding how the content of the notes is interpreted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentSettings {
+    /// Extensions of the files loaded as notes, e.g. `["md", "markdown"]`.
+    /// All of them are parsed as markdown.
+    pub note_extensions: Vec<String>,
+    /// Names of the directories (relative to the input directory) which
+    /// contain media files referenced via `![[...]]`.
+    pub media_directories: Vec<String>,
+    /// Options passed to the markdown renderer.
+    pub render: RenderSettings,
+    /// Sources of the created date, the first available one is used.
+    pub date_source_priority: Vec<DateSource>,
+    /// Pattern matching the date in a file name, the first capture group (or
+    /// the group named `date`) is used.
+    pub filename_date_pattern: Pattern,
+    /// How notes with an empty body are handled.
+    pub empty_notes: EmptyNotePolicy,
+    /// How notes declaring an empty list of tags are handled.
+    pub empty_tags: EmptyTagsPolicy,
+    /// Resolve wikilinks which don't match a note exactly against notes whose
+    /// names only differ in case, spacing or punctuation, like Obsidian does.
+    pub loose_wikilinks: bool,
+    /// Delimiters recognized around math.
+    pub math: MathSettings,
+    /// Optional markdown syntax extensions.
+    pub extensions: ExtensionSettings,
+    /// Flavors of callouts rendered as alert boxes.
+    pub callouts: CalloutSettings,
+    /// Extensions of wikilink targets which are linked and copied as media
+    /// files instead of notes, e.g. `[[report.pdf]]`.
+    pub wikilink_media_extensions: Vec<String>,
+    /// Front matter fields a note has to declare to be built.
+    pub schema: Schema,
+    /// How notes violating the schema are handled.
+    pub schema_policy: SchemaPolicy,
+    /// Collect the table of contents of every note as its `toc`, which the
+    /// templates list above the content.
+    pub toc: bool,
+    /// Lowest heading level listed in the `toc` of a note, e.g. `2` if the
+    /// notes use `#` for their title.
+    pub toc_min_level: u8,
+    /// Highest heading level listed in the `toc` of a note. Headings outside
+    /// of the levels still get an id.
+    pub toc_max_level: u8,
+    /// Characters separating tag levels in addition to `/`, e.g. `["."]`
+    /// nests `rust.async` under `rust` just like `rust/async`.
+    pub tag_separators: Vec<char>,
+    /// Whether heading ids only have to be unique within their note or
+    /// across the whole site.
+    pub heading_ids: HeadingIdScope,
+    /// Handling of the front matter fields before they are interpreted.
+    pub front_matter: FrontMatterSettings,
+    /// Whether `- [ ]` and `- [x]` list items are rendered as checkboxes and
+    /// how.
+    pub task_lists: TaskListMode,
+    /// Class of a div every table is wrapped in, e.g. `table-wrapper`, so
+    /// stylesheets can make wide tables scroll. Tables aren't wrapped if
+    /// unset.
+    pub table_wrapper: Option<String>,
+    /// Where the titles of media wikilinks are placed.
+    pub media_titles: MediaTitlePlacement,
+    /// Sections removed from the notes by their heading, unless a note sets
+    /// `keep_questions`. By default everything from `## Questions` on.
+    pub clip: Vec<ClipRule>,
+    /// Build private notes too, e.g. to preview them locally. They get a
+    /// `noindex` and are left out of the feeds, the sitemap and the content
+    /// map.
+    pub include_private: bool,
+}
+
+/// Removes the sections of a note whose heading matches. The pattern of a
+/// [ClipMatch::Regex] rule is compiled once when the settings are loaded, so
+/// an invalid pattern fails right away.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RawClipRule", into = "RawClipRule")]
+pub struct ClipRule {
+    /// Level of the headings the rule applies to, `2` for `## ...`.
+    pub level: u8,
+    /// Text or pattern the heading is matched against.
+    pub heading: String,
+    /// How the heading is matched.
+    pub matching: ClipMatch,
+    /// What is removed along with the heading.
+    pub scope: ClipScope,
+    /// The compiled `heading` of a [ClipMatch::Regex] rule.
+    pattern: Option<Regex>,
+}
+
+/// A [ClipRule] as written in the settings.
+#[derive(Serialize, Deserialize)]
+struct RawClipRule {
+    level: u8,
+    heading: String,
+    #[serde(default)]
+    matching: ClipMatch,
+    #[serde(default)]
+    scope: ClipScope,
+}
+
+impl ClipRule {
+    /// # Errors
+    ///
+    /// Returns an error if the heading of a [ClipMatch::Regex] rule isn't a
+    /// valid regular expression.
+    pub fn new(
+        level: u8,
+        heading: &str,
+        matching: ClipMatch,
+        scope: ClipScope,
+    ) -> Result<Self, regex::Error> {
+        let pattern = match matching {
+            ClipMatch::Regex => Some(Regex::new(heading)?),
+            ClipMatch::Exact | ClipMatch::Prefix => None,
+        };
+
+        Ok(ClipRule {
+            level,
+            heading: heading.to_string(),
+            matching,
+            scope,
+            pattern,
+        })
+    }
+
+    /// Whether the plain text of a heading matches the rule.
+    pub fn matches(&self, text: &str) -> bool {
+        match (self.matching, &self.pattern) {
+            (ClipMatch::Exact, _) => text == self.heading,
+            (ClipMatch::Prefix, _) => text.starts_with(&self.heading),
+            (ClipMatch::Regex, Some(pattern)) => pattern.is_match(text),
+            (ClipMatch::Regex, None) => false,
+        }
+    }
+}
+
+impl TryFrom<RawClipRule> for ClipRule {
+    type Error = String;
+
+    fn try_from(raw: RawClipRule) -> Result<Self, Self::Error> {
+        ClipRule::new(raw.level, &raw.heading, raw.matching, raw.scope)
+            .map_err(|err| format!("Invalid clip pattern {:?}: {err}", raw.heading))
+    }
+}
+
+impl From<ClipRule> for RawClipRule {
+    fn from(rule: ClipRule) -> Self {
+        RawClipRule {
+            level: rule.level,
+            heading: rule.heading,
+            matching: rule.matching,
+            scope: rule.scope,
+        }
+    }
+}
+
+/// The pattern follows from the heading, so it isn't compared.
+impl PartialEq for ClipRule {
+    fn eq(&self, other: &Self) -> bool {
+        (self.level, &self.heading, self.matching, self.scope)
+            == (other.level, &other.heading, other.matching, other.scope)
+    }
+}
+
+impl Eq for ClipRule {}
+
+/// A regular expression in the settings, compiled once when the settings are
+/// loaded, so an invalid pattern fails right away.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    /// # Errors
+    ///
+    /// Returns an error if the pattern isn't a valid regular expression.
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Pattern(Regex::new(pattern)?))
+    }
+}
+
+impl Deref for Pattern {
+    type Target = Regex;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for Pattern {
+    type Error = String;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        Pattern::new(&pattern).map_err(|err| format!("Invalid pattern {pattern:?}: {err}"))
+    }
+}
+
+impl From<Pattern> for String {
+    fn from(pattern: Pattern) -> Self {
+        pattern.0.as_str().to_string()
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for Pattern {}
+
+/// How the heading of a [ClipRule] is matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipMatch {
+    /// The heading equals the text.
+    #[default]
+    Exact,
+    /// The heading starts with the text.
+    Prefix,
+    /// The heading matches the regular expression.
+    Regex,
+}
+
+/// What a [ClipRule] removes along with the heading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipScope {
+    /// Everything up to the end of the note.
+    #[default]
+    ToEnd,
+    /// Everything up to the next heading of the same or a higher level.
+    Section,
+}
+
+/// Handling of the front matter fields before they are interpreted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrontMatterSettings {
+    /// Alternate field names mapped to the field they stand for, e.g.
+    /// `summary = "description"` or `date = "created"`. If a note declares
+    /// both, the canonical field wins. By default the names other tools use
+    /// for the modification date, like `updated`, map to `modified`.
+    pub rename: BTreeMap<String, String>,
+    /// Decode entities like `&amp;` in the `title` and `description`, for
+    /// notes written with escaped text. Otherwise they are taken literally
+    /// and show up escaped once more in feeds and templates.
+    pub decode_entities: bool,
+    /// Values used for fields a note doesn't declare, e.g. `public` or
+    /// `template`. Extended by the `.postnotes.toml` files of the
+    /// directories containing a note.
+    pub defaults: BTreeMap<String, serde_yaml::Value>,
+}
+
+impl Default for FrontMatterSettings {
+    fn default() -> Self {
+        FrontMatterSettings {
+            rename: DEFAULT_MODIFIED_ALIASES
+                .iter()
+                .map(|alias| (alias.to_string(), "modified".to_string()))
+                .collect(),
+            decode_entities: false,
+            defaults: BTreeMap::new(),
+        }
+    }
+}
+
+/// Where the title of a media wikilink like `![[media/photo.png|Title]]`
+/// ends up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaTitlePlacement {
+    /// The title is the alt text of the image.
+    #[default]
+    Alt,
+    /// The title is the alt text and the visible caption of a `<figure>`
+    /// wrapping the image, if the image is all its paragraph contains.
+    Figcaption,
+}
+
+/// How the checkboxes of task list items are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskListMode {
+    /// Task list items are rendered as plain list items.
+    #[default]
+    Disabled,
+    /// Disabled checkboxes which only show the state of the task.
+    Static,
+    /// Enabled checkboxes carrying a `data-task-id` like `task-1`, numbered
+    /// in the order of the items, so a script can persist their state.
+    Interactive,
+}
+
+/// Scope in which the ids of headings are unique.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HeadingIdScope {
+    /// Ids are derived from the heading only, e.g. `setup`.
+    #[default]
+    Page,
+    /// Ids are prefixed with the slug of the note, e.g. `intro--setup`.
+    Site,
+}
+
+/// Options regarding how markdown gets rendered to HTML - mirrors the render
+/// options of comrak.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    /// Render raw HTML and potentially dangerous links.
+    #[serde(rename = "unsafe")]
+    pub unsafe_: bool,
+    /// Render soft line breaks as hard line breaks.
+    pub hardbreaks: bool,
+    /// Wrap column for the rendered output, `0` disables wrapping.
+    pub width: usize,
+    /// Escape raw HTML instead of clobbering it.
+    pub escape: bool,
+}
+
+impl Default for ContentSettings {
+    fn default() -> Self {
+        ContentSettings {
+            note_extensions: vec![DEFAULT_NOTE_EXTENSION.to_string()],
+            media_directories: vec![DEFAULT_MEDIA_DIRECTORY.to_string()],
+            render: RenderSettings::default(),
+            date_source_priority: vec![DateSource::FrontMatter, DateSource::Filename],
+            filename_date_pattern: Pattern::new(DEFAULT_FILENAME_DATE_PATTERN)
+                .expect("Invalid default file name date pattern"),
+            empty_notes: EmptyNotePolicy::default(),
+            empty_tags: EmptyTagsPolicy::default(),
+            loose_wikilinks: false,
+            math: MathSettings::default(),
+            extensions: ExtensionSettings::default(),
+            callouts: CalloutSettings::default(),
+            wikilink_media_extensions: DEFAULT_WIKILINK_MEDIA_EXTENSIONS
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect(),
+            schema: Schema::default(),
+            schema_policy: SchemaPolicy::default(),
+            toc: false,
+            toc_min_level: 1,
+            toc_max_level: 6,
+            tag_separators: Vec::new(),
+            heading_ids: HeadingIdScope::default(),
+            front_matter: FrontMatterSettings::default(),
+            task_lists: TaskListMode::default(),
+            table_wrapper: None,
+            media_titles: MediaTitlePlacement::default(),
+            clip: vec![
+                ClipRule::new(2, DEFAULT_CLIP_HEADING, ClipMatch::Exact, ClipScope::ToEnd)
+                    .expect("Exact clip rules can't be invalid"),
+            ],
+            include_private: false,
+        }
+    }
+}
+
+/// Front matter fields which have to be present. By default only `title`
+/// and `public` are required, missing descriptions and tags fall back to
+/// empty values and a missing created date to the current time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Schema {
+    /// Names of the required fields, e.g. `[description, tags, created]`.
+    pub required: Vec<String>,
+}
+
+/// Flavors of callouts rendered as alert boxes, both share the same markup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalloutSettings {
+    /// GitHub alerts: `> [!NOTE]`, `> [!TIP]`, `> [!IMPORTANT]`,
+    /// `> [!WARNING]` and `> [!CAUTION]` in any casing.
+    pub github: bool,
+    /// Obsidian callouts: `> [!info] Title` including Obsidian's additional
+    /// types, which are mapped onto the closest GitHub type.
+    pub obsidian: bool,
+}
+
+impl Default for CalloutSettings {
+    fn default() -> Self {
+        CalloutSettings {
+            github: true,
+            obsidian: true,
+        }
+    }
+}
+
+/// Delimiters recognized around math, all enabled styles can be mixed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MathSettings {
+    /// `$inline$` and `$$display$$` math, `\$` stays a literal dollar.
+    pub dollars: bool,
+    /// ``$`inline`$`` math and ```` ```math ```` blocks.
+    pub code: bool,
+    /// LaTeX-style `\(inline\)` and `\[display\]` math. This conflicts
+    /// with escaped brackets, so it's disabled by default.
+    pub latex: bool,
+}
+
+impl Default for MathSettings {
+    fn default() -> Self {
+        MathSettings {
+            dollars: true,
+            code: false,
+            latex: false,
+        }
+    }
+}
+
+/// Optional markdown syntax extensions, all disabled by default.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ExtensionSettings {
+    /// Definition lists, a `Term` line followed by `: Definition` lines.
+    pub description_lists: bool,
+    /// Superscripts like `x^2^`. Carets within math are left to the math.
+    pub superscript: bool,
+}
+
+/// All settings describing the website itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SiteSettings {
+    /// Title of the website.
+    pub title: String,
+    /// Short description of the website.
+    pub description: String,
+    /// Absolute url the website gets deployed to, e.g.
+    /// `https://example.com/`. Required for everything that needs absolute
+    /// links, like feeds.
+    pub base_url: Option<String>,
+    /// Path the website is served under, e.g. `/notes` when deploying to
+    /// `https://example.com/notes/`. If set, links within the website are
+    /// written root-relative with this prefix instead of relative.
+    pub base_path: Option<String>,
+    /// Language of the notes not declaring a `lang`, `en` if unset.
+    pub lang: Option<String>,
+    /// Names of the notes pinned to the `featured` notes of the templates,
+    /// in this order and ahead of the notes marked as `featured`.
+    pub featured: Vec<String>,
+}
+
+impl Default for SiteSettings {
+    fn default() -> Self {
+        SiteSettings {
+            title: DEFAULT_SITE_TITLE.to_string(),
+            description: DEFAULT_SITE_DESCRIPTION.to_string(),
+            base_url: None,
+            base_path: None,
+            lang: None,
+            featured: Vec::new(),
+        }
+    }
+}
+
+/// All settings regarding the generated RSS, Atom and JSON feeds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct FeedSettings {
+    /// Write a feed containing all notes to `feed.xml`.
+    pub enabled: bool,
+    /// Write a feed per tag to `feeds/<tag>.xml`.
+    pub per_tag: bool,
+    /// Write a JSON Feed 1.1 containing all notes to `feed.json`.
+    pub json: bool,
+    /// Write an Atom feed containing all notes to `atom.xml`.
+    pub atom: bool,
+    /// Tags to write a feed for. If empty, all top-level tags are used.
+    pub tags: Vec<String>,
+    /// Write a `feeds.opml` listing all RSS feeds, requires `per_tag`.
+    pub opml: bool,
+}
+
+/// All settings regarding the generated sitemap.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SitemapSettings {
+    /// Write a `sitemap.xml`, requires a base url.
+    pub enabled: bool,
+}
+
+impl Default for SitemapSettings {
+    fn default() -> Self {
+        SitemapSettings { enabled: true }
+    }
+}
+
+/// All settings regarding the generated `llms.txt`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LlmsTxtSettings {
+    /// Write an `llms.txt` listing all notes grouped by their top-level tag,
+    /// requires a base url.
+    pub enabled: bool,
+}
+
+/// All settings regarding the PDF export of the notes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct PdfSettings {
+    /// External program converting a rendered note into a PDF, e.g.
+    /// `weasyprint`. It's called with the path of the note's html and of the
+    /// PDF to write. If unset, no PDFs are written.
+    pub renderer: Option<String>,
+    /// Arguments passed to the renderer before the two paths.
+    pub args: Vec<String>,
+}
+
+/// All settings regarding the variants generated for the copied raster
+/// images.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageSettings {
+    /// Write a WebP variant beside every image, e.g. `photo.png.webp`.
+    pub webp: bool,
+    /// Write an AVIF variant beside every image, e.g. `photo.png.avif`.
+    pub avif: bool,
+    /// Quality of the WebP variants from 0 to 100.
+    pub webp_quality: u8,
+    /// Quality of the AVIF variants from 0 to 100.
+    pub avif_quality: u8,
+}
+
+impl Default for ImageSettings {
+    fn default() -> Self {
+        ImageSettings {
+            webp: false,
+            avif: false,
+            webp_quality: 80,
+            avif_quality: 70,
         }
     }
 }
 
+/// All settings regarding the generated `robots.txt`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct RobotsSettings {
+    /// Write a `robots.txt`.
+    pub enabled: bool,
+    /// Paths crawlers are allowed to visit. If neither `allow` nor
+    /// `disallow` are set, everything is allowed.
+    pub allow: Vec<String>,
+    /// Paths crawlers are not allowed to visit.
+    pub disallow: Vec<String>,
+}
+
+/// All settings regarding how the website gets built.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BuildSettings {
+    /// Fall back to a minimal built-in `base.html` if the template directory
+    /// doesn't provide one.
+    pub builtin_template_fallback: bool,
+    /// Show a progress bar while loading and rendering notes.
+    pub progress: bool,
+    /// Write the serialized note as `<name>.json` next to each `<name>.html`.
+    pub emit_note_json: bool,
+    /// Write a `tags.json` listing every tag with its number of notes.
+    pub emit_tags_json: bool,
+    /// Write a `graph.json` of the notes and the links between them, e.g.
+    /// to visualize the garden with a force-directed graph.
+    pub emit_graph_json: bool,
+    /// Write a `manifest.json` listing every file of the website with its
+    /// size and content hash, after everything else was written.
+    pub emit_manifest: bool,
+    /// Write a `diagnostics.json` of the problems found while building to the
+    /// volatile directory, even if the build fails.
+    pub emit_diagnostics: bool,
+    /// Build the website even if no public notes were loaded.
+    pub allow_empty: bool,
+    /// Keep running after the build and rebuild whenever notes, templates or
+    /// assets change.
+    pub watch: bool,
+    /// Place notes with a `category` under `<category>/<slug>.html`.
+    pub categorized_output: bool,
+    /// Link notes as `<slug>/` and write them to `<slug>/index.html`.
+    pub pretty_urls: bool,
+    /// Note written to the `index.html` as the landing page of the website,
+    /// e.g. `home.md`. Links to it are rewritten accordingly.
+    pub home_note: Option<String>,
+    /// Pattern of the files next to a note which get copied along with it,
+    /// e.g. `\.(css|js|json)$`, so the note can link them as `./data.json`.
+    /// The copies are placed in a directory named after the note, e.g.
+    /// `setup/data.json`. Nothing is copied if unset.
+    pub colocated_assets: Option<Pattern>,
+    /// Octal permissions (e.g. `"644"`) applied to every written file, unix
+    /// only. Permissions are left untouched if unset.
+    pub file_mode: Option<String>,
+    /// Octal permissions (e.g. `"755"`) applied to every created directory,
+    /// unix only. Permissions are left untouched if unset.
+    pub dir_mode: Option<String>,
+    /// Number of threads copying media files. Copying is I/O-bound, so it
+    /// runs on its own pool instead of the one rendering the notes.
+    pub io_threads: usize,
+    /// Largest media file which gets copied, like `10MB` or `512KiB`. Larger
+    /// files are handled according to `oversized_media`, unlimited if unset.
+    pub max_media_size: Option<String>,
+    /// How media files larger than `max_media_size` are handled.
+    pub oversized_media: OversizedMediaPolicy,
+    /// Write a copy of every asset with a content hash in its name, linked
+    /// by the `asset` template function.
+    pub fingerprint_assets: bool,
+    /// Notes whose html is larger than this, like `1MB`, are rendered one at
+    /// a time and streamed to disk instead of in parallel.
+    pub large_note_size: String,
+    /// Also write all notes into a single `all.html` for offline reading.
+    pub single_page: bool,
+    /// Retrying of writes and copies failing with transient errors.
+    pub retry: RetrySettings,
+    /// Fail the rendering of notes whose template reads undefined variables,
+    /// even in conditions or tests where Tera treats them as missing.
+    pub strict_templates: bool,
+    /// Copy all media files, even if their copy in the output directory is
+    /// up to date.
+    pub force: bool,
+    /// Directory within the output directory, like `assets/media`, all media
+    /// files are collected in instead of mirroring the input directories.
+    pub media_output_dir: Option<String>,
+    /// Keep the parsed notes in the volatile directory and reuse them for
+    /// notes which didn't change since the last build.
+    pub parse_cache: bool,
+    /// Wipe the volatile directory, including the parse cache, before
+    /// building.
+    pub clear_cache: bool,
+}
+
+/// Retrying of filesystem operations which failed with a transient error,
+/// like a busy network mount. Other errors fail right away.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetrySettings {
+    /// Number of retries after the first failed attempt, `0` disables
+    /// retrying.
+    pub attempts: u32,
+    /// Delay before the first retry in milliseconds, doubled for every
+    /// further retry.
+    pub backoff_ms: u64,
+    /// Also treat `PermissionDenied` as transient, e.g. for files briefly
+    /// locked by a virus scanner.
+    pub permission_denied: bool,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        RetrySettings {
+            attempts: DEFAULT_RETRY_ATTEMPTS,
+            backoff_ms: DEFAULT_RETRY_BACKOFF_MS,
+            permission_denied: false,
+        }
+    }
+}
+
+/// How media files exceeding the configured maximum size are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizedMediaPolicy {
+    /// Don't copy the file and log a warning.
+    #[default]
+    Skip,
+    /// Don't copy the file and fail the build.
+    Fail,
+}
+
+impl Default for BuildSettings {
+    fn default() -> Self {
+        BuildSettings {
+            builtin_template_fallback: true,
+            progress: false,
+            emit_note_json: false,
+            emit_tags_json: false,
+            emit_graph_json: false,
+            emit_manifest: false,
+            emit_diagnostics: false,
+            allow_empty: false,
+            watch: false,
+            categorized_output: false,
+            pretty_urls: false,
+            home_note: None,
+            colocated_assets: None,
+            file_mode: None,
+            dir_mode: None,
+            io_threads: DEFAULT_IO_THREADS,
+            max_media_size: None,
+            oversized_media: OversizedMediaPolicy::default(),
+            fingerprint_assets: false,
+            large_note_size: DEFAULT_LARGE_NOTE_SIZE.to_string(),
+            single_page: false,
+            retry: RetrySettings::default(),
+            strict_templates: false,
+            force: false,
+            media_output_dir: None,
+            parse_cache: false,
+            clear_cache: false,
+        }
+    }
+}
+
+/// All settings regarding the navigation derived from the tags.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NavigationSettings {
+    /// Label of the root node all top-level tags are nested under. Without a
+    /// label (the default) the root is serialized without a `tag`.
+    pub root_label: String,
+    /// Expose the top-level tags directly instead of wrapping them in the root
+    /// node, so templates use `navigation.child_tags` instead of
+    /// `navigation.root.child_tags`.
+    pub hide_root: bool,
+    /// Tag paths like `start-here` or `blog/series` listed before their
+    /// siblings, in the given order. All other tags are sorted alphabetically.
+    pub pinned_tags: Vec<String>,
+    /// Label of the top-level tag listing the notes declaring `tags: []`,
+    /// e.g. `uncategorized`. They are left out of the navigation if unset.
+    pub uncategorized: Option<String>,
+    /// Label tags in the casing they were first declared with, e.g. `Rust`,
+    /// instead of lowercase. Links and ids stay lowercase.
+    pub original_tag_case: bool,
+    /// Front matter field holding the integer the files of a tag are sorted
+    /// by, e.g. `order`. Files without it come last, sorted by title.
+    pub weight_field: String,
+    /// Merge tags without files into their only child tag, so a chain like
+    /// `projects/2024/q1` is shown as a single node.
+    pub collapse_chains: bool,
+}
+
+impl Default for NavigationSettings {
+    fn default() -> Self {
+        NavigationSettings {
+            root_label: String::new(),
+            hide_root: false,
+            pinned_tags: Vec::new(),
+            uncategorized: None,
+            original_tag_case: false,
+            weight_field: DEFAULT_WEIGHT_FIELD.to_string(),
+            collapse_chains: false,
+        }
+    }
+}
+
+/// All settings regarding the local preview server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServeSettings {
+    /// Serve the output directory after the build.
+    pub enabled: bool,
+    /// Port the preview server listens on.
+    pub port: u16,
+}
+
+impl Default for ServeSettings {
+    fn default() -> Self {
+        ServeSettings {
+            enabled: false,
+            port: DEFAULT_SERVE_PORT,
+        }
+    }
+}
+
+/// All settings regarding the check of links to external websites.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExternalLinkSettings {
+    /// Request every linked `http(s)://` url and report the dead ones.
+    pub enabled: bool,
+    /// Number of requests made at the same time.
+    pub concurrency: usize,
+    /// Time in milliseconds a request may take before the url counts as dead.
+    pub timeout_ms: u64,
+    /// Domains which aren't checked, including their subdomains.
+    pub skip_domains: Vec<String>,
+}
+
+impl Default for ExternalLinkSettings {
+    fn default() -> Self {
+        ExternalLinkSettings {
+            enabled: false,
+            concurrency: DEFAULT_LINK_CHECK_CONCURRENCY,
+            timeout_ms: DEFAULT_LINK_CHECK_TIMEOUT_MS,
+            skip_domains: Vec::new(),
+        }
+    }
+}
+
+/// How links to notes which exist but are private are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivateLinkPolicy {
+    /// Remove the link, only its text is rendered.
+    Strip,
+    /// Keep the (dead) link and log a warning.
+    #[default]
+    Warn,
+    /// Fail the build.
+    Fail,
+}
+
+/// All settings regarding the validation of the built notes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ValidationSettings {
+    /// How links to private notes are handled.
+    pub private_links: PrivateLinkPolicy,
+    /// Whether links to a heading of another note, like `[[note#Setup]]`,
+    /// are checked to point to an existing heading.
+    pub heading_anchors: bool,
+    /// Classes marking whether the target of a link to another note exists.
+    pub link_classes: LinkClassSettings,
+}
+
+/// Classes added to the links pointing to other notes, so stylesheets can
+/// highlight dead links.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinkClassSettings {
+    pub enabled: bool,
+    /// Class of links to notes which are part of the build.
+    pub existing: String,
+    /// Class of links to notes which are missing or private.
+    pub missing: String,
+}
+
+impl Default for LinkClassSettings {
+    fn default() -> Self {
+        LinkClassSettings {
+            enabled: false,
+            existing: "internal-link".to_string(),
+            missing: "broken-link".to_string(),
+        }
+    }
+}
+
+/// All settings regarding the content map (`map.json`) used for searching.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentMapSettings {
+    /// Fields included per note: front matter fields (nested ones like
+    /// `series.name` separated by `.`) or the computed `excerpt`,
+    /// `reading_time` and `content_hash`.
+    pub fields: Vec<String>,
+    /// Split the map by top-level tag into `map/<tag>.json` plus a
+    /// `map/manifest.json` instead of writing a single `map.json`. The
+    /// bundled search and link previews expect the single `map.json`.
+    pub shard_by_tag: bool,
+}
+
+impl Default for ContentMapSettings {
+    fn default() -> Self {
+        ContentMapSettings {
+            fields: content_map::DEFAULT_FIELDS
+                .iter()
+                .map(|field| field.to_string())
+                .collect(),
+            shard_by_tag: false,
+        }
+    }
+}
+
+/// All settings regarding which notes get built.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct FilterSettings {
+    /// Only build notes carrying one of these tags (or one of their
+    /// descendants). If empty, all notes are built.
+    pub only_tags: Vec<String>,
+    /// Don't build notes carrying one of these tags (or one of their
+    /// descendants).
+    pub exclude_tags: Vec<String>,
+}
+
 /// Optional path settings used to parse command line arguments - mirros
 /// [PathSettings].
 #[derive(
@@ -60,14 +1037,134 @@ struct CliPathSettings {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[clap(short, long, value_parser, num_args = 1.., value_delimiter = ' ')]
     pub assets: Option<Vec<PathBuf>>,
+    /// Directory for generated files which aren't part of the website.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volatile: Option<PathBuf>,
+}
+
+/// Optional build settings used to parse command line arguments - mirrors
+/// [BuildSettings].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default, Parser)]
+struct CliBuildSettings {
+    /// Show a progress bar while loading and rendering notes.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub progress: bool,
+    /// Build the website even if no public notes were loaded.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub allow_empty: bool,
+    /// Keep running after the build and rebuild whenever notes, templates or
+    /// assets change.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub watch: bool,
+    /// Also write all notes into a single `all.html` for offline reading.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub single_page: bool,
+    /// Fail the rendering of notes whose template reads undefined variables.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub strict_templates: bool,
+    /// Copy all media files, even if their copy is up to date. Lets
+    /// `--init` overwrite existing files.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub force: bool,
+    /// Wipe the volatile directory before building.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub clear_cache: bool,
+}
+
+/// Optional content settings used to parse command line arguments - mirrors
+/// [ContentSettings].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default, Parser)]
+struct CliContentSettings {
+    /// Build private notes too, marked as `noindex` and left out of feeds,
+    /// sitemap and content map.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub include_private: bool,
+}
+
+/// Optional filter settings used to parse command line arguments - mirrors
+/// [FilterSettings].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default, Parser)]
+struct CliFilterSettings {
+    /// Only build notes carrying this tag, can be repeated.
+    #[arg(long = "only-tag")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub only_tags: Option<Vec<String>>,
+    /// Don't build notes carrying this tag, can be repeated.
+    #[arg(long = "exclude-tag")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_tags: Option<Vec<String>>,
+}
+
+/// Optional serve settings used to parse command line arguments - mirrors
+/// [ServeSettings].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default, Parser)]
+struct CliServeSettings {
+    /// Serve the output directory after the build.
+    #[arg(long = "serve")]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub enabled: bool,
+    /// Port the preview server listens on.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+}
+
+/// Optional external link settings used to parse command line arguments -
+/// mirrors [ExternalLinkSettings].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default, Parser)]
+struct CliExternalLinkSettings {
+    /// Request every linked external url and report the dead ones.
+    #[arg(id = "check_external", long = "check-external")]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub enabled: bool,
 }
 
 /// Configurable application settings which get derived from command line
 /// arguments and the `Config.toml`.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub struct Settings {
     /// Settings related to the paths of input files or assets and the like.
     pub path: PathSettings,
+    /// Settings related to the interpretation of the note content.
+    pub content: ContentSettings,
+    /// Settings related to the build of the website.
+    pub build: BuildSettings,
+    /// Settings describing the website.
+    pub site: SiteSettings,
+    /// Settings related to the RSS and JSON feeds.
+    pub feed: FeedSettings,
+    /// Settings related to the sitemap.
+    pub sitemap: SitemapSettings,
+    /// Settings related to the `llms.txt`.
+    pub llms_txt: LlmsTxtSettings,
+    /// Settings related to the PDF export of the notes.
+    pub pdf: PdfSettings,
+    /// Settings related to the variants of the copied images.
+    pub images: ImageSettings,
+    /// Settings related to the `robots.txt`.
+    pub robots: RobotsSettings,
+    /// Settings related to which notes get built.
+    pub filter: FilterSettings,
+    /// Settings related to the navigation.
+    pub navigation: NavigationSettings,
+    /// Settings related to the local preview server.
+    pub serve: ServeSettings,
+    /// Settings related to the check of external links.
+    pub external_links: ExternalLinkSettings,
+    /// Settings related to the content map.
+    pub content_map: ContentMapSettings,
+    /// Settings related to the validation of the notes.
+    pub validation: ValidationSettings,
 }
 
 /// Command line arguments - mirrors [Settings] structure.
@@ -76,13 +1173,64 @@ pub struct Settings {
 #[command(about = "Building a cute digital garden.")]
 #[command(version)]
 struct Args {
-    /// Config file path.
+    /// Config file path, `none` ignores config files entirely.
     #[arg(short, long, default_value = CONFIG_PATH)]
     #[serde(skip)]
     config: String,
+    /// Ignore config files entirely, same as `--config none`.
+    #[arg(long)]
+    #[serde(skip)]
+    no_config: bool,
+    /// Print the effective settings as TOML and exit without building.
+    #[arg(long)]
+    #[serde(skip)]
+    print_config: bool,
+    /// Scaffold a project with notes, templates, assets and a `Config.toml`
+    /// in the current directory and exit. Existing files are only
+    /// overwritten with `--force`.
+    #[arg(long)]
+    #[serde(skip)]
+    init: bool,
     /// Path settings.
     #[command(flatten)]
     path: CliPathSettings,
+    /// Build settings.
+    #[command(flatten)]
+    build: CliBuildSettings,
+    /// Content settings.
+    #[command(flatten)]
+    content: CliContentSettings,
+    /// Filter settings.
+    #[command(flatten)]
+    filter: CliFilterSettings,
+    /// Serve settings.
+    #[command(flatten)]
+    serve: CliServeSettings,
+    /// External link settings.
+    #[command(flatten)]
+    external_links: CliExternalLinkSettings,
+}
+
+/// Ensures the path points to an existing directory, otherwise names the
+/// command line flag and config key used to configure it.
+pub fn ensure_directory(path: &Path, flag: &str, key: &str) -> Result<(), Error> {
+    if path.is_dir() {
+        return Ok(());
+    }
+
+    let problem = if path.exists() {
+        "is not a directory"
+    } else {
+        "does not exist"
+    };
+
+    Err(anyhow!(
+        "The directory {} {}, configure it using `{}` or `{}` in the config file",
+        path.display(),
+        problem,
+        flag,
+        key
+    ))
 }
 
 /// Read Settings from `Config.toml` or command line arguments.
@@ -90,7 +1238,7 @@ fn merge_settings(
     default: Config,
     file: Option<Config>,
     args: Option<Config>,
-) -> Result<Settings, Error> {
+) -> Result<Settings, config::ConfigError> {
     let mut raw_settings = Config::builder().add_source(default);
     if let Some(file) = file {
         raw_settings = raw_settings.add_source(file);
@@ -99,7 +1247,16 @@ fn merge_settings(
         raw_settings = raw_settings.add_source(args);
     };
 
-    Ok(raw_settings.build()?.try_deserialize::<Settings>()?)
+    raw_settings.build()?.try_deserialize::<Settings>()
+}
+
+/// What the command line asks to do.
+#[derive(Debug)]
+pub enum Command {
+    /// Build the website with the loaded settings.
+    Build(Box<Settings>),
+    /// Scaffold a project into the current directory.
+    Init { force: bool },
 }
 
 /// Loads the configured settings from either `Config.toml` or the command line
@@ -107,28 +1264,86 @@ fn merge_settings(
 /// - If both are set the command line arguments overwrites the settings from
 ///   the `Config.toml`.
 /// - If neither are set the default settings are used.
-pub fn get_settings() -> Settings {
+///
+/// With `--print-config` the settings are printed instead and the process
+/// exits. With `--init` no settings are loaded, the project is to be
+/// scaffolded instead.
+pub fn get_command() -> Command {
     let args = Args::parse();
+    if args.init {
+        return Command::Init {
+            force: args.build.force,
+        };
+    }
+    let settings = load_settings(&args);
+
+    if args.print_config {
+        match render_config(&settings) {
+            Ok(config) => print!("{config}"),
+            Err(err) => {
+                log::error!("Could not render the settings: {err}");
+                process::exit(1);
+            }
+        }
+        process::exit(0);
+    }
+
+    Command::Build(Box::new(settings))
+}
+
+/// Renders the settings as TOML, usable as a `Config.toml`. Secret settings
+/// are masked.
+pub fn render_config(settings: &Settings) -> Result<String, Error> {
+    let mut config = toml::Table::try_from(settings)?;
+    for key in SECRET_SETTINGS {
+        redact(&mut config, key);
+    }
+
+    Ok(toml::to_string_pretty(&config)?)
+}
+
+/// Replaces the value of the dotted key, if it's set.
+fn redact(config: &mut toml::Table, key: &str) {
+    match key.split_once('.') {
+        Some((table, key)) => {
+            if let Some(toml::Value::Table(table)) = config.get_mut(table) {
+                redact(table, key);
+            }
+        }
+        None => {
+            if let Some(value) = config.get_mut(key) {
+                *value = toml::Value::from(REDACTED);
+            }
+        }
+    }
+}
+
+fn load_settings(args: &Args) -> Settings {
     // Interpret default settings.
     let config_default = Config::try_from(&Settings::default())
         .map_err(|err| log::error!("Could not interpret the default settings as config: {err}"))
         .ok();
-    // Load and interpret config file.
-    let config_file = Config::builder()
-        .add_source(File::with_name(&args.config).required(false))
-        .build()
-        .map_err(|err| log::error!("Could not interpret config file: {err}"))
-        .ok();
+    // Load and interpret config file, unless it's disabled.
+    let config_file = if args.no_config || args.config == NO_CONFIG {
+        log::info!("Ignoring config files.");
+        None
+    } else {
+        Config::builder()
+            .add_source(File::with_name(&args.config).required(false))
+            .build()
+            .map_err(|err| log::error!("Could not interpret config file: {err}"))
+            .ok()
+    };
     // Interpret cli arguments.
-    let config_args = Config::try_from(&args)
+    let config_args = Config::try_from(args)
         .map_err(|err| log::error!("Could not interpret cli arguments: {err}"))
         .ok();
     // If we have a default config, try to merge everything.
     if let Some(default) = config_default {
-        if let Ok(settings) = merge_settings(default, config_file, config_args) {
-            return settings;
+        match merge_settings(default, config_file, config_args) {
+            Ok(settings) => return settings,
+            Err(err) => log::error!("Could not merge settings: {:#}", Error::from(err)),
         }
-        log::error!("Could not merge settings.");
     }
     log::info!(
         "Could not load settings from config file or command line arguments, using default settings instead."
@@ -151,7 +1366,9 @@ mod tests {
                 output: DEFAULT_OUTPUT_PATH.into(),
                 assets: vec![DEFAULT_ASSET_PATH.into()],
                 template: DEFAULT_TEMPLATE_PATH.into(),
+                volatile: DEFAULT_VOLATILE_PATH.into(),
             },
+            ..Default::default()
         };
         let default_settings = Config::try_from(&Settings::default()).unwrap();
         let config_file = Config::builder()
@@ -163,6 +1380,40 @@ mod tests {
         assert_eq!(expected, produced);
     }
 
+    #[test]
+    fn test_invalid_clip_pattern_fails_to_load() {
+        let default_settings = Config::try_from(&Settings::default()).unwrap();
+        let config_file = Config::builder()
+            .add_source(File::from_str(
+                "[[content.clip]]\nlevel = 2\nheading = '(unclosed'\nmatching = 'regex'",
+                FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+
+        let err = merge_settings(default_settings, Some(config_file), None).unwrap_err();
+
+        let err = format!("{:#}", Error::from(err));
+        assert!(err.contains("Invalid clip pattern \"(unclosed\""), "{err}");
+    }
+
+    #[test]
+    fn test_invalid_filename_date_pattern_fails_to_load() {
+        let default_settings = Config::try_from(&Settings::default()).unwrap();
+        let config_file = Config::builder()
+            .add_source(File::from_str(
+                "[content]\nfilename_date_pattern = '(unclosed'",
+                FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+
+        let err = merge_settings(default_settings, Some(config_file), None).unwrap_err();
+
+        let err = format!("{:#}", Error::from(err));
+        assert!(err.contains("Invalid pattern \"(unclosed\""), "{err}");
+    }
+
     #[test]
     fn test_merge_defualt_settings_with_args() {
         let expected = Settings {
@@ -171,7 +1422,9 @@ mod tests {
                 output: DEFAULT_OUTPUT_PATH.into(),
                 assets: vec![DEFAULT_ASSET_PATH.into()],
                 template: DEFAULT_TEMPLATE_PATH.into(),
+                volatile: DEFAULT_VOLATILE_PATH.into(),
             },
+            ..Default::default()
         };
         let default_settings = Config::try_from(&Settings::default()).unwrap();
         let args = Args::try_parse_from(["post_notes", "-i", "../notes"]).unwrap();
@@ -180,4 +1433,203 @@ mod tests {
 
         assert_eq!(expected, produced);
     }
+
+    #[test]
+    fn test_no_config_ignores_config_file() {
+        let config_dir = tempfile::tempdir().unwrap();
+        let config_path = config_dir.path().join("Config.toml");
+        std::fs::write(&config_path, "[path]\noutput='./distinctive'").unwrap();
+        let config_path = config_path.to_str().unwrap();
+
+        let args = Args::try_parse_from(["post_notes", "-c", config_path]).unwrap();
+        assert_eq!(
+            PathBuf::from("./distinctive"),
+            load_settings(&args).path.output
+        );
+
+        let args = Args::try_parse_from(["post_notes", "-c", config_path, "--no-config"]).unwrap();
+        assert_eq!(
+            PathBuf::from(DEFAULT_OUTPUT_PATH),
+            load_settings(&args).path.output
+        );
+
+        let args = Args::try_parse_from(["post_notes", "--config", "none", "-o", "./cli"]).unwrap();
+        assert_eq!(PathBuf::from("./cli"), load_settings(&args).path.output);
+    }
+
+    #[test]
+    fn test_print_config_reflects_cli_overrides() {
+        let args = Args::try_parse_from([
+            "post_notes",
+            "--no-config",
+            "--print-config",
+            "-o",
+            "./cli",
+            "--force",
+        ])
+        .unwrap();
+        assert!(args.print_config);
+
+        let produced = render_config(&load_settings(&args)).unwrap();
+
+        assert!(produced.contains("output = \"./cli\""));
+        assert!(produced.contains("force = true"));
+        let mut expected = Settings::default();
+        expected.path.output = PathBuf::from("./cli");
+        expected.build.force = true;
+        let config = Config::builder()
+            .add_source(File::from_str(&produced, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+        assert_eq!(expected, config.try_deserialize::<Settings>().unwrap());
+    }
+
+    #[test]
+    fn test_redact_secret_settings() {
+        let mut config = toml::Table::try_from(Settings::default()).unwrap();
+
+        redact(&mut config, "site.title");
+        redact(&mut config, "site.missing");
+
+        assert_eq!(
+            Some(&toml::Value::from(REDACTED)),
+            config["site"].get("title")
+        );
+        assert_eq!(None, config["site"].get("missing"));
+    }
+
+    #[test]
+    fn test_merge_default_settings_with_tag_filter_args() {
+        let default_settings = Config::try_from(&Settings::default()).unwrap();
+        let args = Args::try_parse_from([
+            "post_notes",
+            "--only-tag",
+            "blog",
+            "--only-tag",
+            "rust",
+            "--exclude-tag",
+            "draft",
+        ])
+        .unwrap();
+        let config_args = Config::try_from(&args).unwrap();
+        let produced = merge_settings(default_settings, None, Some(config_args)).unwrap();
+
+        assert_eq!(
+            FilterSettings {
+                only_tags: vec!["blog".to_string(), "rust".to_string()],
+                exclude_tags: vec!["draft".to_string()],
+            },
+            produced.filter
+        );
+    }
+
+    #[test]
+    fn test_merge_default_settings_with_progress_arg() {
+        let default_settings = Config::try_from(&Settings::default()).unwrap();
+        let args = Args::try_parse_from(["post_notes", "--progress"]).unwrap();
+        let config_args = Config::try_from(&args).unwrap();
+        let produced = merge_settings(default_settings, None, Some(config_args)).unwrap();
+
+        assert!(produced.build.progress);
+    }
+
+    #[test]
+    fn test_merge_default_settings_with_clear_cache_arg() {
+        let default_settings = Config::try_from(&Settings::default()).unwrap();
+        let args = Args::try_parse_from(["post_notes", "--clear-cache"]).unwrap();
+        let config_args = Config::try_from(&args).unwrap();
+        let produced = merge_settings(default_settings, None, Some(config_args)).unwrap();
+
+        assert!(produced.build.clear_cache);
+    }
+
+    #[test]
+    fn test_merge_default_settings_with_media_directories() {
+        let default_settings = Config::try_from(&Settings::default()).unwrap();
+        let config_file = Config::builder()
+            .add_source(File::from_str(
+                "[content]\nmedia_directories=['attachments', 'img']",
+                FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+        let produced = merge_settings(default_settings, Some(config_file), None).unwrap();
+
+        assert_eq!(
+            vec!["attachments".to_string(), "img".to_string()],
+            produced.content.media_directories
+        );
+    }
+
+    #[test]
+    fn test_merge_default_settings_with_date_source_priority() {
+        let default_settings = Config::try_from(&Settings::default()).unwrap();
+        let config_file = Config::builder()
+            .add_source(File::from_str(
+                "[content]\ndate_source_priority=['modified_time', 'front_matter']",
+                FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+        let produced = merge_settings(default_settings, Some(config_file), None).unwrap();
+
+        assert_eq!(
+            vec![DateSource::ModifiedTime, DateSource::FrontMatter],
+            produced.content.date_source_priority
+        );
+    }
+
+    #[test]
+    fn test_merge_default_settings_with_unknown_render_option() {
+        let default_settings = Config::try_from(&Settings::default()).unwrap();
+        let config_file = Config::builder()
+            .add_source(File::from_str(
+                "[content.render]\nhardbreak=true",
+                FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+
+        assert!(merge_settings(default_settings, Some(config_file), None).is_err());
+    }
+
+    #[test]
+    fn test_merge_default_settings_with_base_url() {
+        let default_settings = Config::try_from(&Settings::default()).unwrap();
+        let config_file = Config::builder()
+            .add_source(File::from_str(
+                "[site]\nbase_url='https://example.com/'\n[feed]\nper_tag=true",
+                FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+        let produced = merge_settings(default_settings, Some(config_file), None).unwrap();
+
+        assert_eq!(
+            Some("https://example.com/".to_string()),
+            produced.site.base_url
+        );
+        assert!(produced.feed.per_tag);
+    }
+
+    #[test]
+    fn test_front_matter_settings_share_one_table() {
+        let default_settings = Config::try_from(&Settings::default()).unwrap();
+        let config_file = Config::builder()
+            .add_source(File::from_str(
+                "[content.front_matter]
+decode_entities=true
+defaults={ public=true }",
+                FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+        let produced = merge_settings(default_settings, Some(config_file), None).unwrap();
+
+        assert!(produced.content.front_matter.decode_entities);
+        assert_eq!(
+            BTreeMap::from([("public".to_string(), serde_yaml::Value::Bool(true))]),
+            produced.content.front_matter.defaults
+        );
+    }
 }
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use anyhow::Context as _;
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use serde::Serialize;
+use tera::{Context, Tera};
+
+use crate::date::parse_date;
+use crate::post_note::{InternalLink, PostNote, URL_ATTRIBUTE_RE, heading_id};
+use crate::retry;
+use crate::settings::{HeadingIdScope, Settings};
+
+/// File name of the export containing all notes.
+pub const SINGLE_PAGE_FILE: &str = "all.html";
+/// Name of the template the export gets rendered with.
+const SINGLE_PAGE_TEMPLATE: &str = "single_page.html";
+/// Minimal template used if the template directory doesn't provide a
+/// [SINGLE_PAGE_TEMPLATE].
+const BUILTIN_SINGLE_PAGE_TEMPLATE: &str = include_str!("builtin/single_page.html");
+
+static ID_ATTRIBUTE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\bid="([^"]*)""#).expect("Invalid id attribute pattern"));
+
+/// A note as exposed to the [SINGLE_PAGE_TEMPLATE].
+#[derive(Debug, Serialize)]
+struct SinglePageNote<'a> {
+    /// Id of the note within the page, which also prefixes all of its ids.
+    slug: String,
+    title: &'a str,
+    description: &'a str,
+    html_content: String,
+}
+
+/// Writes all notes, oldest first, into a single `all.html` for offline
+/// reading.
+///
+/// Heading and block ids are prefixed with the slug of their note, so they
+/// are unique within the page, and links between notes point to the
+/// corresponding anchors instead of the separate pages.
+///
+/// # Errors
+///
+/// Returns an error if the template can't be rendered or the file can't be
+/// written.
+pub fn write_single_page(
+    notes: &[PostNote],
+    tera: &mut Tera,
+    settings: &Settings,
+) -> anyhow::Result<()> {
+    if !tera
+        .get_template_names()
+        .any(|name| name == SINGLE_PAGE_TEMPLATE)
+    {
+        tera.add_raw_template(SINGLE_PAGE_TEMPLATE, BUILTIN_SINGLE_PAGE_TEMPLATE)?;
+    }
+
+    let mut notes = notes.iter().collect::<Vec<&PostNote>>();
+    notes.sort_by_cached_key(|note| (parse_date(&note.properties.created), note.file_name.clone()));
+
+    let slugs = notes
+        .iter()
+        .map(|note| (note.file_name.target(), (page_slug(&note.file_name), *note)))
+        .collect::<HashMap<&str, (String, &PostNote)>>();
+    let base_path = settings.site.base_path.as_deref();
+    let notes = notes
+        .iter()
+        .map(|note| SinglePageNote {
+            slug: slugs[note.file_name.target()].0.clone(),
+            title: &note.properties.title,
+            description: &note.properties.description,
+            html_content: combine_note(note, &slugs, base_path, settings.content.heading_ids),
+        })
+        .collect::<Vec<SinglePageNote>>();
+
+    let mut context = Context::new();
+    context.insert("site", &settings.site);
+    context.insert(
+        "root",
+        &InternalLink::from(SINGLE_PAGE_FILE.to_string()).root_path(base_path),
+    );
+    context.insert("notes", &notes);
+
+    let page = tera
+        .render(SINGLE_PAGE_TEMPLATE, &context)
+        .context("Could not render the single page export")?;
+    let path = settings.path.output.join(SINGLE_PAGE_FILE);
+    retry::write(&settings.build.retry, &path, page)?;
+    log::info!("Created the single page export at: {}", path.display());
+
+    Ok(())
+}
+
+/// Id of the note within the single page, e.g. `guides-setup` for
+/// `guides/setup.html`.
+fn page_slug(link: &InternalLink) -> String {
+    let target = link.target();
+    target
+        .strip_suffix(".html")
+        .unwrap_or(target)
+        .trim_end_matches('/')
+        .replace('/', "-")
+}
+
+/// Prefixes the ids of the note with its slug and rewrites its links so they
+/// resolve within the single page at the output root.
+fn combine_note(
+    note: &PostNote,
+    slugs: &HashMap<&str, (String, &PostNote)>,
+    base_path: Option<&str>,
+    heading_ids: HeadingIdScope,
+) -> String {
+    let (slug, _) = &slugs[note.file_name.target()];
+    let root = note.file_name.root_path(base_path);
+
+    let html = ID_ATTRIBUTE_RE.replace_all(&note.html_content, |caps: &regex::Captures| {
+        format!("id=\"{slug}--{}\"", &caps[1])
+    });
+
+    URL_ATTRIBUTE_RE
+        .replace_all(&html, |caps: &regex::Captures| {
+            let url = &caps[2];
+            if let Some(anchor) = url.strip_prefix('#') {
+                return format!("{}=\"#{slug}--{anchor}\"", &caps[1]);
+            }
+            let Some(relative) = url.strip_prefix(root.as_str()) else {
+                return caps[0].to_string();
+            };
+
+            let (target, anchor) = relative.split_once('#').unwrap_or((relative, ""));
+            let url = match (slugs.get(target), anchor) {
+                (Some((target_slug, _)), "") => format!("#{target_slug}"),
+                (Some((target_slug, target)), anchor) => {
+                    format!("#{target_slug}--{}", anchor_id(target, anchor, heading_ids))
+                }
+                (None, _) => format!("./{relative}"),
+            };
+
+            format!("{}=\"{url}\"", &caps[1])
+        })
+        .into_owned()
+}
+
+/// Id an anchor of a link to the note refers to: block ids are used as they
+/// are, headings like `Getting%20Started` get the id of the heading.
+fn anchor_id(note: &PostNote, anchor: &str, heading_ids: HeadingIdScope) -> String {
+    if anchor.starts_with('^') {
+        return anchor.to_string();
+    }
+
+    heading_id(
+        &note.source_path,
+        &percent_decode_str(anchor).decode_utf8_lossy(),
+        heading_ids,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post_note::{parse_public, relocate_notes};
+    use std::fs;
+
+    #[test]
+    fn test_single_page_contains_all_notes() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let mut notes = vec![
+            parse_public(
+                "setup.md",
+                "---\ntitle: Setup\ndescription: Description\ncategory: guides\ntags: []\ncreated: 2025-05-24T13:35\npublic: true\n---\n## Install\n\n![](./media/install.png)",
+            ),
+            parse_public(
+                "intro.md",
+                "---\ntitle: Intro\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\nSee [[setup#Install]].",
+            ),
+        ];
+        relocate_notes(&mut notes, true, false, None, None);
+        let mut settings = Settings::default();
+        settings.path.output = output_dir.path().to_path_buf();
+
+        write_single_page(&notes, &mut Tera::default(), &settings).unwrap();
+
+        let produced = fs::read_to_string(output_dir.path().join(SINGLE_PAGE_FILE)).unwrap();
+        assert!(produced.contains("<h1>Intro</h1>"));
+        assert!(produced.contains("<h1>Setup</h1>"));
+        assert!(produced.find("<h1>Intro</h1>") < produced.find("<h1>Setup</h1>"));
+        assert!(produced.contains("<a href=\"#guides-setup\">Setup</a>"));
+        assert!(produced.contains("href=\"#guides-setup--install\""));
+        assert!(produced.contains("<h2 id=\"guides-setup--install\">Install</h2>"));
+        assert!(produced.contains("src=\"./media/install.png\""));
+    }
+}
@@ -0,0 +1,155 @@
+use std::path::Path;
+
+use crate::date::parse_date;
+use crate::escape::escape_xml;
+use crate::feed::absolute_url;
+use crate::post_note::PostNote;
+use crate::retry;
+use crate::settings::{RobotsSettings, Settings};
+
+pub const SITEMAP_FILE: &str = "sitemap.xml";
+pub const ROBOTS_FILE: &str = "robots.txt";
+
+/// Writes the sitemap and the `robots.txt` referencing it.
+///
+/// The sitemap requires absolute links, so it only gets written if a base url
+/// is configured.
+///
+/// # Errors
+///
+/// Returns an error if one of the files could not be written.
+pub fn write_sitemap(
+    notes: &[PostNote],
+    settings: &Settings,
+    output_path: &Path,
+) -> anyhow::Result<()> {
+    let base_url = settings.site.base_url.as_deref();
+
+    if settings.sitemap.enabled {
+        match base_url {
+            Some(base_url) => {
+                let path = output_path.join(SITEMAP_FILE);
+                retry::write(
+                    &settings.build.retry,
+                    &path,
+                    render_sitemap(base_url, notes),
+                )?;
+                log::info!("Created the sitemap at: {}", path.display());
+            }
+            None => log::warn!("Skipping sitemap, because no base url is configured."),
+        }
+    }
+
+    if settings.robots.enabled {
+        let sitemap_url = base_url
+            .filter(|_| settings.sitemap.enabled)
+            .map(|base_url| absolute_url(base_url, SITEMAP_FILE));
+        let path = output_path.join(ROBOTS_FILE);
+
+        retry::write(
+            &settings.build.retry,
+            &path,
+            render_robots(&settings.robots, sitemap_url.as_deref()),
+        )?;
+        log::info!("Created the robots.txt at: {}", path.display());
+    }
+
+    Ok(())
+}
+
+fn render_sitemap(base_url: &str, notes: &[PostNote]) -> String {
+    let mut notes = notes
+        .iter()
+        .filter(|note| note.is_published())
+        .collect::<Vec<&PostNote>>();
+    notes.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+    let urls = notes
+        .into_iter()
+        .map(|note| {
+            let last_modified = note
+                .properties
+                .modified
+                .as_deref()
+                .and_then(parse_date)
+                .or_else(|| parse_date(&note.properties.created))
+                .map(|date| format!("<lastmod>{}</lastmod>\n", date.format("%Y-%m-%d")))
+                .unwrap_or_default();
+
+            format!(
+                "<url>\n<loc>{}</loc>\n{}</url>\n",
+                escape_xml(&absolute_url(base_url, &note.file_name)),
+                last_modified
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n\
+         {}\
+         </urlset>\n",
+        urls
+    )
+}
+
+fn render_robots(settings: &RobotsSettings, sitemap_url: Option<&str>) -> String {
+    let mut robots = String::from("User-agent: *\n");
+
+    if settings.allow.is_empty() && settings.disallow.is_empty() {
+        robots.push_str("Allow: /\n");
+    }
+    for path in &settings.allow {
+        robots.push_str(&format!("Allow: {}\n", path));
+    }
+    for path in &settings.disallow {
+        robots.push_str(&format!("Disallow: {}\n", path));
+    }
+    if let Some(sitemap_url) = sitemap_url {
+        robots.push_str(&format!("\nSitemap: {}\n", sitemap_url));
+    }
+
+    robots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    #[test]
+    fn test_robots_references_sitemap_with_base_url() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let mut settings = Settings::default();
+        settings.site.base_url = Some("https://example.com/notes/".to_string());
+        settings.robots.enabled = true;
+        settings.robots.disallow = vec!["/private/".to_string()];
+
+        write_sitemap(&[], &settings, output_dir.path()).unwrap();
+
+        assert_eq!(
+            "User-agent: *\nDisallow: /private/\n\nSitemap: https://example.com/notes/sitemap.xml\n",
+            fs::read_to_string(output_dir.path().join(ROBOTS_FILE)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_robots_without_base_url() {
+        let output_dir = tempfile::tempdir().unwrap();
+
+        write_sitemap(&[], &Settings::default(), output_dir.path()).unwrap();
+
+        assert!(!output_dir.path().join(ROBOTS_FILE).exists());
+
+        let mut settings = Settings::default();
+        settings.robots.enabled = true;
+        write_sitemap(&[], &settings, output_dir.path()).unwrap();
+
+        assert_eq!(
+            "User-agent: *\nAllow: /\n",
+            fs::read_to_string(output_dir.path().join(ROBOTS_FILE)).unwrap()
+        );
+        assert!(!output_dir.path().join(SITEMAP_FILE).exists());
+    }
+}
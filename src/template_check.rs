@@ -0,0 +1,228 @@
+use std::collections::HashSet;
+
+use tera::ast::{Expr, ExprVal, Node};
+use tera::{Context, Tera, Value, dotted_pointer};
+
+/// Returns the variables the template reads which aren't defined in the
+/// context, like `note.propertis.title`, including the ones read by the
+/// templates it extends or includes.
+///
+/// Tera only fails on undefined variables which get printed, in conditions,
+/// tests and `default` filters they are silently treated as missing. Only
+/// variables guarded by `is defined` or a `default` filter are accepted here.
+pub fn undefined_variables(tera: &Tera, template: &str, context: &Context) -> Vec<String> {
+    let mut checker = Checker {
+        tera,
+        context: context.clone().into_json(),
+        locals: Vec::new(),
+        globals: Vec::new(),
+        visited: HashSet::new(),
+        undefined: Vec::new(),
+    };
+    checker.visit_template(template);
+
+    checker.undefined
+}
+
+struct Checker<'a> {
+    tera: &'a Tera,
+    context: Value,
+    /// Variables bound by the enclosing loops and `set` tags.
+    locals: Vec<String>,
+    /// Variables bound by `set_global` tags.
+    globals: Vec<String>,
+    visited: HashSet<String>,
+    undefined: Vec<String>,
+}
+
+impl Checker<'_> {
+    fn visit_template(&mut self, name: &str) {
+        if !self.visited.insert(name.to_string()) {
+            return;
+        }
+        let tera = self.tera;
+        let Ok(template) = tera.get_template(name) else {
+            return;
+        };
+
+        self.visit_nodes(&template.ast);
+        for parent in &template.parents {
+            self.visit_template(parent);
+        }
+    }
+
+    fn visit_nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::VariableBlock(_, expr) => self.visit_expr(expr),
+                Node::Set(_, set) => {
+                    self.visit_expr(&set.value);
+                    if set.global {
+                        self.globals.push(set.key.clone());
+                    } else {
+                        self.locals.push(set.key.clone());
+                    }
+                }
+                Node::Include(_, names, _) => {
+                    for name in names {
+                        self.visit_template(name);
+                    }
+                }
+                Node::FilterSection(_, section, _) => {
+                    section
+                        .filter
+                        .args
+                        .values()
+                        .for_each(|arg| self.visit_expr(arg));
+                    self.visit_nodes(&section.body);
+                }
+                Node::Block(_, block, _) => self.visit_nodes(&block.body),
+                Node::Forloop(_, forloop, _) => {
+                    self.visit_expr(&forloop.container);
+
+                    let scope = self.locals.len();
+                    self.locals.extend(forloop.key.iter().cloned());
+                    self.locals.push(forloop.value.clone());
+                    self.locals.push("loop".to_string());
+                    self.visit_nodes(&forloop.body);
+                    if let Some(empty_body) = &forloop.empty_body {
+                        self.visit_nodes(empty_body);
+                    }
+                    self.locals.truncate(scope);
+                }
+                Node::If(if_, _) => {
+                    for (_, condition, body) in &if_.conditions {
+                        self.visit_expr(condition);
+
+                        // Within `{% if x is defined %}` x can be used freely.
+                        let scope = self.locals.len();
+                        if let ExprVal::Test(test) = &condition.val
+                            && test.name == "defined"
+                            && !test.negated
+                            && !condition.negated
+                        {
+                            self.locals.push(test.ident.clone());
+                        }
+                        self.visit_nodes(body);
+                        self.locals.truncate(scope);
+                    }
+                    if let Some((_, body)) = &if_.otherwise {
+                        self.visit_nodes(body);
+                    }
+                }
+                // Macro arguments are only known once the macro gets called.
+                _ => {}
+            }
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        for filter in &expr.filters {
+            filter.args.values().for_each(|arg| self.visit_expr(arg));
+        }
+
+        let has_default = expr.filters.iter().any(|filter| filter.name == "default");
+        if has_default && matches!(expr.val, ExprVal::Ident(_)) {
+            return;
+        }
+        self.visit_value(&expr.val);
+    }
+
+    fn visit_value(&mut self, value: &ExprVal) {
+        match value {
+            ExprVal::Ident(ident) => self.check(ident),
+            ExprVal::Math(math) => {
+                self.visit_expr(&math.lhs);
+                self.visit_expr(&math.rhs);
+            }
+            ExprVal::Logic(logic) => {
+                self.visit_expr(&logic.lhs);
+                self.visit_expr(&logic.rhs);
+            }
+            ExprVal::In(in_) => {
+                self.visit_expr(&in_.lhs);
+                self.visit_expr(&in_.rhs);
+            }
+            ExprVal::Test(test) => {
+                if !matches!(test.name.as_str(), "defined" | "undefined") {
+                    self.check(&test.ident);
+                }
+                test.args.iter().for_each(|arg| self.visit_expr(arg));
+            }
+            ExprVal::FunctionCall(call) => call.args.values().for_each(|arg| self.visit_expr(arg)),
+            ExprVal::MacroCall(call) => call.args.values().for_each(|arg| self.visit_expr(arg)),
+            ExprVal::Array(items) => items.iter().for_each(|item| self.visit_expr(item)),
+            ExprVal::StringConcat(concat) => concat
+                .values
+                .iter()
+                .for_each(|value| self.visit_value(value)),
+            ExprVal::String(_) | ExprVal::Int(_) | ExprVal::Float(_) | ExprVal::Bool(_) => {}
+        }
+    }
+
+    /// Records the variable if it's neither bound by the template nor
+    /// defined in the context. Indexing with `[...]` is only checked up to
+    /// the first index.
+    fn check(&mut self, ident: &str) {
+        let path = ident.split('[').next().unwrap_or(ident);
+        let root = path.split('.').next().unwrap_or(path);
+        let is_bound = root.starts_with("__tera")
+            || self
+                .locals
+                .iter()
+                .chain(&self.globals)
+                .any(|name| name == root || is_within(path, name));
+
+        if !is_bound
+            && dotted_pointer(&self.context, path).is_none()
+            && !self.undefined.iter().any(|undefined| undefined == ident)
+        {
+            self.undefined.push(ident.to_string());
+        }
+    }
+}
+
+/// Whether the dotted path is the guarded variable or one of its fields.
+fn is_within(path: &str, guarded: &str) -> bool {
+    path.strip_prefix(guarded)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn context() -> Context {
+        let mut context = Context::new();
+        context.insert(
+            "note",
+            &serde_json::json!({ "properties": { "title": "Note", "tags": ["rust"] } }),
+        );
+        context
+    }
+
+    #[test]
+    fn test_undefined_variables() {
+        let mut tera = Tera::default();
+        tera.add_raw_templates([
+            (
+                "base.html",
+                "{% if note.propertis.title %}{{ note.properties.title }}{% endif %}{% block content %}{% endblock %}",
+            ),
+            (
+                "note.html",
+                "{% extends \"base.html\" %}{% block content %}{% for tag in note.properties.tags %}{{ tag }}{{ loop.index }}{% endfor %}{{ missing | default(value=\"\") }}{% if other is defined %}{{ other }}{% endif %}{% if note.series.name is defined %}{{ note.series.name }}{% endif %}{{ note.series.part }}{% include \"footer.html\" %}{% endblock %}",
+            ),
+            ("footer.html", "{{ site.title | upper }}"),
+        ])
+        .unwrap();
+
+        let produced = undefined_variables(&tera, "note.html", &context());
+
+        assert_eq!(
+            vec!["note.series.part", "site.title", "note.propertis.title"],
+            produced
+        );
+    }
+}
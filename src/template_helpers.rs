@@ -0,0 +1,250 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+
+use chrono::NaiveDateTime;
+use tera::{Tera, Value};
+
+use crate::date::{now, parse_date};
+use crate::feed::absolute_url;
+use crate::post_note::InternalLink;
+use crate::settings::SiteSettings;
+
+/// Maps asset paths like `css/style.css` to their fingerprinted copy like
+/// `css/style.1a2b3c4d.css`, both relative to the output directory.
+pub type AssetManifest = BTreeMap<String, String>;
+
+/// Registers the custom filters available to all templates:
+/// - `date_format(format="%b %d, %Y")` formats a stored date with a
+///   strftime-style pattern.
+/// - `relative_time` describes a stored date relative to now, like
+///   `3 days ago`.
+pub fn register_filters(tera: &mut Tera) {
+    tera.register_filter("date_format", date_format);
+    tera.register_filter("relative_time", relative_time);
+}
+
+fn date_format(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let format = args
+        .get("format")
+        .and_then(Value::as_str)
+        .ok_or("The date_format filter expects a `format` argument")?;
+
+    // Formatting with an invalid pattern fails instead of panicking when
+    // written like this.
+    let mut formatted = String::new();
+    write!(
+        formatted,
+        "{}",
+        filter_date(value, "date_format")?.format(format)
+    )
+    .map_err(|_| format!("Invalid date format {format:?}"))?;
+
+    Ok(Value::from(formatted))
+}
+
+fn relative_time(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let date = filter_date(value, "relative_time")?;
+
+    Ok(Value::from(describe_relative(date, now())))
+}
+
+/// Registers the custom functions available to all templates:
+/// - `url(note=note)` or `url(path="tags.json")` links a note or an output
+///   file.
+/// - `asset(path="css/style.css")` links an asset, using its fingerprinted
+///   copy if there is one.
+///
+/// Both take a `root` (pass `root=root` for links relative to the current
+/// note), which may only be left out if a base path or base url is configured
+/// to start the links with instead.
+pub fn register_functions(tera: &mut Tera, site: &SiteSettings, manifest: AssetManifest) {
+    let url_site = site.clone();
+    tera.register_function("url", move |args: &HashMap<String, Value>| {
+        let path = match (args.get("note"), args.get("path")) {
+            (Some(note), _) => note
+                .get("file_name")
+                .and_then(Value::as_str)
+                .ok_or("The url function expects a note with a `file_name`")?,
+            (None, Some(path)) => path
+                .as_str()
+                .ok_or("The url function expects `path` to be a string")?,
+            (None, None) => return Err("The url function expects a `note` or `path`".into()),
+        };
+
+        link_to(&url_site, args.get("root"), path).map(Value::from)
+    });
+
+    let asset_site = site.clone();
+    tera.register_function("asset", move |args: &HashMap<String, Value>| {
+        let path = args
+            .get("path")
+            .and_then(Value::as_str)
+            .ok_or("The asset function expects a `path`")?;
+        let path = path.trim_start_matches("./").trim_start_matches('/');
+        let path = manifest.get(path).map_or(path, String::as_str);
+
+        link_to(&asset_site, args.get("root"), path).map(Value::from)
+    });
+}
+
+/// Prefixes the path relative to the output directory with the given root or
+/// the configured base path or base url. Without any of them there is no way
+/// to tell how deep the current page is, so guessing a root-absolute link
+/// would break sites which aren't served from the domain root.
+fn link_to(site: &SiteSettings, root: Option<&Value>, path: &str) -> tera::Result<String> {
+    let path = path.trim_start_matches("./").trim_start_matches('/');
+
+    if let Some(root) = root.and_then(Value::as_str) {
+        return Ok(format!("{root}{path}"));
+    }
+
+    match (site.base_path.as_deref(), site.base_url.as_deref()) {
+        (Some(base_path), _) => {
+            let root = InternalLink::from(path.to_string()).root_path(Some(base_path));
+            Ok(format!("{root}{path}"))
+        }
+        (None, Some(base_url)) => Ok(absolute_url(base_url, path)),
+        (None, None) => Err(format!(
+            "Linking {path:?} needs `root=root` unless a base path or base url is configured"
+        )
+        .into()),
+    }
+}
+
+/// Parses the value a date filter was applied to.
+fn filter_date(value: &Value, filter: &str) -> tera::Result<NaiveDateTime> {
+    let raw_date = value
+        .as_str()
+        .ok_or_else(|| format!("The {filter} filter expects a string, got {value}"))?;
+
+    parse_date(raw_date)
+        .ok_or_else(|| format!("The {filter} filter could not parse the date {raw_date:?}").into())
+}
+
+/// Describes the date in the largest fitting unit, e.g. `3 days ago` or
+/// `in 2 hours`.
+fn describe_relative(date: NaiveDateTime, now: NaiveDateTime) -> String {
+    const UNITS: [(i64, &str); 6] = [
+        (365 * 24 * 60 * 60, "year"),
+        (30 * 24 * 60 * 60, "month"),
+        (7 * 24 * 60 * 60, "week"),
+        (24 * 60 * 60, "day"),
+        (60 * 60, "hour"),
+        (60, "minute"),
+    ];
+
+    let seconds = (now - date).num_seconds();
+    let Some((count, unit)) = UNITS
+        .iter()
+        .map(|(length, unit)| (seconds.abs() / length, unit))
+        .find(|(count, _)| *count > 0)
+    else {
+        return "just now".to_string();
+    };
+
+    let plural = if count == 1 { "" } else { "s" };
+    if seconds < 0 {
+        format!("in {count} {unit}{plural}")
+    } else {
+        format!("{count} {unit}{plural} ago")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tera::Context;
+
+    #[test]
+    fn test_date_format_filter() {
+        let mut tera = Tera::default();
+        register_filters(&mut tera);
+        tera.add_raw_template(
+            "note.html",
+            "{{ created | date_format(format=\"%b %d, %Y\") }}",
+        )
+        .unwrap();
+        let mut context = Context::new();
+        context.insert("created", "2024-01-15T13:35");
+
+        let produced = tera.render("note.html", &context).unwrap();
+
+        assert_eq!("Jan 15, 2024", produced);
+        assert!(
+            date_format(
+                &Value::from("2024-01-15"),
+                &HashMap::from([("format".to_string(), Value::from("%Q"))])
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_asset_resolves_to_fingerprinted_url() {
+        let site = SiteSettings {
+            base_path: Some("/notes/".to_string()),
+            ..Default::default()
+        };
+        let manifest =
+            AssetManifest::from([("style.css".to_string(), "style.1a2b3c4d.css".to_string())]);
+        let mut tera = Tera::default();
+        register_functions(&mut tera, &site, manifest);
+        tera.add_raw_template(
+            "links",
+            "{{ asset(path=\"style.css\") }} {{ asset(path=\"app.js\", root=\"../\") }} {{ url(note=note) }}",
+        )
+        .unwrap();
+        let mut context = Context::new();
+        context.insert(
+            "note",
+            &serde_json::json!({ "file_name": "guides/note.html" }),
+        );
+
+        let produced = tera.render("links", &context).unwrap();
+
+        assert_eq!(
+            "/notes/style.1a2b3c4d.css ../app.js /notes/guides/note.html",
+            produced
+        );
+    }
+
+    #[test]
+    fn test_links_without_base_path_are_relative() {
+        let mut tera = Tera::default();
+        register_functions(&mut tera, &SiteSettings::default(), AssetManifest::new());
+        tera.add_raw_templates([
+            (
+                "links",
+                "{{ asset(path=\"style.css\", root=root) }} {{ url(path=\"tags.json\", root=root) }}",
+            ),
+            ("unrooted", "{{ url(path=\"tags.json\") }}"),
+        ])
+        .unwrap();
+        let mut context = Context::new();
+        context.insert(
+            "root",
+            &InternalLink::from("guides/note.html".to_string()).root_path(None),
+        );
+
+        let produced = tera.render("links", &context).unwrap();
+
+        assert_eq!("../style.css ../tags.json", produced);
+        assert!(tera.render("unrooted", &context).is_err());
+    }
+
+    #[test]
+    fn test_describe_relative() {
+        let now = parse_date("2024-01-15T13:35").unwrap();
+
+        assert_eq!(
+            "3 days ago",
+            describe_relative(parse_date("2024-01-12T10:00").unwrap(), now)
+        );
+        assert_eq!(
+            "in 1 hour",
+            describe_relative(parse_date("2024-01-15T15:00").unwrap(), now)
+        );
+        assert_eq!("just now", describe_relative(now, now));
+    }
+}
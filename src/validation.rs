@@ -0,0 +1,337 @@
+use std::collections::{HashMap, HashSet};
+
+use derive_more::Display;
+use percent_encoding::percent_decode_str;
+
+use crate::post_note::{InternalLink, PostNote, heading_id};
+use crate::settings::{HeadingIdScope, LinkClassSettings, PrivateLinkPolicy, ValidationSettings};
+
+/// A problem found while validating the links between notes.
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+pub enum LinkIssue {
+    /// The linked note doesn't exist (or isn't public).
+    #[display("{} links to the missing note {}", &**source, &**link)]
+    MissingNote {
+        source: InternalLink,
+        link: InternalLink,
+    },
+    /// The linked note exists, but is private.
+    #[display("{} links to the private note {}", &**source, &**link)]
+    PrivateNote {
+        source: InternalLink,
+        link: InternalLink,
+    },
+    /// The linked note exists, but has no block with the linked id.
+    #[display("{} links to the unknown block {}", &**source, &**link)]
+    UnknownBlock {
+        source: InternalLink,
+        link: InternalLink,
+    },
+    /// The linked note exists, but has no heading with the linked anchor.
+    #[display("{} links to the unknown heading {}", &**source, &**link)]
+    UnknownHeading {
+        source: InternalLink,
+        link: InternalLink,
+    },
+}
+
+/// Checks that every internal link points to a loaded note and, for block
+/// references like `[[note#^block-id]]`, to an existing block of that note.
+/// If enabled, links to headings like `[[note#Setup]]` have to point to an
+/// existing heading as well, whose ids are scoped like `heading_ids`. Links
+/// to private notes are reported separately from links to missing ones.
+/// Every issue found is logged as a warning.
+pub fn validate_links(
+    notes: &[PostNote],
+    private_notes: &HashSet<InternalLink>,
+    settings: &ValidationSettings,
+    heading_ids: HeadingIdScope,
+) -> Vec<LinkIssue> {
+    let notes_by_link = notes
+        .iter()
+        .map(|note| (&*note.file_name, note))
+        .collect::<HashMap<&str, &PostNote>>();
+
+    let mut issues = Vec::new();
+
+    for note in notes {
+        for link in &note.internal_links {
+            let issue = match notes_by_link.get(link.target()) {
+                None if private_notes.contains(link.target()) => Some(LinkIssue::PrivateNote {
+                    source: note.file_name.clone(),
+                    link: link.clone(),
+                }),
+                None => Some(LinkIssue::MissingNote {
+                    source: note.file_name.clone(),
+                    link: link.clone(),
+                }),
+                Some(target) => match link.anchor() {
+                    Some(block_id)
+                        if block_id.starts_with('^')
+                            && !target.block_ids.iter().any(|id| id == block_id) =>
+                    {
+                        Some(LinkIssue::UnknownBlock {
+                            source: note.file_name.clone(),
+                            link: link.clone(),
+                        })
+                    }
+                    Some(anchor)
+                        if settings.heading_anchors
+                            && !anchor.starts_with('^')
+                            && !has_heading(target, anchor, heading_ids) =>
+                    {
+                        Some(LinkIssue::UnknownHeading {
+                            source: note.file_name.clone(),
+                            link: link.clone(),
+                        })
+                    }
+                    _ => None,
+                },
+            };
+
+            if let Some(issue) = issue {
+                log::warn!("{}", issue);
+                issues.push(issue);
+            }
+        }
+    }
+
+    issues
+}
+
+/// Whether the note has a heading the (percent-encoded) anchor refers to.
+fn has_heading(note: &PostNote, anchor: &str, scope: HeadingIdScope) -> bool {
+    let heading = percent_decode_str(anchor).decode_utf8_lossy();
+
+    note.heading_ids
+        .contains(&heading_id(&note.source_path, &heading, scope))
+}
+
+/// Removes all links to private notes, only their text is kept.
+pub fn strip_private_links(notes: &mut [PostNote], private_notes: &HashSet<InternalLink>) {
+    if private_notes.is_empty() {
+        return;
+    }
+
+    for note in notes {
+        note.strip_links(private_notes);
+    }
+}
+
+/// Marks every link to another note with the class of existing or missing
+/// notes. Has to run before the notes are relocated, while the links still
+/// match the names of the notes.
+pub fn mark_internal_links(notes: &mut [PostNote], classes: &LinkClassSettings) {
+    let existing = notes
+        .iter()
+        .map(|note| note.file_name.clone())
+        .collect::<HashSet<InternalLink>>();
+
+    for note in notes {
+        note.mark_links(&existing, classes);
+    }
+}
+
+/// Fails if links to private notes were found and the policy doesn't allow
+/// them.
+pub fn enforce_private_link_policy(
+    issues: &[LinkIssue],
+    policy: PrivateLinkPolicy,
+) -> anyhow::Result<()> {
+    let private_links = issues
+        .iter()
+        .filter(|issue| matches!(issue, LinkIssue::PrivateNote { .. }))
+        .count();
+
+    if private_links > 0 && policy == PrivateLinkPolicy::Fail {
+        anyhow::bail!("Found {private_links} link(s) to private notes");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post_note;
+    use crate::settings::ContentSettings;
+    use pretty_assertions::assert_eq;
+
+    fn parse_public(file_name: &str, body: &str) -> PostNote {
+        parse_public_with(file_name, body, &ContentSettings::default())
+    }
+
+    fn parse_public_with(file_name: &str, body: &str, settings: &ContentSettings) -> PostNote {
+        let raw_md = format!(
+            "---\ntitle: Note\ndescription: Description\ntags: []\ncreated: 2025-05-23T13:35\npublic: true\n---\n{body}"
+        );
+        post_note::parse_public_with(file_name, &raw_md, settings)
+    }
+
+    #[test]
+    fn test_links_are_marked_by_existence_of_their_target() {
+        let mut notes = vec![
+            parse_public("target.md", "Content"),
+            parse_public(
+                "source.md",
+                "[[target#Intro]] [[missing]] [docs](https://docs.rs)",
+            ),
+        ];
+
+        mark_internal_links(&mut notes, &LinkClassSettings::default());
+
+        assert_eq!(
+            "<p><a href=\"target.html#Intro\" class=\"internal-link\" data-wikilink=\"true\">target#Intro</a> <a href=\"missing.html\" class=\"broken-link\" data-wikilink=\"true\">missing</a> <a href=\"https://docs.rs\">docs</a></p>",
+            &*notes[1].html_content
+        );
+    }
+
+    #[test]
+    fn test_block_references_are_validated() {
+        let notes = vec![
+            parse_public("target.md", "The intro. ^intro"),
+            parse_public(
+                "source.md",
+                "[[target#^intro]] [[target#^outro]] [[missing]]",
+            ),
+        ];
+
+        let produced = validate_links(
+            &notes,
+            &HashSet::new(),
+            &ValidationSettings::default(),
+            HeadingIdScope::Page,
+        );
+
+        assert_eq!(
+            vec![
+                LinkIssue::UnknownBlock {
+                    source: InternalLink::from("source".to_string()),
+                    link: InternalLink::from("target#^outro".to_string()),
+                },
+                LinkIssue::MissingNote {
+                    source: InternalLink::from("source".to_string()),
+                    link: InternalLink::from("missing".to_string()),
+                },
+            ],
+            produced
+        );
+    }
+
+    #[test]
+    fn test_heading_anchors_are_validated() {
+        let notes = vec![
+            parse_public("target.md", "## Getting Started\n\nThe intro."),
+            parse_public(
+                "source.md",
+                "[[target#Getting Started]] [[target#Installation]] [[missing#Setup]]",
+            ),
+        ];
+        let settings = ValidationSettings {
+            heading_anchors: true,
+            ..ValidationSettings::default()
+        };
+
+        let produced = validate_links(&notes, &HashSet::new(), &settings, HeadingIdScope::Page);
+
+        assert_eq!(
+            vec![
+                LinkIssue::UnknownHeading {
+                    source: InternalLink::from("source".to_string()),
+                    link: InternalLink::from("target#Installation".to_string()),
+                },
+                LinkIssue::MissingNote {
+                    source: InternalLink::from("source".to_string()),
+                    link: InternalLink::from("missing#Setup".to_string()),
+                },
+            ],
+            produced
+        );
+        assert_eq!(
+            1,
+            validate_links(
+                &notes,
+                &HashSet::new(),
+                &ValidationSettings::default(),
+                HeadingIdScope::Page
+            )
+            .len()
+        );
+    }
+
+    #[test]
+    fn test_heading_anchors_are_validated_against_site_wide_ids() {
+        let content = ContentSettings {
+            heading_ids: HeadingIdScope::Site,
+            ..ContentSettings::default()
+        };
+        let notes = vec![
+            parse_public_with("target.md", "## Getting Started\n\nThe intro.", &content),
+            parse_public_with(
+                "source.md",
+                "[[target#Getting%20Started]] [[target#Installation]]",
+                &content,
+            ),
+        ];
+        let settings = ValidationSettings {
+            heading_anchors: true,
+            ..ValidationSettings::default()
+        };
+
+        let produced = validate_links(&notes, &HashSet::new(), &settings, HeadingIdScope::Site);
+
+        assert_eq!(vec!["target--getting-started"], notes[0].heading_ids);
+        assert_eq!(
+            vec![LinkIssue::UnknownHeading {
+                source: InternalLink::from("source".to_string()),
+                link: InternalLink::from("target#Installation".to_string()),
+            }],
+            produced
+        );
+    }
+
+    #[test]
+    fn test_links_to_private_notes() {
+        let private_notes = HashSet::from([InternalLink::from("secret".to_string())]);
+        let mut notes = vec![parse_public(
+            "source.md",
+            "See [[secret|the secret]] and [[missing]]",
+        )];
+
+        let produced = validate_links(
+            &notes,
+            &private_notes,
+            &ValidationSettings::default(),
+            HeadingIdScope::Page,
+        );
+        assert!(enforce_private_link_policy(&produced, PrivateLinkPolicy::Warn).is_ok());
+        assert!(enforce_private_link_policy(&produced, PrivateLinkPolicy::Fail).is_err());
+        assert_eq!(
+            vec![
+                LinkIssue::PrivateNote {
+                    source: InternalLink::from("source".to_string()),
+                    link: InternalLink::from("secret".to_string()),
+                },
+                LinkIssue::MissingNote {
+                    source: InternalLink::from("source".to_string()),
+                    link: InternalLink::from("missing".to_string()),
+                },
+            ],
+            produced
+        );
+
+        strip_private_links(&mut notes, &private_notes);
+        assert_eq!(
+            "<p>See the secret and <a href=\"missing.html\" data-wikilink=\"true\">missing</a></p>",
+            notes[0].html_content.trim()
+        );
+        let produced = validate_links(
+            &notes,
+            &private_notes,
+            &ValidationSettings::default(),
+            HeadingIdScope::Page,
+        );
+        assert_eq!(1, produced.len());
+        assert!(enforce_private_link_policy(&produced, PrivateLinkPolicy::Fail).is_ok());
+    }
+}
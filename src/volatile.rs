@@ -0,0 +1,158 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::settings::PathSettings;
+
+/// Prepares the volatile directory before the first build: makes sure it
+/// neither is, contains nor lies within the notes, templates, assets or the
+/// website, wipes it if `clear` is set and creates it.
+///
+/// # Errors
+///
+/// Returns an error if the volatile directory overlaps one of the other
+/// directories or if it can't be cleared or created.
+pub fn prepare_volatile(paths: &PathSettings, clear: bool) -> Result<()> {
+    let volatile = normalize(&paths.volatile);
+    let others = [
+        ("path.input", &paths.input),
+        ("path.output", &paths.output),
+        ("path.template", &paths.template),
+    ]
+    .into_iter()
+    .chain(paths.assets.iter().map(|asset| ("path.assets", asset)));
+    for (key, other) in others {
+        let other = normalize(other);
+        if volatile.starts_with(&other) || other.starts_with(&volatile) {
+            anyhow::bail!(
+                "The volatile directory {} overlaps the directory configured as `{}`; it gets cleared, so it needs a directory of its own",
+                paths.volatile.display(),
+                key
+            );
+        }
+    }
+
+    if clear {
+        match fs::remove_dir_all(&paths.volatile) {
+            Ok(()) => log::info!(
+                "Cleared the volatile directory {}",
+                paths.volatile.display()
+            ),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Could not clear {}", paths.volatile.display()));
+            }
+        }
+    }
+
+    fs::create_dir_all(&paths.volatile)
+        .with_context(|| format!("Could not create {}", paths.volatile.display()))
+}
+
+/// The canonical path if it exists, otherwise the absolute one without `.`
+/// and `..` components, so different spellings of the same directory compare
+/// equal.
+fn normalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path).map(|path| lexical_normalize(&path)))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn lexical_normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_volatile_must_not_be_another_directory() {
+        let root = tempfile::tempdir().unwrap();
+        let mut paths = PathSettings {
+            input: root.path().join("notes"),
+            output: root.path().join("output"),
+            template: root.path().join("templates"),
+            assets: vec![root.path().join("assets")],
+            volatile: root.path().join("assets/../output"),
+        };
+        fs::create_dir(&paths.output).unwrap();
+        fs::create_dir(&paths.assets[0]).unwrap();
+
+        let err = prepare_volatile(&paths, false).unwrap_err();
+
+        assert!(err.to_string().contains("configured as `path.output`"));
+
+        paths.volatile = root.path().join("./assets");
+        let err = prepare_volatile(&paths, false).unwrap_err();
+
+        assert!(err.to_string().contains("configured as `path.assets`"));
+
+        paths.volatile = root.path().join(".post_notes");
+        prepare_volatile(&paths, false).unwrap();
+
+        assert!(paths.volatile.is_dir());
+    }
+
+    #[test]
+    fn test_volatile_must_not_contain_or_be_within_another_directory() {
+        let root = tempfile::tempdir().unwrap();
+        let notes = root.path().join("notes");
+        fs::create_dir_all(notes.join("note")).unwrap();
+        fs::write(notes.join("note.md"), "# Note").unwrap();
+        let mut paths = PathSettings {
+            input: notes.clone(),
+            output: root.path().join("output"),
+            template: root.path().join("templates"),
+            assets: vec![root.path().join("assets")],
+            volatile: root.path().to_path_buf(),
+        };
+
+        let err = prepare_volatile(&paths, true).unwrap_err();
+
+        assert!(err.to_string().contains("configured as `path.input`"));
+        assert!(notes.join("note.md").is_file());
+
+        paths.volatile = notes.join("note");
+        let err = prepare_volatile(&paths, true).unwrap_err();
+
+        assert!(err.to_string().contains("configured as `path.input`"));
+        assert!(notes.join("note.md").is_file());
+    }
+
+    #[test]
+    fn test_clear_cache_removes_cached_files() {
+        let root = tempfile::tempdir().unwrap();
+        let paths = PathSettings {
+            volatile: root.path().join(".post_notes"),
+            ..Default::default()
+        };
+        let cached = paths.volatile.join("parse-cache/note.json");
+        fs::create_dir_all(cached.parent().unwrap()).unwrap();
+        fs::write(&cached, "{}").unwrap();
+
+        prepare_volatile(&paths, false).unwrap();
+
+        assert!(cached.is_file());
+
+        prepare_volatile(&paths, true).unwrap();
+
+        assert!(!cached.exists());
+        assert_eq!(0, fs::read_dir(&paths.volatile).unwrap().count());
+    }
+}
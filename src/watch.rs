@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::{fs, thread};
+
+use tera::Tera;
+
+use crate::builder::reload_templates;
+use crate::settings::Settings;
+
+/// How often the watched directories are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Modification times of all files below a set of directories.
+type Snapshot = BTreeMap<PathBuf, SystemTime>;
+
+/// Rebuilds the website whenever a note, template or asset changes.
+///
+/// Template changes reload the templates before rebuilding, so edits take
+/// effect without restarting. Failing rebuilds are logged and watching
+/// continues.
+pub fn watch(settings: &Settings, mut tera: Tera, rebuild: impl Fn(&Tera) -> anyhow::Result<()>) {
+    let template_dirs = [settings.path.template.as_path()];
+    let content_dirs = std::iter::once(settings.path.input.as_path())
+        .chain(settings.path.assets.iter().map(PathBuf::as_path))
+        .collect::<Vec<&Path>>();
+
+    let mut templates = snapshot(&template_dirs);
+    let mut content = snapshot(&content_dirs);
+
+    log::info!("=== Watching for changes. ===");
+    loop {
+        thread::sleep(POLL_INTERVAL);
+
+        let current_templates = snapshot(&template_dirs);
+        let current_content = snapshot(&content_dirs);
+        let templates_changed = current_templates != templates;
+        if !templates_changed && current_content == content {
+            continue;
+        }
+
+        if templates_changed {
+            reload_templates(
+                &mut tera,
+                &settings.path.template,
+                settings.build.builtin_template_fallback,
+            );
+        }
+
+        println!();
+        if let Err(err) = rebuild(&tera) {
+            log::error!("Rebuild failed: {err:#}");
+        }
+
+        templates = current_templates;
+        content = current_content;
+    }
+}
+
+/// Collects the modification times of all files below the directories.
+/// Directories which can't be read are skipped.
+fn snapshot(dirs: &[&Path]) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for dir in dirs {
+        collect_modified(dir, &mut snapshot);
+    }
+
+    snapshot
+}
+
+fn collect_modified(dir: &Path, snapshot: &mut Snapshot) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+
+        if metadata.is_dir() {
+            collect_modified(&path, snapshot);
+        } else if let Ok(modified) = metadata.modified() {
+            snapshot.insert(path, modified);
+        }
+    }
+}